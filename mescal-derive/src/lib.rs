@@ -0,0 +1,316 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr, Type};
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    default: bool,
+    flatten: bool,
+}
+
+fn parse_attrs(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+    let mut res = FieldAttrs::default();
+    for attr in attrs {
+        if !attr.path().is_ident("bencode") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                let s: LitStr = meta.value()?.parse()?;
+                res.rename = Some(s.value());
+            } else if meta.path.is_ident("default") {
+                res.default = true;
+            } else if meta.path.is_ident("flatten") {
+                res.flatten = true;
+            } else {
+                return Err(meta.error("unsupported bencode attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(res)
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p.path.segments.last().map(|s| s.ident == "Option").unwrap_or(false),
+        _ => false,
+    }
+}
+
+struct NamedField {
+    ident: syn::Ident,
+    key: String,
+    attrs: FieldAttrs,
+    optional: bool,
+}
+
+fn named_fields(fields: &syn::FieldsNamed) -> syn::Result<Vec<NamedField>> {
+    fields.named.iter().map(|f| {
+        let ident = f.ident.clone().expect("named field");
+        let attrs = parse_attrs(&f.attrs)?;
+        let key = attrs.rename.clone().unwrap_or_else(|| ident.to_string());
+        Ok(NamedField { ident, key, attrs, optional: is_option(&f.ty) })
+    }).collect()
+}
+
+// `value` through to_bencode, or through try_to_bencode with `?` when `fallible`
+fn encode(value: TokenStream2, fallible: bool) -> TokenStream2 {
+    if fallible {
+        quote! { ::mescal::ToBencode::try_to_bencode(#value)? }
+    } else {
+        quote! { ::mescal::ToBencode::to_bencode(#value) }
+    }
+}
+
+// builds `__entries` from fields that are reachable through `access(ident)`
+fn push_entries(fields: &[NamedField], access: impl Fn(&syn::Ident) -> TokenStream2, fallible: bool) -> TokenStream2 {
+    let pushes = fields.iter().map(|f| {
+        let key = &f.key;
+        let value = access(&f.ident);
+        if f.attrs.flatten {
+            // to_bencode can't fail, so it keeps a flattened field that isn't a dict under its
+            // own key; try_to_bencode reports it
+            let not_a_dict = if fallible {
+                quote! {
+                    return Err(::mescal::BencodeError::UnexpectedType(::std::format!(
+                        "{}: expected dict to flatten, found {}", #key, other.type_name()
+                    )))
+                }
+            } else {
+                quote! { __entries.push(#key, other) }
+            };
+            let encoded = encode(value, fallible);
+            quote! {
+                match #encoded {
+                    ::mescal::BencodeItem::Dict(d) => {
+                        for (k, v) in d {
+                            __entries.push(k, v);
+                        }
+                    },
+                    other => #not_a_dict,
+                }
+            }
+        } else if f.optional {
+            let encoded = encode(quote! { v }, fallible);
+            quote! {
+                if let Some(v) = #value {
+                    __entries.push(#key, #encoded);
+                }
+            }
+        } else {
+            let encoded = encode(value, fallible);
+            quote! {
+                __entries.push(#key, #encoded);
+            }
+        }
+    });
+    quote! {
+        let mut __entries = ::mescal::BencodeDict::new();
+        #(#pushes)*
+    }
+}
+
+// field initializers reading from the dict item `__item`
+fn read_fields(fields: &[NamedField]) -> TokenStream2 {
+    let inits = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let key = &f.key;
+        if f.attrs.flatten {
+            quote! { #ident: ::mescal::FromBencode::from_bencode(__item)? }
+        } else if f.optional {
+            quote! { #ident: __item.optional_field(#key)? }
+        } else if f.attrs.default {
+            quote! { #ident: __item.optional_field(#key)?.unwrap_or_default() }
+        } else {
+            quote! { #ident: __item.required_field(#key)? }
+        }
+    });
+    quote! { #(#inits),* }
+}
+
+#[proc_macro_derive(ToBencode, attributes(bencode))]
+pub fn derive_to_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match to_bencode(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(FromBencode, attributes(bencode))]
+pub fn derive_from_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match from_bencode(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// the input's generics, with `T: bound` added for each type parameter T
+fn bounded_generics(input: &DeriveInput, bound: TokenStream2) -> syn::Generics {
+    let mut generics = input.generics.clone();
+    let params: Vec<syn::Ident> = generics.type_params().map(|p| p.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(syn::parse_quote! { #param: #bound });
+    }
+    generics
+}
+
+fn to_bencode(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = bounded_generics(input, quote! { ::mescal::ToBencode });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = to_bencode_body(input, false)?;
+    let fallible_body = to_bencode_body(input, true)?;
+    Ok(quote! {
+        impl #impl_generics ::mescal::ToBencode for #name #ty_generics #where_clause {
+            fn to_bencode(&self) -> ::mescal::BencodeItem {
+                #body
+            }
+
+            fn try_to_bencode(&self) -> ::std::result::Result<::mescal::BencodeItem, ::mescal::BencodeError> {
+                Ok({ #fallible_body })
+            }
+        }
+    })
+}
+
+fn to_bencode_body(input: &DeriveInput, fallible: bool) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => {
+                let fields = named_fields(fields)?;
+                let entries = push_entries(&fields, |ident| quote! { &self.#ident }, fallible);
+                quote! {
+                    #entries
+                    ::mescal::BencodeItem::Dict(__entries)
+                }
+            },
+            // newtypes are transparent
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => encode(quote! { &self.0 }, fallible),
+            _ => return Err(syn::Error::new_spanned(input, "only structs with named fields and newtypes are supported")),
+        },
+        Data::Enum(e) => {
+            // externally tagged: unit variants as their name, the rest as a single-entry dict
+            let arms = e.variants.iter().map(|v| {
+                let ident = &v.ident;
+                let tag = parse_attrs(&v.attrs)?.rename.unwrap_or_else(|| ident.to_string());
+                Ok(match &v.fields {
+                    Fields::Unit => quote! {
+                        #name::#ident => ::mescal::ToBencode::to_bencode(#tag)
+                    },
+                    Fields::Unnamed(f) if f.unnamed.len() == 1 => {
+                        let encoded = encode(quote! { v }, fallible);
+                        quote! {
+                            #name::#ident(v) => {
+                                let mut __tagged = ::mescal::BencodeDict::new();
+                                __tagged.push(#tag, #encoded);
+                                ::mescal::BencodeItem::Dict(__tagged)
+                            }
+                        }
+                    },
+                    Fields::Named(f) => {
+                        let fields = named_fields(f)?;
+                        let idents = fields.iter().map(|f| &f.ident);
+                        let entries = push_entries(&fields, |ident| quote! { #ident }, fallible);
+                        quote! {
+                            #name::#ident { #(#idents),* } => {
+                                #entries
+                                let mut __tagged = ::mescal::BencodeDict::new();
+                                __tagged.push(#tag, ::mescal::BencodeItem::Dict(__entries));
+                                ::mescal::BencodeItem::Dict(__tagged)
+                            }
+                        }
+                    },
+                    _ => return Err(syn::Error::new_spanned(v, "tuple variants must have exactly one field")),
+                })
+            }).collect::<syn::Result<Vec<_>>>()?;
+            quote! {
+                match self {
+                    #(#arms),*
+                }
+            }
+        },
+        Data::Union(_) => return Err(syn::Error::new_spanned(input, "unions are not supported")),
+    };
+    Ok(body)
+}
+
+fn from_bencode(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = bounded_generics(input, quote! { ::mescal::FromBencode });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => {
+                let inits = read_fields(&named_fields(fields)?);
+                quote! {
+                    let __item = item;
+                    Ok(#name { #inits })
+                }
+            },
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+                Ok(#name(::mescal::FromBencode::from_bencode(item)?))
+            },
+            _ => return Err(syn::Error::new_spanned(input, "only structs with named fields and newtypes are supported")),
+        },
+        Data::Enum(e) => {
+            let mut unit_arms = vec![];
+            let mut tagged_arms = vec![];
+            for v in &e.variants {
+                let ident = &v.ident;
+                let tag = parse_attrs(&v.attrs)?.rename.unwrap_or_else(|| ident.to_string());
+                match &v.fields {
+                    Fields::Unit => unit_arms.push(quote! { #tag => Ok(#name::#ident) }),
+                    Fields::Unnamed(f) if f.unnamed.len() == 1 => tagged_arms.push(quote! {
+                        #tag => Ok(#name::#ident(::mescal::FromBencode::from_bencode(__value)?))
+                    }),
+                    Fields::Named(f) => {
+                        let inits = read_fields(&named_fields(f)?);
+                        tagged_arms.push(quote! {
+                            #tag => {
+                                let __item = __value;
+                                Ok(#name::#ident { #inits })
+                            }
+                        });
+                    },
+                    _ => return Err(syn::Error::new_spanned(v, "tuple variants must have exactly one field")),
+                }
+            }
+            quote! {
+                match item {
+                    ::mescal::BencodeItem::String(_) => {
+                        let tag: ::std::string::String = ::mescal::FromBencode::from_bencode(item)?;
+                        match tag.as_str() {
+                            #(#unit_arms,)*
+                            other => Err(::mescal::BencodeError::UnknownVariant(::std::string::String::from(other))),
+                        }
+                    },
+                    ::mescal::BencodeItem::Dict(d) if d.len() == 1 => {
+                        let (tag, __value) = &d.as_slice()[0];
+                        match tag.as_str() {
+                            #(#tagged_arms,)*
+                            other => Err(::mescal::BencodeError::UnknownVariant(::std::string::String::from(other))),
+                        }
+                    },
+                    _ => Err(::mescal::BencodeError::UnexpectedType(::std::format!(
+                        "expected a string or single-entry dict for {}", stringify!(#name)
+                    ))),
+                }
+            }
+        },
+        Data::Union(_) => return Err(syn::Error::new_spanned(input, "unions are not supported")),
+    };
+    Ok(quote! {
+        impl #impl_generics ::mescal::FromBencode for #name #ty_generics #where_clause {
+            fn from_bencode(item: &::mescal::BencodeItem) -> ::std::result::Result<Self, ::mescal::BencodeError> {
+                #body
+            }
+        }
+    })
+}
@@ -0,0 +1,78 @@
+use std::io;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{AsBencodeBytes, BencodeError, BencodeItem};
+use crate::decoder;
+use crate::hash::{self, Hasher};
+
+// Encodes into memory first, then hands the writer a single buffer.
+pub async fn write_to_async<W: AsyncWrite + Unpin + Send>(item: &BencodeItem, w: &mut W) -> io::Result<()> {
+    w.write_all(&item.as_bytes()).await
+}
+
+// CPU-bound work moved off the reactor onto tokio's blocking pool
+
+pub async fn parse_bytes_blocking(bytes: Vec<u8>) -> Result<BencodeItem, BencodeError> {
+    run_blocking(move || decoder::parse_bytes(&mut bytes.iter().peekable())).await
+}
+
+pub async fn digest_blocking<H>(data: Vec<u8>) -> H::Digest
+where
+    H: Hasher + Default + Send + 'static,
+    H::Digest: Send + 'static,
+{
+    run_blocking(move || hash::digest::<H>(&data)).await
+}
+
+async fn run_blocking<T: Send + 'static, F: FnOnce() -> T + Send + 'static>(f: F) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        // blocking tasks can't be cancelled, so this is the closure having panicked
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ByteString;
+
+    #[test]
+    fn write_async() {
+        let item = BencodeItem::Dict(vec!(
+            (String::from("Hello"), BencodeItem::List(vec!(
+                BencodeItem::Int(-7),
+                BencodeItem::String(ByteString::from("World")),
+                BencodeItem::Dict(vec!().into()),
+            ))),
+        ).into());
+        let mut out: Vec<u8> = vec!();
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        rt.block_on(write_to_async(&item, &mut out)).unwrap();
+        assert_eq!(item.as_bytes(), out);
+    }
+
+    #[derive(Default)]
+    struct Len(usize);
+
+    impl Hasher for Len {
+        type Digest = [u8; 1];
+
+        fn update(&mut self, data: &[u8]) {
+            self.0 += data.len();
+        }
+
+        fn finalize(self) -> Self::Digest {
+            [self.0 as u8]
+        }
+    }
+
+    #[test]
+    fn blocking() {
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        assert_eq!(Ok(BencodeItem::Int(7)), rt.block_on(parse_bytes_blocking(b"i7e".to_vec())));
+        assert_eq!(Err(BencodeError::BytestreamEnded), rt.block_on(parse_bytes_blocking(b"i7".to_vec())));
+        assert_eq!([3], rt.block_on(digest_blocking::<Len>(b"abc".to_vec())));
+    }
+}
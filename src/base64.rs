@@ -0,0 +1,71 @@
+use crate::{BencodeError, ByteString};
+
+// Standard alphabet with padding (RFC 4648), enough for the magnet links and web seeds
+// that carry hashes and keys as base64. No dependency, so the feature stays cheap.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl ByteString {
+    pub fn to_base64(&self) -> String {
+        let mut out = String::with_capacity(self.bytes.len().div_ceil(3) * 4);
+        for chunk in self.bytes.chunks(3) {
+            let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    pub fn from_base64(text: &str) -> Result<ByteString, BencodeError> {
+        let text = text.as_bytes();
+        if !text.len().is_multiple_of(4) {
+            return Err(BencodeError::Base64(format!("length {} is not a multiple of 4", text.len())))
+        }
+        let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+        for (c, chunk) in text.chunks(4).enumerate() {
+            let last = c == text.len() / 4 - 1;
+            let padding = chunk.iter().rev().take_while(|b| **b == b'=').count();
+            if padding > 2 || (padding > 0 && !last) {
+                return Err(BencodeError::Base64(format!("at {}: unexpected padding", c * 4)))
+            }
+            let mut n = 0u32;
+            for (i, b) in chunk[..4 - padding].iter().enumerate() {
+                match ALPHABET.iter().position(|a| a == b) {
+                    Some(v) => n |= (v as u32) << (18 - 6 * i),
+                    None => return Err(BencodeError::Base64(format!("at {}: invalid character '{}'", c * 4 + i, *b as char))),
+                }
+            }
+            bytes.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+        }
+        Ok(ByteString::new(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for (bytes, text) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")] {
+            let s = ByteString::from(bytes);
+            assert_eq!(text, s.to_base64());
+            assert_eq!(Ok(s), ByteString::from_base64(text));
+        }
+        let hash = ByteString::new((0..=255).collect());
+        assert_eq!(Ok(hash.clone()), ByteString::from_base64(&hash.to_base64()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Err(BencodeError::Base64(String::from("length 3 is not a multiple of 4"))), ByteString::from_base64("Zm9"));
+        assert_eq!(Err(BencodeError::Base64(String::from("at 2: invalid character '-'"))), ByteString::from_base64("Zm-v"));
+        assert_eq!(Err(BencodeError::Base64(String::from("at 0: unexpected padding"))), ByteString::from_base64("Zg==Zm9v"));
+        assert_eq!(Err(BencodeError::Base64(String::from("at 0: unexpected padding"))), ByteString::from_base64("Z==="));
+    }
+}
@@ -5,4 +5,8 @@ pub const M_END: u8 = 0x65;
 pub const M_COLON: u8 = 0x3A;
 pub const M_0: u8 = 0x30;
 pub const M_9: u8 = 0x39;
-pub const M_DASH: u8 = 0x2D;
\ No newline at end of file
+pub const M_DASH: u8 = 0x2D;
+
+// prefix for byte strings written as hex text by serde and JSON
+#[cfg(any(feature = "serde", feature = "json"))]
+pub const HEX: &str = "hex:";
@@ -0,0 +1,97 @@
+use ciborium::value::{Integer, Value};
+
+use crate::convert::in_field;
+use crate::{BencodeDict, BencodeError, BencodeItem, ByteString};
+
+// Conversion to and from ciborium's CBOR values. Strings that are UTF-8 become text and
+// the rest byte strings, and both come back as the same bytes; dicts become maps in their
+// stored order, duplicates included, so bencode -> CBOR -> bencode is exact. Going the
+// other way, bools become 0/1 and floats, nulls and tags are errors.
+
+impl From<&BencodeItem> for Value {
+    fn from(item: &BencodeItem) -> Value {
+        match item {
+            BencodeItem::Int(i) => Value::Integer(Integer::from(*i)),
+            BencodeItem::String(s) => match s.as_utf8() {
+                Some(text) => Value::Text(String::from(text)),
+                None => Value::Bytes(s.bytes.clone()),
+            },
+            BencodeItem::List(l) => Value::Array(l.iter().map(Value::from).collect()),
+            BencodeItem::Dict(d) => Value::Map(d.iter().map(|(k, v)| (Value::Text(k.clone()), Value::from(v))).collect()),
+        }
+    }
+}
+
+impl TryFrom<&Value> for BencodeItem {
+    type Error = BencodeError;
+
+    fn try_from(value: &Value) -> Result<BencodeItem, BencodeError> {
+        match value {
+            Value::Integer(i) => i64::try_from(*i)
+                .map(BencodeItem::Int)
+                .map_err(|_| BencodeError::ValueOutOfRange(format!("{} doesn't fit a bencode int", i128::from(*i)))),
+            Value::Bool(b) => Ok(BencodeItem::Int(*b as i64)),
+            Value::Text(s) => Ok(BencodeItem::String(ByteString::from(s.as_str()))),
+            Value::Bytes(b) => Ok(BencodeItem::String(ByteString::from(b.as_slice()))),
+            Value::Array(a) => a.iter().enumerate()
+                .map(|(i, v)| BencodeItem::try_from(v).map_err(|e| in_field(&format!("[{}]", i), e)))
+                .collect(),
+            Value::Map(m) => {
+                let mut d = BencodeDict::with_capacity(m.len());
+                for (k, v) in m {
+                    let key = match k {
+                        Value::Text(k) => k.clone(),
+                        Value::Bytes(b) => String::from_utf8(b.clone()).map_err(|_| BencodeError::DictKeyParse)?,
+                        _ => return Err(BencodeError::UnexpectedType(String::from("map keys must be text"))),
+                    };
+                    let value = BencodeItem::try_from(v).map_err(|e| in_field(&key, e))?;
+                    d.push(key, value);
+                }
+                Ok(BencodeItem::Dict(d))
+            },
+            Value::Float(_) => Err(BencodeError::UnexpectedType(String::from("bencode has no floats"))),
+            Value::Null => Err(BencodeError::UnexpectedType(String::from("bencode has no null"))),
+            Value::Tag(tag, _) => Err(BencodeError::UnexpectedType(format!("bencode has no tags, found tag {}", tag))),
+            _ => Err(BencodeError::UnexpectedType(String::from("unknown CBOR value"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let item = bencode!({
+            "b": [1, -2, i64::MIN, "x", ByteString::new(vec!(0xFF, 0))],
+            "a": {},
+            "a": [],
+        });
+        let value = Value::from(&item);
+        assert_eq!(
+            Value::Map(vec!(
+                (Value::Text(String::from("b")), Value::Array(vec!(
+                    Value::Integer(1.into()), Value::Integer((-2).into()), Value::Integer(i64::MIN.into()),
+                    Value::Text(String::from("x")), Value::Bytes(vec!(0xFF, 0)),
+                ))),
+                (Value::Text(String::from("a")), Value::Map(vec!())),
+                (Value::Text(String::from("a")), Value::Array(vec!())),
+            )),
+            value
+        );
+        assert_eq!(Ok(item), BencodeItem::try_from(&value));
+    }
+
+    #[test]
+    fn from_cbor() {
+        let value = Value::Map(vec!((Value::Bytes(b"k".to_vec()), Value::Array(vec!(Value::Bool(true), Value::Float(1.5))))));
+        assert_eq!(Err(BencodeError::UnexpectedType(String::from("k: [1]: bencode has no floats"))), BencodeItem::try_from(&value));
+        assert_eq!(Ok(bencode!({"k": [1]})), BencodeItem::try_from(&Value::Map(vec!((Value::Bytes(b"k".to_vec()), Value::Array(vec!(Value::Bool(true))))))));
+        assert_eq!(
+            Err(BencodeError::ValueOutOfRange(String::from("18446744073709551615 doesn't fit a bencode int"))),
+            BencodeItem::try_from(&Value::Integer(u64::MAX.into()))
+        );
+        assert_eq!(Err(BencodeError::UnexpectedType(String::from("map keys must be text"))), BencodeItem::try_from(&Value::Map(vec!((Value::Null, Value::Null)))));
+    }
+}
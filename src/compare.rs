@@ -0,0 +1,46 @@
+use crate::BencodeItem;
+
+impl BencodeItem {
+    // Equality that treats dicts as sets of entries: key order doesn't matter, and neither
+    // does an entry appearing more than once. Lists still compare in order.
+    pub fn semantically_equals(&self, other: &BencodeItem) -> bool {
+        self == other || normalized(self) == normalized(other)
+    }
+}
+
+// dict entries sorted by key, then by value, with exact repeats dropped
+fn normalized(item: &BencodeItem) -> BencodeItem {
+    item.clone().transform(|node| match node {
+        BencodeItem::Dict(mut d) => {
+            // children are normalized already, so equal values sort next to each other
+            d.entries_mut().sort();
+            d.entries_mut().dedup();
+            BencodeItem::Dict(d)
+        },
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn semantically_equals() {
+        let a = bencode!({"announce": "x", "info": {"length": 1, "name": "a"}});
+        let reordered = bencode!({"info": {"name": "a", "length": 1}, "announce": "x"});
+        assert_ne!(a, reordered);
+        assert!(a.semantically_equals(&reordered));
+        assert!(reordered.semantically_equals(&a));
+
+        let duplicated = bencode!({"announce": "x", "announce": "x", "info": {"length": 1, "name": "a"}});
+        assert!(a.semantically_equals(&duplicated));
+
+        assert!(!a.semantically_equals(&bencode!({"announce": "x", "info": {"length": 2, "name": "a"}})));
+        assert!(!a.semantically_equals(&bencode!({"announce": "x"})));
+        // list order still counts
+        assert!(!bencode!([1, 2]).semantically_equals(&bencode!([2, 1])));
+        assert!(bencode!([{"a": 1, "b": 2}]).semantically_equals(&bencode!([{"b": 2, "a": 1}])));
+        // same key twice with different values is two entries
+        assert!(bencode!({"a": 1, "a": 2}).semantically_equals(&bencode!({"a": 2, "a": 1})));
+        assert!(!bencode!({"a": 1, "a": 2}).semantically_equals(&bencode!({"a": 1})));
+    }
+}
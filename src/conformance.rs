@@ -0,0 +1,118 @@
+use crate::{AsBencodeBytes, RoundTripIssue};
+use crate::decoder;
+use crate::roundtrip;
+use crate::text;
+
+// The battery of properties the crate holds itself to, runnable over arbitrary input
+// (e.g. samples of production traffic).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    // the input decodes at all
+    Decodes,
+    // encode(decode(x)) == x
+    RoundTrip,
+    // x is already in canonical form, i.e. canonical(decode(x)) == x
+    Canonical,
+    // canonicalizing a canonical encoding changes nothing
+    CanonicalIdempotent,
+    // encoded_len() agrees with the actual encoding
+    EncodedLen,
+    // the text form parses back to the same tree
+    TextRoundTrip,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CheckResult {
+    pub check: Check,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ConformanceReport {
+    pub results: Vec<CheckResult>,
+    pub issues: Vec<RoundTripIssue>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.results.iter().filter(|r| !r.passed)
+    }
+}
+
+fn result(check: Check, passed: bool, detail: impl FnOnce() -> String) -> CheckResult {
+    CheckResult { check, passed, detail: if passed { None } else { Some(detail()) } }
+}
+
+pub fn check_bytes(bytes: &[u8]) -> ConformanceReport {
+    let mut bytes_iter = bytes.iter().peekable();
+    let item = match decoder::parse_bytes(&mut bytes_iter) {
+        Ok(item) => item,
+        Err(e) => return ConformanceReport {
+            results: vec!(result(Check::Decodes, false, || format!("{:?}", e))),
+            issues: vec!(),
+        },
+    };
+    let mut results = vec!(result(Check::Decodes, true, String::new));
+
+    let roundtrip = roundtrip::verify_roundtrip(bytes).expect("input already decoded once");
+    results.push(result(Check::RoundTrip, roundtrip.identical, || {
+        format!("re-encoding differs at offset {}", roundtrip.first_difference.unwrap_or(0))
+    }));
+
+    let canonical = item.as_canonical_bytes();
+    results.push(result(Check::Canonical, canonical == bytes, || {
+        String::from("input is not in canonical form")
+    }));
+
+    let recanonical = decoder::parse_bytes(&mut canonical.iter().peekable())
+        .map(|i| i.as_canonical_bytes());
+    results.push(result(Check::CanonicalIdempotent, recanonical.as_ref() == Ok(&canonical), || {
+        format!("canonical encoding isn't stable: {:?}", recanonical.as_ref().err())
+    }));
+
+    let encoded_len = item.encoded_len();
+    let actual_len = item.as_bytes().len();
+    results.push(result(Check::EncodedLen, encoded_len == actual_len, || {
+        format!("encoded_len() is {} but the encoding is {} bytes", encoded_len, actual_len)
+    }));
+
+    let from_text = text::from_text(&item.to_text());
+    results.push(result(Check::TextRoundTrip, from_text.as_ref() == Ok(&item), || {
+        format!("text form doesn't parse back: {:?}", from_text.as_ref().err())
+    }));
+
+    ConformanceReport { results, issues: roundtrip.issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conforming() {
+        for bytes in [&b"d1:ai1e1:bl0:i-1edeee"[..], b"i0e", b"0:", b"le"] {
+            let report = check_bytes(bytes);
+            assert!(report.passed(), "{:?}", report);
+            assert_eq!(6, report.results.len());
+        }
+    }
+
+    #[test]
+    fn failing() {
+        let report = check_bytes(b"i01e");
+        assert!(!report.passed());
+        assert_eq!(vec!(Check::Decodes), report.failures().map(|r| r.check).collect::<Vec<_>>());
+
+        let report = check_bytes(b"d1:bi1e1:ai2ee");
+        assert_eq!(vec!(Check::Canonical), report.failures().map(|r| r.check).collect::<Vec<_>>());
+        assert_eq!(vec!(RoundTripIssue::UnsortedKeys { path: String::new() }), report.issues);
+
+        let report = check_bytes(b"i1ei2e");
+        assert_eq!(vec!(Check::RoundTrip, Check::Canonical), report.failures().map(|r| r.check).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,672 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{BencodeDict, BencodeError, BencodeInt, BencodeItem, ByteString};
+
+pub trait ToBencode {
+    fn to_bencode(&self) -> BencodeItem;
+
+    // For values that can be out of shape for bencode, like a derived struct whose flattened
+    // field isn't a dict: to_bencode makes do, this reports it
+    fn try_to_bencode(&self) -> Result<BencodeItem, BencodeError> {
+        Ok(self.to_bencode())
+    }
+}
+
+pub trait FromBencode: Sized {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError>;
+}
+
+fn unexpected_type(expected: &str, found: &BencodeItem) -> BencodeError {
+    BencodeError::UnexpectedType(format!("expected {}, found {}", expected, found.type_name()))
+}
+
+// prefixes conversion errors with the key they happened under, so nested failures
+// read like "info: piece length: expected int, found string"
+pub(crate) fn in_field(key: &str, e: BencodeError) -> BencodeError {
+    match e {
+        BencodeError::MissingKey(m) => BencodeError::MissingKey(format!("{}: {}", key, m)),
+        BencodeError::UnexpectedType(m) => BencodeError::UnexpectedType(format!("{}: {}", key, m)),
+        BencodeError::ValueOutOfRange(m) => BencodeError::ValueOutOfRange(format!("{}: {}", key, m)),
+        e => e,
+    }
+}
+
+impl BencodeItem {
+    pub fn required_field<T: FromBencode>(&self, key: &str) -> Result<T, BencodeError> {
+        match self.optional_field(key)? {
+            Some(v) => Ok(v),
+            None => Err(BencodeError::MissingKey(format!("missing key \"{}\"", key))),
+        }
+    }
+
+    pub fn optional_field<T: FromBencode>(&self, key: &str) -> Result<Option<T>, BencodeError> {
+        match self {
+            BencodeItem::Dict(d) => match d.iter().find(|(k, _)| k == key) {
+                Some((_, v)) => T::from_bencode(v).map(Some).map_err(|e| in_field(key, e)),
+                None => Ok(None),
+            },
+            other => Err(unexpected_type("dict", other)),
+        }
+    }
+
+    // like required_field, but for anything convertible with TryFrom:
+    // `dict.get_as::<i64>("piece length")?`
+    pub fn get_as<'a, T>(&'a self, key: &str) -> Result<T, BencodeError>
+    where
+        T: TryFrom<&'a BencodeItem, Error = BencodeError>,
+    {
+        match self {
+            BencodeItem::Dict(d) => match d.iter().find(|(k, _)| k == key) {
+                Some((_, v)) => T::try_from(v).map_err(|e| in_field(key, e)),
+                None => Err(BencodeError::MissingKey(format!("missing key \"{}\"", key))),
+            },
+            other => Err(unexpected_type("dict", other)),
+        }
+    }
+}
+
+// TryFrom for the common leaf types, so they work with get_as and `?` without naming FromBencode
+macro_rules! impl_try_from_item {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<&BencodeItem> for $t {
+                type Error = BencodeError;
+
+                fn try_from(item: &BencodeItem) -> Result<Self, Self::Error> {
+                    <$t>::from_bencode(item)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_item!(i64, u64, u32, i32, u16, bool, String, ByteString, BencodeInt, Vec<u8>, Vec<String>, Vec<i64>);
+
+impl ToBencode for BencodeItem {
+    fn to_bencode(&self) -> BencodeItem {
+        self.clone()
+    }
+}
+
+impl FromBencode for BencodeItem {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        Ok(item.clone())
+    }
+}
+
+impl ToBencode for i64 {
+    fn to_bencode(&self) -> BencodeItem {
+        BencodeItem::Int(*self)
+    }
+}
+
+impl FromBencode for i64 {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        match item {
+            BencodeItem::Int(i) => Ok(*i),
+            other => Err(unexpected_type("int", other)),
+        }
+    }
+}
+
+macro_rules! impl_bencode_int {
+    ($($t:ty),*) => {
+        $(
+            impl ToBencode for $t {
+                fn to_bencode(&self) -> BencodeItem {
+                    BencodeItem::Int(i64::from(*self))
+                }
+            }
+
+            impl FromBencode for $t {
+                fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+                    let i = i64::from_bencode(item)?;
+                    <$t>::try_from(i).map_err(|_| BencodeError::ValueOutOfRange(
+                        format!("{} doesn't fit in {}", i, stringify!($t))
+                    ))
+                }
+            }
+        )*
+    };
+}
+
+// no u8: like AsBencodeBytes, Vec<u8> and [u8] are byte strings, not lists of ints
+impl_bencode_int!(u16, u32, i32);
+
+impl FromBencode for u64 {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        let i = i64::from_bencode(item)?;
+        u64::try_from(i).map_err(|_| BencodeError::ValueOutOfRange(format!("{} doesn't fit in u64", i)))
+    }
+}
+
+// flags like `private` are 0 or 1
+impl ToBencode for bool {
+    fn to_bencode(&self) -> BencodeItem {
+        BencodeItem::Int(i64::from(*self))
+    }
+}
+
+impl FromBencode for bool {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        match i64::from_bencode(item)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            i => Err(BencodeError::ValueOutOfRange(format!("{} isn't a bool, expected 0 or 1", i))),
+        }
+    }
+}
+
+impl ToBencode for ByteString {
+    fn to_bencode(&self) -> BencodeItem {
+        BencodeItem::String(self.clone())
+    }
+}
+
+impl FromBencode for ByteString {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        match item {
+            BencodeItem::String(s) => Ok(s.clone()),
+            other => Err(unexpected_type("string", other)),
+        }
+    }
+}
+
+impl ToBencode for str {
+    fn to_bencode(&self) -> BencodeItem {
+        BencodeItem::String(ByteString::from(self))
+    }
+}
+
+impl ToBencode for String {
+    fn to_bencode(&self) -> BencodeItem {
+        self.as_str().to_bencode()
+    }
+}
+
+impl FromBencode for String {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        match item {
+            BencodeItem::String(s) => String::try_from(s).map_err(|_| BencodeError::StrParse),
+            other => Err(unexpected_type("string", other)),
+        }
+    }
+}
+
+impl<T: ToBencode + ?Sized> ToBencode for &T {
+    fn to_bencode(&self) -> BencodeItem {
+        (**self).to_bencode()
+    }
+
+    fn try_to_bencode(&self) -> Result<BencodeItem, BencodeError> {
+        (**self).try_to_bencode()
+    }
+}
+
+impl<T: ToBencode> ToBencode for [T] {
+    fn to_bencode(&self) -> BencodeItem {
+        BencodeItem::List(self.iter().map(|i| i.to_bencode()).collect())
+    }
+
+    fn try_to_bencode(&self) -> Result<BencodeItem, BencodeError> {
+        Ok(BencodeItem::List(self.iter().map(|i| i.try_to_bencode()).collect::<Result<_, _>>()?))
+    }
+}
+
+impl<T: ToBencode> ToBencode for Vec<T> {
+    fn to_bencode(&self) -> BencodeItem {
+        self.as_slice().to_bencode()
+    }
+
+    fn try_to_bencode(&self) -> Result<BencodeItem, BencodeError> {
+        self.as_slice().try_to_bencode()
+    }
+}
+
+impl ToBencode for [u8] {
+    fn to_bencode(&self) -> BencodeItem {
+        BencodeItem::String(ByteString::from(self))
+    }
+}
+
+impl ToBencode for Vec<u8> {
+    fn to_bencode(&self) -> BencodeItem {
+        self.as_slice().to_bencode()
+    }
+}
+
+impl FromBencode for Vec<u8> {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        match item {
+            BencodeItem::String(s) => Ok(s.bytes.clone()),
+            other => Err(unexpected_type("string", other)),
+        }
+    }
+}
+
+impl<T: FromBencode> FromBencode for Vec<T> {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        match item {
+            BencodeItem::List(l) => l.iter().enumerate()
+                .map(|(i, v)| T::from_bencode(v).map_err(|e| in_field(&format!("[{}]", i), e)))
+                .collect(),
+            other => Err(unexpected_type("list", other)),
+        }
+    }
+}
+
+// hash map iteration order is arbitrary, so entries are sorted to keep encoding deterministic
+impl From<HashMap<String, BencodeItem>> for BencodeItem {
+    fn from(map: HashMap<String, BencodeItem>) -> Self {
+        BencodeItem::Dict(BencodeDict::from_hashmap(map))
+    }
+}
+
+impl From<BTreeMap<String, BencodeItem>> for BencodeItem {
+    fn from(map: BTreeMap<String, BencodeItem>) -> Self {
+        BencodeItem::Dict(BencodeDict::from_btreemap(map))
+    }
+}
+
+// dict keys have to be UTF-8
+impl TryFrom<BTreeMap<ByteString, BencodeItem>> for BencodeItem {
+    type Error = BencodeError;
+
+    fn try_from(map: BTreeMap<ByteString, BencodeItem>) -> Result<Self, Self::Error> {
+        let mut d = BencodeDict::with_capacity(map.len());
+        for (key, value) in map {
+            match String::try_from(&key) {
+                Ok(key) => d.push(key, value),
+                Err(_) => return Err(BencodeError::DictKeyParse),
+            }
+        }
+        Ok(BencodeItem::Dict(d))
+    }
+}
+
+impl From<i64> for BencodeItem {
+    fn from(i: i64) -> Self {
+        BencodeItem::Int(i)
+    }
+}
+
+impl From<&str> for ByteString {
+    fn from(s: &str) -> Self {
+        ByteString::new(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for ByteString {
+    fn from(s: String) -> Self {
+        ByteString::new(s.into_bytes())
+    }
+}
+
+impl From<&[u8]> for ByteString {
+    fn from(bytes: &[u8]) -> Self {
+        ByteString::new(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for ByteString {
+    fn from(bytes: Vec<u8>) -> Self {
+        ByteString::new(bytes)
+    }
+}
+
+impl From<&str> for BencodeItem {
+    fn from(s: &str) -> Self {
+        BencodeItem::String(ByteString::from(s))
+    }
+}
+
+impl From<String> for BencodeItem {
+    fn from(s: String) -> Self {
+        BencodeItem::String(ByteString::from(s))
+    }
+}
+
+impl From<Vec<u8>> for BencodeItem {
+    fn from(bytes: Vec<u8>) -> Self {
+        BencodeItem::String(ByteString::new(bytes))
+    }
+}
+
+impl From<ByteString> for BencodeItem {
+    fn from(s: ByteString) -> Self {
+        BencodeItem::String(s)
+    }
+}
+
+impl From<Vec<BencodeItem>> for BencodeItem {
+    fn from(items: Vec<BencodeItem>) -> Self {
+        BencodeItem::List(items)
+    }
+}
+
+impl From<Vec<(String, BencodeItem)>> for BencodeItem {
+    fn from(entries: Vec<(String, BencodeItem)>) -> Self {
+        BencodeItem::Dict(entries.into())
+    }
+}
+
+impl From<BencodeDict> for BencodeItem {
+    fn from(d: BencodeDict) -> Self {
+        BencodeItem::Dict(d)
+    }
+}
+
+impl From<&[BencodeItem]> for BencodeItem {
+    fn from(items: &[BencodeItem]) -> Self {
+        BencodeItem::List(items.to_vec())
+    }
+}
+
+// There's no Extend for BencodeItem to go with these: it couldn't fail, so extending an int,
+// a string or the wrong kind of container would have to panic. Extend the Vec from
+// as_list_mut() or the BencodeDict from as_dict_mut() instead.
+impl FromIterator<BencodeItem> for BencodeItem {
+    fn from_iter<I: IntoIterator<Item = BencodeItem>>(iter: I) -> Self {
+        BencodeItem::List(iter.into_iter().collect())
+    }
+}
+
+impl FromIterator<(String, BencodeItem)> for BencodeItem {
+    fn from_iter<I: IntoIterator<Item = (String, BencodeItem)>>(iter: I) -> Self {
+        BencodeItem::Dict(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct TorrentInfo {
+        name: String,
+        piece_length: u32,
+        private: Option<i64>,
+        files: Vec<ByteString>,
+    }
+
+    impl ToBencode for TorrentInfo {
+        fn to_bencode(&self) -> BencodeItem {
+            let mut d = BencodeDict::new();
+            d.push("name", self.name.to_bencode());
+            d.push("piece length", self.piece_length.to_bencode());
+            if let Some(private) = self.private {
+                d.push("private", private.to_bencode());
+            }
+            d.push("files", self.files.to_bencode());
+            BencodeItem::Dict(d)
+        }
+    }
+
+    impl FromBencode for TorrentInfo {
+        fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+            Ok(TorrentInfo {
+                name: item.required_field("name")?,
+                piece_length: item.required_field("piece length")?,
+                private: item.optional_field("private")?,
+                files: item.required_field("files")?,
+            })
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let info = TorrentInfo {
+            name: String::from("x"),
+            piece_length: 16384,
+            private: None,
+            files: vec!(ByteString::from("a")),
+        };
+        assert_eq!(Ok(info.to_bencode()), BencodeItem::from_bencode(&info.to_bencode()));
+        assert_eq!(Ok(info), TorrentInfo::from_bencode(&TorrentInfo {
+            name: String::from("x"),
+            piece_length: 16384,
+            private: None,
+            files: vec!(ByteString::from("a")),
+        }.to_bencode()));
+    }
+
+    #[test]
+    fn get_as() {
+        let item = bencode!({"name": "sample", "piece length": 16384, "info": {"length": -1}});
+        assert_eq!(Ok(16384), item.get_as::<i64>("piece length"));
+        assert_eq!(Ok(String::from("sample")), item.get_as::<String>("name"));
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("name: expected int, found string"))),
+            item.get_as::<i64>("name")
+        );
+        assert_eq!(Err(BencodeError::MissingKey(String::from("missing key \"x\""))), item.get_as::<String>("x"));
+        let info = &item["info"];
+        assert_eq!(Err(BencodeError::ValueOutOfRange(String::from("length: -1 doesn't fit in u64"))), info.get_as::<u64>("length"));
+        assert_eq!(Ok(-1), i64::try_from(&bencode!(-1)));
+    }
+
+    #[test]
+    fn errors() {
+        let item = BencodeItem::Dict(vec!(
+            (String::from("name"), BencodeItem::Int(1)),
+            (String::from("piece length"), BencodeItem::Int(-1)),
+        ).into());
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("name: expected string, found int"))),
+            item.required_field::<String>("name")
+        );
+        assert_eq!(
+            Err(BencodeError::ValueOutOfRange(String::from("piece length: -1 doesn't fit in u32"))),
+            item.required_field::<u32>("piece length")
+        );
+        assert_eq!(
+            Err(BencodeError::MissingKey(String::from("missing key \"files\""))),
+            item.required_field::<Vec<ByteString>>("files")
+        );
+        assert_eq!(Ok(None), item.optional_field::<i64>("private"));
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("expected dict, found int"))),
+            BencodeItem::Int(1).optional_field::<i64>("x")
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use crate::{BencodeError, BencodeItem, ByteString, FromBencode, ToBencode};
+
+        #[derive(Debug, PartialEq, ToBencode, FromBencode)]
+        struct Info {
+            name: String,
+            #[bencode(rename = "piece length")]
+            piece_length: u32,
+            #[bencode(default)]
+            private: i64,
+            comment: Option<String>,
+            #[bencode(flatten)]
+            extra: Extra,
+        }
+
+        #[derive(Debug, PartialEq, ToBencode, FromBencode)]
+        struct Extra {
+            source: ByteString,
+        }
+
+        #[derive(Debug, PartialEq, ToBencode, FromBencode)]
+        struct Pieces {
+            pieces: Vec<u8>,
+        }
+
+        #[derive(Debug, PartialEq, ToBencode, FromBencode)]
+        struct Labeled<T> {
+            label: String,
+            value: T,
+            #[bencode(flatten)]
+            extra: T,
+        }
+
+        #[derive(Debug, PartialEq, ToBencode, FromBencode)]
+        enum Message {
+            Ping,
+            #[bencode(rename = "e")]
+            Error(Vec<i64>),
+            Query { id: String },
+        }
+
+        #[test]
+        fn structs() {
+            let info = Info {
+                name: String::from("x"),
+                piece_length: 16,
+                private: 0,
+                comment: None,
+                extra: Extra { source: ByteString::from("s") },
+            };
+            let item = info.to_bencode();
+            assert_eq!(BencodeItem::Dict(vec!(
+                (String::from("name"), BencodeItem::String(ByteString::from("x"))),
+                (String::from("piece length"), BencodeItem::Int(16)),
+                (String::from("private"), BencodeItem::Int(0)),
+                (String::from("source"), BencodeItem::String(ByteString::from("s"))),
+            ).into()), item);
+            assert_eq!(Ok(info), Info::from_bencode(&item));
+
+            let missing = BencodeItem::Dict(vec!(
+                (String::from("name"), BencodeItem::String(ByteString::from("x"))),
+                (String::from("piece length"), BencodeItem::Int(16)),
+            ).into());
+            assert_eq!(Err(BencodeError::MissingKey(String::from("missing key \"source\""))), Info::from_bencode(&missing));
+        }
+
+        #[test]
+        fn byte_fields() {
+            let item = bencode!({"pieces": ByteString::new(vec!(0xFF, 0))});
+            assert_eq!(Ok(Pieces { pieces: vec!(0xFF, 0) }), Pieces::from_bencode(&item));
+            assert_eq!(item, Pieces { pieces: vec!(0xFF, 0) }.to_bencode());
+        }
+
+        #[test]
+        fn generics() {
+            let labeled = Labeled { label: String::from("a"), value: Extra { source: ByteString::from("s") }, extra: Extra { source: ByteString::from("t") } };
+            let item = labeled.to_bencode();
+            assert_eq!(bencode!({"label": "a", "value": {"source": "s"}, "source": "t"}), item);
+            assert_eq!(Ok(labeled), Labeled::from_bencode(&item));
+
+            // a flattened field that isn't a dict is kept under its key, or reported
+            let labeled = Labeled { label: String::from("a"), value: 1i64, extra: 2 };
+            assert_eq!(bencode!({"label": "a", "value": 1, "extra": 2}), labeled.to_bencode());
+            assert_eq!(
+                Err(BencodeError::UnexpectedType(String::from("extra: expected dict to flatten, found int"))),
+                labeled.try_to_bencode()
+            );
+            assert_eq!(
+                Err(BencodeError::UnexpectedType(String::from("extra: expected dict to flatten, found int"))),
+                vec!(labeled).try_to_bencode()
+            );
+        }
+
+        #[test]
+        fn enums() {
+            for m in [Message::Ping, Message::Error(vec!(201)), Message::Query { id: String::from("abc") }] {
+                assert_eq!(Ok(&m), Message::from_bencode(&m.to_bencode()).as_ref());
+            }
+            assert_eq!(BencodeItem::String(ByteString::from("Ping")), Message::Ping.to_bencode());
+            assert_eq!(
+                BencodeItem::Dict(vec!((String::from("e"), BencodeItem::List(vec!(BencodeItem::Int(201))))).into()),
+                Message::Error(vec!(201)).to_bencode()
+            );
+            assert_eq!(
+                Err(BencodeError::UnknownVariant(String::from("Pong"))),
+                Message::from_bencode(&BencodeItem::String(ByteString::from("Pong")))
+            );
+        }
+    }
+
+    #[test]
+    fn from_values() {
+        let item = BencodeItem::from(vec!(
+            (String::from("name"), BencodeItem::from("a")),
+            (String::from("owner"), BencodeItem::from(String::from("b"))),
+            (String::from("pieces"), BencodeItem::from(vec!(0xFFu8, 0))),
+            (String::from("files"), BencodeItem::from(vec!(BencodeItem::from(1), 2.into()))),
+        ));
+        assert_eq!(bencode!({"name": "a", "owner": "b", "pieces": ByteString::new(vec!(0xFF, 0)), "files": [1, 2]}), item);
+        assert_eq!(bencode!({}), BencodeItem::from(BencodeDict::new()));
+
+        let hash = ByteString::from(&[0x8A, 0x0F][..]);
+        assert_eq!(ByteString::new(vec!(0x8A, 0x0F)), hash);
+        assert_eq!(hash, ByteString::from(vec!(0x8A, 0x0F)));
+        assert!(hash == [0x8A, 0x0F][..]);
+        let name = ByteString::from(String::from("x"));
+        assert_eq!(ByteString::from("x"), name);
+        assert!(name == *"x");
+    }
+
+    #[test]
+    fn try_from() {
+        let item = bencode!({"private": 1, "pieces": ByteString::new(vec!(0xFF)), "urls": ["a", 1], "sizes": [1, 2]});
+        assert_eq!(Ok(true), item.get_as::<bool>("private"));
+        assert_eq!(Ok(false), bool::try_from(&bencode!(0)));
+        assert_eq!(Err(BencodeError::ValueOutOfRange(String::from("2 isn't a bool, expected 0 or 1"))), bool::try_from(&bencode!(2)));
+        assert_eq!(Ok(vec!(0xFF)), item.get_as::<Vec<u8>>("pieces"));
+        assert_eq!(Ok(vec!(1, 2)), item.get_as::<Vec<i64>>("sizes"));
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("urls: [1]: expected string, found int"))),
+            item.get_as::<Vec<String>>("urls")
+        );
+        assert_eq!(Err(BencodeError::UnexpectedType(String::from("expected string, found list"))), Vec::<u8>::try_from(&bencode!([])));
+        assert_eq!(bencode!(1), true.to_bencode());
+    }
+
+    #[test]
+    fn byte_vectors() {
+        // Vec<u8> is a byte string whichever way it goes in or out
+        let item = bencode!({"pieces": ByteString::new(vec!(0xFF, 0))});
+        let bytes = vec!(0xFFu8, 0);
+        assert_eq!(Ok(bytes.clone()), item.get_as::<Vec<u8>>("pieces"));
+        assert_eq!(Ok(bytes.clone()), item.required_field::<Vec<u8>>("pieces"));
+        assert_eq!(Ok(bytes.clone()), Vec::<u8>::from_bencode(&item["pieces"]));
+        assert_eq!(Ok(bytes.clone()), Vec::<u8>::try_from(&item["pieces"]));
+        assert_eq!(item["pieces"], bytes.to_bencode());
+        assert_eq!(item["pieces"], bytes[..].to_bencode());
+        assert_eq!(item["pieces"], BencodeItem::from(bytes.clone()));
+        assert_eq!(
+            crate::AsBencodeBytes::as_bytes(&item["pieces"]),
+            crate::AsBencodeBytes::as_bytes(&bytes)
+        );
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("pieces: expected string, found list"))),
+            bencode!({"pieces": [255, 0]}).required_field::<Vec<u8>>("pieces")
+        );
+        // other Vecs are still lists
+        assert_eq!(bencode!([[255, 0]]), vec!(vec!(255u16, 0)).to_bencode());
+        assert_eq!(bencode!([ByteString::new(vec!(0xFF, 0))]), vec!(bytes).to_bencode());
+    }
+
+    #[test]
+    fn from_collections() {
+        let mut hash_map = HashMap::new();
+        hash_map.insert(String::from("b"), BencodeItem::Int(2));
+        hash_map.insert(String::from("a"), BencodeItem::Int(1));
+        assert_eq!(bencode!({"a": 1, "b": 2}), BencodeItem::from(hash_map));
+
+        let mut btree_map = BTreeMap::new();
+        btree_map.insert(String::from("b"), BencodeItem::Int(2));
+        btree_map.insert(String::from("a"), BencodeItem::Int(1));
+        assert_eq!(bencode!({"a": 1, "b": 2}), BencodeItem::from(btree_map));
+
+        let mut bytes_map = BTreeMap::new();
+        bytes_map.insert(ByteString::from("x"), BencodeItem::Int(1));
+        assert_eq!(Ok(bencode!({"x": 1})), BencodeItem::try_from(bytes_map));
+        let mut bytes_map = BTreeMap::new();
+        bytes_map.insert(ByteString::new(vec!(0xFF)), BencodeItem::Int(1));
+        assert_eq!(Err(BencodeError::DictKeyParse), BencodeItem::try_from(bytes_map));
+
+        let items = [BencodeItem::Int(1), bencode!("x")];
+        assert_eq!(bencode!([1, "x"]), BencodeItem::from(&items[..]));
+        assert_eq!(bencode!([0, 1, 2]), (0..3i64).map(BencodeItem::Int).collect::<BencodeItem>());
+        assert_eq!(
+            bencode!({"k0": 0, "k1": 1}),
+            (0..2i64).map(|i| (format!("k{}", i), BencodeItem::Int(i))).collect::<BencodeItem>()
+        );
+    }
+}
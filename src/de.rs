@@ -0,0 +1,647 @@
+use std::fmt::{self, Display};
+
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer, Visitor};
+
+use crate::c::HEX;
+use crate::{decoder, BencodeDict, BencodeError, BencodeItem, ByteString};
+
+// Serde data format, reading side; the inverse of the table in ser.rs. Input is decoded
+// into a tree first, and types can borrow strs and byte slices from that tree when
+// deserialized through from_item.
+
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BencodeError> {
+    let mut bytes_iter = bytes.iter().peekable();
+    let item = decoder::parse_bytes(&mut bytes_iter)?;
+    if bytes_iter.peek().is_some() {
+        return Err(BencodeError::Serde(format!("{} bytes left after the value", bytes_iter.len())))
+    }
+    from_item(&item)
+}
+
+pub fn from_item<'de, T: Deserialize<'de>>(item: &'de BencodeItem) -> Result<T, BencodeError> {
+    T::deserialize(item)
+}
+
+impl de::Error for BencodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BencodeError::Serde(msg.to_string())
+    }
+}
+
+// the inverse of Serialize for BencodeItem in ser.rs
+impl<'de> Deserialize<'de> for BencodeItem {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<BencodeItem, D::Error> {
+        let human_readable = deserializer.is_human_readable();
+        deserializer.deserialize_any(ItemVisitor { human_readable })
+    }
+}
+
+struct ItemVisitor {
+    human_readable: bool,
+}
+
+impl<'de> Visitor<'de> for ItemVisitor {
+    type Value = BencodeItem;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an int, string, list or map")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<BencodeItem, E> {
+        Ok(BencodeItem::Int(v as i64))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<BencodeItem, E> {
+        Ok(BencodeItem::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<BencodeItem, E> {
+        i64::try_from(v).map(BencodeItem::Int).map_err(|_| E::custom(format!("{} doesn't fit a bencode int", v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<BencodeItem, E> {
+        match v.strip_prefix(HEX) {
+            Some(hex) if self.human_readable => ByteString::from_hex(hex)
+                .map(BencodeItem::String)
+                .ok_or_else(|| E::custom(format!("invalid hex {}", hex))),
+            _ => Ok(BencodeItem::String(ByteString::from(v))),
+        }
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<BencodeItem, E> {
+        Ok(BencodeItem::String(ByteString::from(v)))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<BencodeItem, E> {
+        Ok(BencodeItem::String(ByteString::new(v)))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<BencodeItem, A::Error> {
+        let mut l = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element_seed(ItemSeed(self.human_readable))? {
+            l.push(item);
+        }
+        Ok(BencodeItem::List(l))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<BencodeItem, A::Error> {
+        let mut d = BencodeDict::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(key) = map.next_key::<String>()? {
+            d.push(key, map.next_value_seed(ItemSeed(self.human_readable))?);
+        }
+        Ok(BencodeItem::Dict(d))
+    }
+}
+
+// children are read the same way as the item that contains them
+struct ItemSeed(bool);
+
+impl<'de> de::DeserializeSeed<'de> for ItemSeed {
+    type Value = BencodeItem;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<BencodeItem, D::Error> {
+        deserializer.deserialize_any(ItemVisitor { human_readable: self.0 })
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteString {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<ByteString, D::Error> {
+        deserializer.deserialize_byte_buf(ByteStringVisitor)
+    }
+}
+
+// takes bytes, text or a sequence of u8s, whichever the format has
+struct ByteStringVisitor;
+
+impl<'de> Visitor<'de> for ByteStringVisitor {
+    type Value = ByteString;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ByteString, E> {
+        Ok(ByteString::from(v))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ByteString, E> {
+        Ok(ByteString::new(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ByteString, E> {
+        Ok(ByteString::from(v))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<ByteString, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(ByteString::new(bytes))
+    }
+}
+
+fn unexpected(expected: &str, found: &BencodeItem) -> BencodeError {
+    BencodeError::UnexpectedType(format!("expected {}, found {}", expected, found.type_name()))
+}
+
+impl<'de> de::Deserializer<'de> for &'de BencodeItem {
+    type Error = BencodeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::Int(i) => visitor.visit_i64(*i),
+            BencodeItem::String(s) => match s.as_utf8() {
+                Some(s) => visitor.visit_borrowed_str(s),
+                None => visitor.visit_borrowed_bytes(&s.bytes),
+            },
+            // end() rejects a list or dict the visitor didn't read to the end, like a
+            // tuple that's too short for the list
+            BencodeItem::List(l) => {
+                let mut seq = de::value::SeqDeserializer::new(l.iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            },
+            BencodeItem::Dict(d) => {
+                let mut map = de::value::MapDeserializer::new(d.iter().map(|(k, v)| (KeyDeserializer(k), v)));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            },
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::Int(0) => visitor.visit_bool(false),
+            BencodeItem::Int(1) => visitor.visit_bool(true),
+            BencodeItem::Int(i) => Err(BencodeError::ValueOutOfRange(format!("{} isn't a bool", i))),
+            other => Err(unexpected("int", other)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::String(s) => match s.as_utf8() {
+                Some(s) => visitor.visit_borrowed_str(s),
+                None => Err(BencodeError::StrParse),
+            },
+            other => Err(unexpected("string", other)),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::String(s) => visitor.visit_borrowed_bytes(&s.bytes),
+            other => Err(unexpected("string", other)),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    // a missing struct field never gets here, serde turns that into None itself
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::List(l) if l.is_empty() => visitor.visit_unit(),
+            other => Err(unexpected("empty list", other)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, BencodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // A byte string reads as a sequence of u8s too, so plain Vec<u8> and [u8; 20] fields
+    // work without serde_bytes. That goes a byte at a time; ByteString, serde_bytes and
+    // &[u8] take the bytes in one piece through deserialize_bytes.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::List(_) => self.deserialize_any(visitor),
+            BencodeItem::String(s) => {
+                let mut seq = de::value::SeqDeserializer::<_, BencodeError>::new(s.bytes.iter().copied());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            },
+            other => Err(unexpected("list", other)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _: &'static str, _: usize, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::Dict(_) => self.deserialize_any(visitor),
+            other => Err(unexpected("dict", other)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self, _: &'static str, _: &'static [&'static str], visitor: V
+    ) -> Result<V::Value, BencodeError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self, _: &'static str, _: &'static [&'static str], visitor: V
+    ) -> Result<V::Value, BencodeError> {
+        match self {
+            BencodeItem::String(_) => visitor.visit_enum(Variant { tag: self, value: None }),
+            BencodeItem::Dict(d) if d.len() == 1 => {
+                let (tag, value) = &d.as_slice()[0];
+                visitor.visit_enum(Variant { tag: KeyDeserializer(tag), value: Some(value) })
+            },
+            BencodeItem::Dict(d) => Err(BencodeError::Serde(format!("expected a dict with one variant, found {} entries", d.len()))),
+            other => Err(unexpected("string or dict", other)),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+    }
+}
+
+impl<'de> IntoDeserializer<'de, BencodeError> for &'de BencodeItem {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+// Dict keys are always strings, but map keys can be ints on the Rust side (they're
+// written as their decimal form), so the int methods parse.
+struct KeyDeserializer<'de>(&'de str);
+
+impl<'de> IntoDeserializer<'de, BencodeError> for KeyDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+                match self.0.parse() {
+                    Ok(i) => visitor.$visit(i),
+                    Err(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = BencodeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, BencodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self, _: &'static str, _: &'static [&'static str], visitor: V
+    ) -> Result<V::Value, BencodeError> {
+        visitor.visit_enum(de::value::BorrowedStrDeserializer::new(self.0))
+    }
+
+    deserialize_parsed!(
+        deserialize_i8 => visit_i8, deserialize_i16 => visit_i16, deserialize_i32 => visit_i32, deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8, deserialize_u16 => visit_u16, deserialize_u32 => visit_u32, deserialize_u64 => visit_u64
+    );
+
+    serde::forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+// `tag` names the variant; `value` is None for a unit variant written as a bare string
+struct Variant<'de, T> {
+    tag: T,
+    value: Option<&'de BencodeItem>,
+}
+
+impl<'de, T: de::Deserializer<'de, Error = BencodeError>> de::EnumAccess<'de> for Variant<'de, T> {
+    type Error = BencodeError;
+    type Variant = VariantValue<'de>;
+
+    fn variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, VariantValue<'de>), BencodeError> {
+        Ok((seed.deserialize(self.tag)?, VariantValue(self.value)))
+    }
+}
+
+struct VariantValue<'de>(Option<&'de BencodeItem>);
+
+impl<'de> de::VariantAccess<'de> for VariantValue<'de> {
+    type Error = BencodeError;
+
+    fn unit_variant(self) -> Result<(), BencodeError> {
+        match self.0 {
+            None => Ok(()),
+            Some(value) => Deserialize::deserialize(value),
+        }
+    }
+
+    fn newtype_variant_seed<S: de::DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, BencodeError> {
+        seed.deserialize(self.value("newtype")?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, BencodeError> {
+        de::Deserializer::deserialize_seq(self.value("tuple")?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _: &'static [&'static str], visitor: V) -> Result<V::Value, BencodeError> {
+        de::Deserializer::deserialize_map(self.value("struct")?, visitor)
+    }
+}
+
+impl<'de> VariantValue<'de> {
+    fn value(self, kind: &str) -> Result<&'de BencodeItem, BencodeError> {
+        self.0.ok_or(BencodeError::Serde(format!("expected a {} variant, found a unit variant", kind)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Info {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: u32,
+        #[serde(default)]
+        private: bool,
+        comment: Option<String>,
+        files: Vec<File>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct File {
+        length: u64,
+        path: Vec<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Message {
+        Ping,
+        Have(u32),
+        Request(u32, u32),
+        Cancel { index: u32 },
+    }
+
+    #[test]
+    fn from_bytes() {
+        let bytes = b"d5:filesld6:lengthi3e4:pathl1:a1:beee4:name1:x12:piece lengthi16384e7:unknowni1ee";
+        let info = Info {
+            name: String::from("x"),
+            piece_length: 16384,
+            private: false,
+            comment: None,
+            files: vec!(File { length: 3, path: vec!(String::from("a"), String::from("b")) }),
+        };
+        assert_eq!(Ok(info), super::from_bytes::<Info>(bytes));
+
+        let info = Info { private: true, comment: Some(String::from("c")), files: vec!(), ..super::from_bytes(bytes).unwrap() };
+        assert_eq!(Ok(&info), super::from_bytes::<Info>(&crate::to_bytes(&info).unwrap()).as_ref());
+    }
+
+    #[test]
+    fn borrowed() {
+        let item = bencode!({"name": "x", "pieces": crate::ByteString::new(vec!(0xFF, 0))});
+        let map: BTreeMap<&str, &[u8]> = from_item(&item).unwrap();
+        assert_eq!(Some(&&b"x"[..]), map.get("name"));
+        assert_eq!(Some(&&[0xFF, 0][..]), map.get("pieces"));
+    }
+
+    #[test]
+    fn enums() {
+        for message in [Message::Ping, Message::Have(3), Message::Request(1, 2), Message::Cancel { index: 4 }] {
+            assert_eq!(Ok(&message), from_item::<Message>(&crate::to_item(&message).unwrap()).as_ref());
+        }
+        assert_eq!(Ok(Message::Ping), from_item(&bencode!({"Ping": []})));
+        assert!(from_item::<Message>(&bencode!({"Have": 1, "Ping": []})).is_err());
+        assert!(from_item::<Message>(&bencode!("Have")).is_err());
+    }
+
+    #[test]
+    fn items() {
+        let item = bencode!({"b": [1, "x"], "a": crate::ByteString::new(vec!(0xFF)), "a": {}});
+        assert_eq!(Ok(item.clone()), from_item::<BencodeItem>(&item));
+        assert_eq!(Ok(bencode!({"a": "\u{FF}"})), super::from_bytes::<BencodeItem>(b"d1:a2:\xC3\xBFe"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn items_in_json() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Config {
+            seed: BencodeItem,
+        }
+
+        let config = Config { seed: bencode!({"pieces": crate::ByteString::new(vec!(0xFF)), "note": "hex:1", "n": [1]}) };
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(r#"{"seed":{"pieces":"hex:ff","note":"hex:6865783a31","n":[1]}}"#, json);
+        assert_eq!(config, serde_json::from_str(&json).unwrap());
+        assert_eq!(bencode!([1, 0]), serde_json::from_str::<BencodeItem>("[true, false]").unwrap());
+        assert!(serde_json::from_str::<BencodeItem>("1.5").is_err());
+        assert!(serde_json::from_str::<BencodeItem>("\"hex:zz\"").is_err());
+    }
+
+    #[test]
+    fn bytes() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Info<'a> {
+            #[serde(with = "serde_bytes")]
+            pieces: Vec<u8>,
+            #[serde(borrow)]
+            root: &'a [u8],
+            buf: serde_bytes::ByteBuf,
+            hash: crate::ByteString,
+            infohash: [u8; 3],
+            plain: Vec<u8>,
+        }
+
+        let item = bencode!({
+            "pieces": crate::ByteString::new(vec!(0xFF; 3)),
+            "root": crate::ByteString::new(vec!(1)),
+            "buf": "b",
+            "hash": crate::ByteString::new(vec!(0x8A)),
+            "infohash": "abc",
+            "plain": [1, 2],
+        });
+        let info: Info = from_item(&item).unwrap();
+        assert_eq!(vec!(0xFF; 3), info.pieces);
+        // straight out of the tree, no copy
+        assert!(std::ptr::eq(info.root, item["root"].as_raw_bytes().unwrap()));
+        assert_eq!(b"b", info.buf.as_slice());
+        assert_eq!(crate::ByteString::new(vec!(0x8A)), info.hash);
+        assert_eq!(*b"abc", info.infohash);
+        assert_eq!(vec!(1, 2), info.plain);
+
+        assert!(from_item::<[u8; 4]>(&bencode!("abc")).is_err());
+        assert_eq!(Ok(crate::ByteString::from("x")), from_item(&bencode!("x")));
+        assert_eq!(Ok(b"x".to_vec()), super::from_bytes::<Vec<u8>>(b"1:x"));
+    }
+
+    // KRPC, the DHT's RPC protocol: "y" says whether it's a query, a response or an error
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Krpc {
+        t: serde_bytes::ByteBuf,
+        #[serde(flatten)]
+        body: KrpcBody,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "y")]
+    enum KrpcBody {
+        #[serde(rename = "q")]
+        Query { q: String, a: Args },
+        #[serde(rename = "r")]
+        Response { r: Args },
+        #[serde(rename = "e")]
+        Error { e: (i64, String) },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Args {
+        id: serde_bytes::ByteBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+        // an int rather than a bool: see the note on buffering in ser.rs
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        implied_port: Option<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "type", content = "value")]
+    enum Adjacent {
+        Length(u64),
+        Path(Vec<String>),
+        Empty,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Untagged {
+        Length(i64),
+        Name(String),
+        // before Bytes, which would take a list of small ints too
+        Files(Vec<Untagged>),
+        Bytes(serde_bytes::ByteBuf),
+        File { length: i64, path: Vec<String> },
+    }
+
+    #[test]
+    fn internally_tagged() {
+        let ping = Krpc {
+            t: serde_bytes::ByteBuf::from(b"aa".to_vec()),
+            body: KrpcBody::Query {
+                q: String::from("announce_peer"),
+                a: Args { id: serde_bytes::ByteBuf::from(vec!(0xFF; 2)), port: Some(6881), implied_port: Some(1) },
+            },
+        };
+        let bytes = crate::to_bytes(&ping).unwrap();
+        assert_eq!(b"d1:ad2:id2:\xFF\xFF12:implied_porti1e4:porti6881ee1:q13:announce_peer1:t2:aa1:y1:qe".to_vec(), bytes);
+        assert_eq!(Ok(&ping), super::from_bytes::<Krpc>(&bytes).as_ref());
+
+        let error = Krpc { t: serde_bytes::ByteBuf::from(b"aa".to_vec()), body: KrpcBody::Error { e: (201, String::from("A Generic Error Ocurred")) } };
+        let bytes = b"d1:eli201e23:A Generic Error Ocurrede1:t2:aa1:y1:ee";
+        assert_eq!(Ok(&error), super::from_bytes::<Krpc>(bytes).as_ref());
+        assert_eq!(bytes.to_vec(), crate::to_bytes(&error).unwrap());
+
+        assert!(super::from_bytes::<Krpc>(b"d1:t2:aa1:y1:xe").is_err());
+    }
+
+    #[test]
+    fn adjacently_tagged() {
+        for (value, item) in [
+            (Adjacent::Length(3), bencode!({"type": "Length", "value": 3})),
+            (Adjacent::Path(vec!(String::from("a"))), bencode!({"type": "Path", "value": ["a"]})),
+            (Adjacent::Empty, bencode!({"type": "Empty"})),
+        ] {
+            assert_eq!(Ok(&item), crate::to_item(&value).as_ref());
+            assert_eq!(Ok(&value), from_item::<Adjacent>(&item).as_ref());
+        }
+    }
+
+    #[test]
+    fn untagged() {
+        for (value, item) in [
+            (Untagged::Length(3), bencode!(3)),
+            (Untagged::Name(String::from("a")), bencode!("a")),
+            (Untagged::Bytes(serde_bytes::ByteBuf::from(vec!(0xFF))), bencode!(crate::ByteString::new(vec!(0xFF)))),
+            (Untagged::Files(vec!(Untagged::Length(1))), bencode!([1])),
+            (Untagged::File { length: 1, path: vec!(String::from("a")) }, bencode!({"length": 1, "path": ["a"]})),
+        ] {
+            assert_eq!(Ok(&item), crate::to_item(&value).as_ref());
+            assert_eq!(Ok(&value), from_item::<Untagged>(&item).as_ref());
+        }
+    }
+
+    #[test]
+    fn keys_and_errors() {
+        let map: BTreeMap<u32, bool> = from_item(&bencode!({"10": 1, "9": 0})).unwrap();
+        assert_eq!(vec!((9, false), (10, true)), map.into_iter().collect::<Vec<_>>());
+
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("expected string, found int"))),
+            from_item::<String>(&bencode!(1))
+        );
+        assert_eq!(Err(BencodeError::ValueOutOfRange(String::from("2 isn't a bool"))), from_item::<bool>(&bencode!(2)));
+        assert_eq!(Err(BencodeError::StrParse), from_item::<String>(&bencode!(crate::ByteString::new(vec!(0xFF)))));
+        assert!(matches!(from_item::<u8>(&bencode!(256)), Err(BencodeError::Serde(_))));
+        assert!(matches!(from_item::<(u8, u8)>(&bencode!([1, 2, 3])), Err(BencodeError::Serde(_))));
+        assert_eq!(Err(BencodeError::Serde(String::from("2 bytes left after the value"))), super::from_bytes::<u8>(b"i1eie"));
+    }
+}
@@ -1,17 +1,303 @@
 use core::slice::Iter;
+use std::collections::HashSet;
+use std::fmt;
 use std::iter::Peekable;
 use std::str::from_utf8;
 
-use crate::{BencodeItem, BencodeError, ByteString};
+use crate::{BencodeDict, BencodeItem, BencodeError, ByteString, ErrorKind};
 use crate::c;
 
+// upper bound on how much we'll reserve up front for a string, regardless of its declared length
+const MAX_STR_PREALLOC: usize = 16 * 1024 * 1024;
+// how many nodes we parse between calls to the caller's cancellation check
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+// limits used by DecodeOptions::network_untrusted, sized for protocol messages
+// (KRPC, tracker responses) rather than whole torrents
+const UNTRUSTED_MAX_BYTES: usize = 1024 * 1024;
+const UNTRUSTED_MAX_NODES: usize = 64 * 1024;
+// the parser recurses once per level, so this is what keeps nesting off the stack
+const UNTRUSTED_MAX_DEPTH: usize = 512;
+
+type OnWarning = dyn Fn(&DecodeWarning);
+
+#[derive(Default)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    pub max_bytes: Option<usize>,
+    pub max_nodes: Option<usize>,
+    // how many lists and dicts deep the input may go
+    pub max_depth: Option<usize>,
+    pub should_cancel: Option<Box<dyn Fn() -> bool>>,
+    // called with each DecodeWarning as it's found, whichever parse function is used
+    pub on_warning: Option<Box<OnWarning>>,
+    // accept ints and string lengths with leading zeros, and -0, instead of failing
+    pub lenient_numbers: bool,
+}
+
+impl DecodeOptions {
+    pub const fn new() -> Self {
+        DecodeOptions { max_bytes: None, max_nodes: None, max_depth: None, should_cancel: None, on_warning: None, lenient_numbers: false }
+    }
+
+    pub const fn network_untrusted() -> Self {
+        DecodeOptions::new()
+            .max_bytes(UNTRUSTED_MAX_BYTES)
+            .max_nodes(UNTRUSTED_MAX_NODES)
+            .max_depth(UNTRUSTED_MAX_DEPTH)
+    }
+
+    // accepts non-canonical numbers, the way EncodeOptions::lenient writes what it's given
+    pub const fn lenient() -> Self {
+        DecodeOptions::new().lenient_numbers(true)
+    }
+
+    // rejects non-canonical numbers; the same as new()
+    pub const fn strict() -> Self {
+        DecodeOptions::new().lenient_numbers(false)
+    }
+
+    pub const fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub const fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub const fn lenient_numbers(mut self, lenient_numbers: bool) -> Self {
+        self.lenient_numbers = lenient_numbers;
+        self
+    }
+
+    pub fn should_cancel<F: Fn() -> bool + 'static>(mut self, should_cancel: F) -> Self {
+        self.should_cancel = Some(Box::new(should_cancel));
+        self
+    }
+
+    pub fn on_warning<F: Fn(&DecodeWarning) + 'static>(mut self, on_warning: F) -> Self {
+        self.on_warning = Some(Box::new(on_warning));
+        self
+    }
+}
+
+// Spec violations the parser got past: dict keys out of order or repeated, which are
+// always accepted, and non-canonical numbers, which are accepted with lenient_numbers. Offsets
+// are from the start of the input, at the key in question, the `i` of an integer or the first
+// digit of a string length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeWarning {
+    UnsortedKey { offset: usize, key: String },
+    DuplicateKey { offset: usize, key: String },
+    LeadingZero { offset: usize },
+    NegativeZero { offset: usize },
+}
+
+impl DecodeWarning {
+    pub fn offset(&self) -> usize {
+        match self {
+            DecodeWarning::UnsortedKey { offset, .. }
+            | DecodeWarning::DuplicateKey { offset, .. }
+            | DecodeWarning::LeadingZero { offset }
+            | DecodeWarning::NegativeZero { offset } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeWarning::UnsortedKey { offset, key } => write!(f, "key \"{}\" out of order at byte {}", key, offset),
+            DecodeWarning::DuplicateKey { offset, key } => write!(f, "duplicate key \"{}\" at byte {}", key, offset),
+            DecodeWarning::LeadingZero { offset } => write!(f, "number with a leading zero at byte {}", offset),
+            DecodeWarning::NegativeZero { offset } => write!(f, "negative zero at byte {}", offset),
+        }
+    }
+}
+
+// limits and bookkeeping for one parse
+struct Budget<'a> {
+    options: &'a DecodeOptions,
+    start_len: usize,
+    nodes: usize,
+    // lists and dicts currently open
+    depth: usize,
+    // None unless the caller asked for a list of warnings
+    warnings: Option<Vec<DecodeWarning>>,
+}
+
+impl<'a> Budget<'a> {
+    fn new(options: &'a DecodeOptions, bytes_iter: &Peekable<Iter<u8>>) -> Self {
+        Budget { options, start_len: bytes_iter.len(), nodes: 0, depth: 0, warnings: None }
+    }
+
+    // whether anyone wants warnings, which saves looking for duplicates otherwise
+    fn wants_warnings(&self) -> bool {
+        self.warnings.is_some() || self.options.on_warning.is_some()
+    }
+
+    fn warn(&mut self, warning: DecodeWarning) {
+        if let Some(on_warning) = &self.options.on_warning {
+            on_warning(&warning);
+        }
+        if let Some(warnings) = &mut self.warnings {
+            warnings.push(warning);
+        }
+    }
+
+    // a non-canonical number: a warning with lenient_numbers, otherwise `error`
+    fn lenient(&mut self, warning: DecodeWarning, error: BencodeError) -> Result<(), BencodeError> {
+        if !self.options.lenient_numbers {
+            return Err(error)
+        }
+        self.warn(warning);
+        Ok(())
+    }
+
+    fn consumed(&self, bytes_iter: &Peekable<Iter<u8>>) -> usize {
+        self.start_len - bytes_iter.len()
+    }
+
+    // called once per parsed node
+    fn tick(&mut self, bytes_iter: &Peekable<Iter<u8>>) -> Result<(), BencodeError> {
+        self.nodes += 1;
+        if let Some(max_nodes) = self.options.max_nodes {
+            if self.nodes > max_nodes {
+                return Err(BencodeError::Cancelled)
+            }
+        }
+        self.check_bytes(bytes_iter, 0)?;
+        if let Some(should_cancel) = &self.options.should_cancel {
+            if self.nodes % CANCEL_CHECK_INTERVAL == 1 && should_cancel() {
+                return Err(BencodeError::Cancelled)
+            }
+        }
+        Ok(())
+    }
+
+    // called on entering a list or dict; leave() on the way out
+    fn enter(&mut self) -> Result<(), BencodeError> {
+        self.depth += 1;
+        match self.options.max_depth {
+            Some(max_depth) if self.depth > max_depth => Err(BencodeError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    // fails if consuming `upcoming` more bytes would go over the byte budget
+    fn check_bytes(&self, bytes_iter: &Peekable<Iter<u8>>, upcoming: usize) -> Result<(), BencodeError> {
+        if let Some(max_bytes) = self.options.max_bytes {
+            if self.consumed(bytes_iter).saturating_add(upcoming) > max_bytes {
+                return Err(BencodeError::Cancelled)
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn parse_bytes(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<BencodeItem, BencodeError> {
+    parse_bytes_with_options(bytes_iter, &DecodeOptions::default())
+}
+
+pub fn parse_bytes_with_options(bytes_iter: &mut Peekable<Iter<u8>>, options: &DecodeOptions) -> Result<BencodeItem, BencodeError> {
+    let mut budget = Budget::new(options, bytes_iter);
+    parse_item(bytes_iter, &mut budget)
+}
+
+// Like parse_bytes_with_options, but also returns what was wrong with the input that the
+// parser let through, so tools can say a file is malformed but loadable.
+pub fn parse_bytes_with_warnings(bytes_iter: &mut Peekable<Iter<u8>>, options: &DecodeOptions) -> Result<(BencodeItem, Vec<DecodeWarning>), BencodeError> {
+    let mut budget = Budget::new(options, bytes_iter);
+    budget.warnings = Some(vec!());
+    let item = parse_item(bytes_iter, &mut budget)?;
+    Ok((item, budget.warnings.unwrap_or_default()))
+}
+
+// For whole files: a syntax error gets the offset it happened at and a look at the bytes
+// around it, so a corrupt file can be diagnosed from the message alone. Other errors, like
+// cancellation, are passed through as they are.
+pub(crate) fn parse_with_context(bytes: &[u8], options: &DecodeOptions) -> Result<BencodeItem, BencodeError> {
+    let mut bytes_iter = bytes.iter().peekable();
+    parse_bytes_with_options(&mut bytes_iter, options).map_err(|error| {
+        if error.kind() != ErrorKind::Syntax {
+            return error
+        }
+        let offset = error_offset(&error).unwrap_or(bytes.len() - bytes_iter.len());
+        BencodeError::Parse { offset, excerpt: excerpt(bytes, offset), error: Box::new(error) }
+    })
+}
+
+// where in the input the error is, for errors that know better than where the parser stopped
+fn error_offset(error: &BencodeError) -> Option<usize> {
+    match error {
+        // the first byte that can't be part of the number, or the digits' start if they're
+        // all fine and the number just doesn't fit
+        BencodeError::IntParseInt { digits, offset, .. } => {
+            let bad = digits.iter().enumerate()
+                .position(|(i, &b)| !(b.is_ascii_digit() || (i == 0 && b == c::M_DASH)))
+                .unwrap_or(0);
+            Some(offset + bad)
+        },
+        _ => None,
+    }
+}
+
+const EXCERPT_RADIUS: usize = 8;
+
+// hex and ASCII of the bytes around `offset`, the byte at it in brackets:
+// "3a 69 31 [78] 65 | :i1[x]e"
+fn excerpt(bytes: &[u8], offset: usize) -> String {
+    let start = offset.saturating_sub(EXCERPT_RADIUS);
+    let end = bytes.len().min(offset + EXCERPT_RADIUS + 1);
+    let mut hex = vec!();
+    let mut ascii = String::new();
+    for (i, &b) in bytes[start..end].iter().enumerate() {
+        let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' };
+        if start + i == offset {
+            hex.push(format!("[{:02x}]", b));
+            ascii.push_str(&format!("[{}]", c));
+        } else {
+            hex.push(format!("{:02x}", b));
+            ascii.push(c);
+        }
+    }
+    if offset >= bytes.len() {
+        hex.push(String::from("[end]"));
+        ascii.push_str("[end]");
+    }
+    format!("{} | {}", hex.join(" "), ascii)
+}
+
+fn parse_item(bytes_iter: &mut Peekable<Iter<u8>>, budget: &mut Budget) -> Result<BencodeItem, BencodeError> {
+    budget.tick(bytes_iter)?;
     match bytes_iter.peek() {
         Some(&&b) => match b {
-            c::M_DICT => Ok(BencodeItem::Dict(read_dict(bytes_iter)?)),
-            c::M_INT => Ok(BencodeItem::Int(read_int(bytes_iter)?)),
-            c::M_LIST => Ok(BencodeItem::List(read_list(bytes_iter)?)),
-            c::M_0..=c::M_9 => Ok(BencodeItem::String(read_string(bytes_iter)?)),
+            c::M_DICT => {
+                budget.enter()?;
+                let d = read_dict(bytes_iter, budget)?;
+                budget.leave();
+                Ok(BencodeItem::Dict(d))
+            },
+            c::M_INT => Ok(BencodeItem::Int(read_int(bytes_iter, budget)?)),
+            c::M_LIST => {
+                budget.enter()?;
+                let l = read_list(bytes_iter, budget)?;
+                budget.leave();
+                Ok(BencodeItem::List(l))
+            },
+            c::M_0..=c::M_9 => Ok(BencodeItem::String(read_string(bytes_iter, budget)?)),
             c::M_END => Err(BencodeError::UnexpectedEndMarker),
             _ => Err(
                 BencodeError::UnrecognizedByte(format!("unrecognized byte: {}", b))
@@ -21,17 +307,23 @@ pub fn parse_bytes(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<BencodeItem, B
     }
 }
 
-fn read_dict(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<Vec<(String, BencodeItem)>, BencodeError> {
+fn read_dict(bytes_iter: &mut Peekable<Iter<u8>>, budget: &mut Budget) -> Result<BencodeDict, BencodeError> {
     // consume 'd'
     bytes_iter.next();
-    let mut res: Vec<(String, BencodeItem)> = vec!();
+    let mut res = BencodeDict::new();
     // empty dict
     if let Some(&&c::M_END) = bytes_iter.peek() {
+        bytes_iter.next(); // consume 'e'
         return Ok(res)
     }
+    let mut key_order = KeyOrder::default();
     loop {
-        if let Ok(key) = String::try_from(&read_string(bytes_iter)?) {
-            res.push((key, parse_bytes(bytes_iter)?));
+        let offset = budget.consumed(bytes_iter);
+        if let Ok(key) = String::try_from(&read_string(bytes_iter, budget)?) {
+            if budget.wants_warnings() {
+                key_order.check(&res, &key, offset, budget);
+            }
+            res.push(key, parse_item(bytes_iter, budget)?);
         } else {
             return Err(BencodeError::DictKeyParse)
         }
@@ -44,7 +336,32 @@ fn read_dict(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<Vec<(String, Bencode
     Ok(res)
 }
 
-fn read_list(mut bytes_iter: &mut Peekable<Iter<u8>>) -> Result<Vec<BencodeItem>, BencodeError> {
+// One dict's keys, for warnings. Keys in order can't repeat, so nothing is kept until the
+// order first breaks; from then on each key is checked against every earlier one.
+#[derive(Default)]
+struct KeyOrder {
+    seen: Option<HashSet<String>>,
+}
+
+impl KeyOrder {
+    fn check(&mut self, res: &BencodeDict, key: &str, offset: usize, budget: &mut Budget) {
+        let out_of_order = res.iter().last().is_some_and(|(last, _)| last.as_bytes() >= key.as_bytes());
+        if out_of_order && self.seen.is_none() {
+            self.seen = Some(res.iter().map(|(k, _)| k.clone()).collect());
+        }
+        let Some(seen) = &mut self.seen else {
+            return
+        };
+        let key = String::from(key);
+        if !seen.insert(key.clone()) {
+            budget.warn(DecodeWarning::DuplicateKey { offset, key });
+        } else if out_of_order {
+            budget.warn(DecodeWarning::UnsortedKey { offset, key });
+        }
+    }
+}
+
+fn read_list(bytes_iter: &mut Peekable<Iter<u8>>, budget: &mut Budget) -> Result<Vec<BencodeItem>, BencodeError> {
     // consume 'l'
     bytes_iter.next();
 
@@ -57,7 +374,7 @@ fn read_list(mut bytes_iter: &mut Peekable<Iter<u8>>) -> Result<Vec<BencodeItem>
                 break;
             },
             Some(_) => {
-                res.push(parse_bytes(&mut bytes_iter)?);
+                res.push(parse_item(bytes_iter, budget)?);
             },
             None => return Err(BencodeError::BytestreamEnded),
         }
@@ -65,9 +382,10 @@ fn read_list(mut bytes_iter: &mut Peekable<Iter<u8>>) -> Result<Vec<BencodeItem>
     Ok(res)
 }
 
-fn read_int(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<i64, BencodeError> {
+fn read_int(bytes_iter: &mut Peekable<Iter<u8>>, budget: &mut Budget) -> Result<i64, BencodeError> {
     let mut buff: Vec<u8> = vec!();
     let mut b: &u8;
+    let offset = budget.consumed(bytes_iter);
 
     // consume 'i'
     bytes_iter.next();
@@ -79,35 +397,34 @@ fn read_int(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<i64, BencodeError> {
             return Err(BencodeError::BytestreamEnded)
         }
         b = curr_byte.unwrap();
-        if buff.len() == 0 && *b == c::M_END {
+        if buff.is_empty() && *b == c::M_END {
             return Err(BencodeError::UnexpectedEndMarker)
         } else if *b == c::M_END {
             break;
         }
-        // -0 not allowed
-        if *b == c::M_DASH {
-            if let Some(&&c::M_0) = bytes_iter.peek() {
-                return Err(BencodeError::IntParseNegativeZero)
-            }
-        }
-        // leading zeros not allowed
-        if buff.len() == 0 && *b == c::M_0 {
-            if let Some(&&c::M_END) = bytes_iter.peek() {} else {
-                return Err(BencodeError::IntParseLeadingZero)
+        // a zero up front must be the whole number: "-0" is a negative zero, "-01" and "01"
+        // have leading zeros
+        if *b == c::M_0 && (buff.is_empty() || buff == [c::M_DASH]) {
+            match (buff.is_empty(), bytes_iter.peek()) {
+                (true, Some(&&c::M_END)) => (),
+                (false, Some(&&c::M_END)) => {
+                    budget.lenient(DecodeWarning::NegativeZero { offset }, BencodeError::IntParseNegativeZero)?
+                },
+                _ => budget.lenient(DecodeWarning::LeadingZero { offset }, BencodeError::IntParseLeadingZero)?,
             }
         }
         buff.push(*b);
     }
 
-    let res = ascii_bytes_to_int(&buff);
-    res
+    ascii_bytes_to_int(&buff, offset + 1)
 }
 
-fn ascii_bytes_to_int(bytes: &Vec<u8>) -> Result<i64, BencodeError> {
-    match from_utf8(&bytes) {
+// `offset` is where the digits start, for the error
+pub(crate) fn ascii_bytes_to_int(bytes: &[u8], offset: usize) -> Result<i64, BencodeError> {
+    match from_utf8(bytes) {
         Ok(s) => match s.parse::<i64>() {
             Ok(i) => Ok(i),
-            Err(e) => Err(BencodeError::IntParseInt(format!("{}", e))),
+            Err(error) => Err(BencodeError::IntParseInt { error, digits: bytes.to_vec(), offset }),
         },
         Err(e) => Err(BencodeError::IntParseAscii(e))
     }
@@ -124,23 +441,21 @@ impl TryFrom<&ByteString> for String {
     }
 }
 
-fn read_string(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<ByteString, BencodeError> {
+fn read_string(bytes_iter: &mut Peekable<Iter<u8>>, budget: &mut Budget) -> Result<ByteString, BencodeError> {
     let mut len_buff = vec!();
+    let offset = budget.consumed(bytes_iter);
     loop {
         let b = bytes_iter.next();
         match b {
             Some(&c::M_COLON) => break,
             Some(c::M_0..=c::M_9) => {
                 // empty string handling
-                if len_buff.len() == 0 {
-                    if *b.unwrap() == c::M_0 {
-                        if let Some(&&c::M_COLON) = bytes_iter.peek() {
-                            bytes_iter.next(); // consume the colon
-                            return Ok(ByteString::new(vec!()));
-                        } else {
-                            return Err(BencodeError::StrParseLeadingZero);
-                        }
+                if len_buff.is_empty() && *b.unwrap() == c::M_0 {
+                    if let Some(&&c::M_COLON) = bytes_iter.peek() {
+                        bytes_iter.next(); // consume the colon
+                        return Ok(ByteString::new(vec!()));
                     }
+                    budget.lenient(DecodeWarning::LeadingZero { offset }, BencodeError::StrParseLeadingZero)?;
                 }
                 len_buff.push(*b.unwrap())
             },
@@ -148,16 +463,22 @@ fn read_string(bytes_iter: &mut Peekable<Iter<u8>>) -> Result<ByteString, Bencod
             None => return Err(BencodeError::BytestreamEnded),
         }
     }
-    let str_len = ascii_bytes_to_int(&len_buff)?;
+    let str_len = ascii_bytes_to_int(&len_buff, offset)?;
+    budget.check_bytes(bytes_iter, usize::try_from(str_len).unwrap_or(usize::MAX))?;
+    // reserve once for the whole string, but never more than what's left in the input,
+    // so a bogus length prefix can't make us allocate gigabytes up front
+    let capacity = usize::try_from(str_len).unwrap_or(usize::MAX)
+        .min(bytes_iter.len())
+        .min(MAX_STR_PREALLOC);
     let mut i = 0;
-    let mut str_buff: Vec<u8> = vec!();
+    let mut str_buff: Vec<u8> = Vec::with_capacity(capacity);
     while i < str_len {
         if let Some(b) = bytes_iter.next() {
             str_buff.push(*b);
         } else {
             return Err(BencodeError::BytestreamEnded);
         }
-        i = i + 1;
+        i += 1;
     }
     Ok(ByteString::new(str_buff))
 }
@@ -168,7 +489,8 @@ mod tests {
 
     macro_rules! assert_bytes_eq {
         ($bytes:expr, $expected:expr) => {
-            match parse_bytes(&mut $bytes.iter().peekable()) {
+            let bytes: Vec<u8> = $bytes;
+            match parse_bytes(&mut bytes.iter().peekable()) {
                 Ok(r) => assert_eq!($expected, r),
                 Err(e) => panic!("Unexpected err: {:?}", e)
             }
@@ -177,7 +499,8 @@ mod tests {
 
     macro_rules! assert_bytes_err {
         ($bytes:expr, $expected:expr) => {
-            match parse_bytes(&mut $bytes.iter().peekable()) {
+            let bytes: Vec<u8> = $bytes;
+            match parse_bytes(&mut bytes.iter().peekable()) {
                 Ok(e) => panic!("Unexpected ok: {:?}. Expected err: {:?}", e, $expected),
                 Err(r) => assert_eq!($expected, r)
             }
@@ -186,13 +509,13 @@ mod tests {
 
     macro_rules! bencode_string {
         ($literal:expr) => {
-            ByteString::new($literal.as_bytes().to_vec())
+            ByteString::from($literal)
         };
     }
 
     #[test]
     fn dict() {
-        assert_bytes_eq!(vec!(0x64, 0x65), BencodeItem::Dict(vec!()));
+        assert_bytes_eq!(vec!(0x64, 0x65), BencodeItem::Dict(vec!().into()));
 
         assert_bytes_eq!(
             vec!(0x64, 0x35, 0x3A, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x35, 0x3A, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x35, 0x3A, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x35, 0x3A, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x65),
@@ -200,18 +523,42 @@ mod tests {
                 vec!(
                     (String::from("Hello"), BencodeItem::String(bencode_string!("World"))),
                     (String::from("World"), BencodeItem::String(bencode_string!("Hello")))
-                )
+                ).into()
             )
         );
 
         assert_bytes_eq!(
             vec!(0x64, 0x35, 0x3A, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x69, 0x31, 0x32, 0x33, 0x65, 0x65),
             BencodeItem::Dict(
-                vec!((String::from("Hello"), BencodeItem::Int(123)))
+                vec!((String::from("Hello"), BencodeItem::Int(123))).into()
             )
         );
     }
 
+    #[test]
+    fn context_offsets() {
+        let offset = |bytes: &[u8]| match parse_with_context(bytes, &DecodeOptions::new()) {
+            Err(BencodeError::Parse { offset, .. }) => Some(offset),
+            _ => None,
+        };
+        assert_eq!(Some(4), offset(b"li1e-1e"));
+        assert_eq!(Some(7), offset(b"li1ei-1-e"));
+        // too big, but every digit is fine: the number's first digit
+        assert_eq!(Some(2), offset(b"li99999999999999999999e"));
+        assert_eq!(Some(4), offset(b"li1ex"));
+    }
+
+    #[test]
+    fn empty_dict_consumes_end() {
+        // the 'e' of an empty dict used to be left behind, ending the enclosing list early
+        assert_bytes_eq!(b"ldei1ee".to_vec(), BencodeItem::List(vec!(BencodeItem::Dict(vec!().into()), BencodeItem::Int(1))));
+        assert_bytes_eq!(b"d1:adee".to_vec(), bencode!({"a": {}}));
+        let bytes = b"dei1e";
+        let mut bytes_iter = bytes.iter().peekable();
+        assert_eq!(Ok(bencode!({})), parse_bytes(&mut bytes_iter));
+        assert_eq!(Some(&&b'i'), bytes_iter.peek());
+    }
+
     #[test]
     fn list() {
         assert_bytes_eq!(vec!(0x6C, 0x65), BencodeItem::List(vec!()));
@@ -247,6 +594,12 @@ mod tests {
         assert_bytes_err!(vec!(0x31, 0x30, 0x78, 0x3A, 0x7A), BencodeError::StrLenInvalidByte);
         assert_bytes_eq!(vec!(0x31, 0x3A, 0x8A), BencodeItem::String(ByteString::new(vec!(0x8A))));
         assert_bytes_err!(vec!(0x31, 0x30, 0x3A, 0x7A), BencodeError::BytestreamEnded);
+        // declared length far beyond the input must not be trusted for allocation
+        assert_bytes_err!(b"999999999999:x".to_vec(), BencodeError::BytestreamEnded);
+
+        let mut long = b"100000:".to_vec();
+        long.extend(vec!(0x78; 100000));
+        assert_bytes_eq!(long, BencodeItem::String(ByteString::new(vec!(0x78; 100000))));
     }
 
     #[test]
@@ -259,8 +612,128 @@ mod tests {
         assert_bytes_err!(vec!(0x69, 0x2D, 0x30, 0x65), BencodeError::IntParseNegativeZero);
         assert_bytes_err!(vec!(0x69, 0x30, 0x30, 0x30, 0x65), BencodeError::IntParseLeadingZero);
         assert_bytes_err!(vec!(0x69, 0x30, 0x30, 0x31, 0x65), BencodeError::IntParseLeadingZero);
-        assert_bytes_err!(vec!(0x69, 0x3A, 0x65), BencodeError::IntParseInt(format!("invalid digit found in string")));
+        assert_bytes_err!(b"i-01e".to_vec(), BencodeError::IntParseLeadingZero);
+        assert_bytes_err!(b"i-00e".to_vec(), BencodeError::IntParseLeadingZero);
+        assert_bytes_err!(vec!(0x69, 0x3A, 0x65), BencodeError::IntParseInt { error: ":".parse::<i64>().unwrap_err(), digits: vec!(0x3A), offset: 1 });
         assert_bytes_err!(vec!(0x69, 0x65), BencodeError::UnexpectedEndMarker);
         assert_bytes_err!(vec!(0x65, 0x69), BencodeError::UnexpectedEndMarker);
     }
+
+    #[test]
+    fn budget() {
+        let bytes = b"l5:Helloi1337eli7eee".to_vec();
+        let parse = |options: &DecodeOptions| parse_bytes_with_options(&mut bytes.iter().peekable(), options);
+
+        assert!(parse(&DecodeOptions::default()).is_ok());
+        assert!(parse(&DecodeOptions { max_nodes: Some(5), ..Default::default() }).is_ok());
+        assert_eq!(Err(BencodeError::Cancelled), parse(&DecodeOptions { max_nodes: Some(4), ..Default::default() }));
+        assert!(parse(&DecodeOptions { max_bytes: Some(bytes.len()), ..Default::default() }).is_ok());
+        assert_eq!(Err(BencodeError::Cancelled), parse(&DecodeOptions { max_bytes: Some(5), ..Default::default() }));
+        assert_eq!(Err(BencodeError::Cancelled), parse(&DecodeOptions { should_cancel: Some(Box::new(|| true)), ..Default::default() }));
+        assert!(parse(&DecodeOptions { should_cancel: Some(Box::new(|| false)), ..Default::default() }).is_ok());
+
+        assert!(parse(&DecodeOptions::new().max_nodes(5).max_bytes(bytes.len())).is_ok());
+        assert_eq!(Err(BencodeError::Cancelled), parse(&DecodeOptions::new().should_cancel(|| true)));
+        assert!(parse(&DecodeOptions::network_untrusted()).is_ok());
+        let mut huge = b"99999999:".to_vec();
+        huge.extend(vec!(0x78; 2 * 1024 * 1024));
+        assert_eq!(Err(BencodeError::Cancelled), parse_bytes_with_options(&mut huge.iter().peekable(), &DecodeOptions::network_untrusted()));
+
+        assert!(parse(&DecodeOptions::new().max_depth(2)).is_ok());
+        assert_eq!(Err(BencodeError::Cancelled), parse(&DecodeOptions::new().max_depth(1)));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        // far below the byte and node caps, but deep enough to overflow the stack unchecked
+        let mut deep = vec!(c::M_LIST; 80_000);
+        deep.extend(vec!(c::M_END; 80_000));
+        let res = parse_bytes_with_options(&mut deep.iter().peekable(), &DecodeOptions::network_untrusted());
+        assert_eq!(Some(ErrorKind::Limit), res.err().map(|e| e.kind()));
+
+        let mut dicts = b"d1:a".repeat(600);
+        dicts.extend(b"i1e".iter().chain(&vec!(c::M_END; 600)));
+        let res = parse_bytes_with_options(&mut dicts.iter().peekable(), &DecodeOptions::network_untrusted());
+        assert_eq!(Some(ErrorKind::Limit), res.err().map(|e| e.kind()));
+        assert!(parse_bytes_with_options(&mut dicts.iter().peekable(), &DecodeOptions::new().max_depth(600)).is_ok());
+    }
+
+    #[test]
+    fn warnings() {
+        let parse = |bytes: &[u8], options: &DecodeOptions| parse_bytes_with_warnings(&mut bytes.iter().peekable(), options);
+
+        let bytes = b"d1:bi1e1:ai2e1:ci3e1:bi4ee";
+        let (item, warnings) = parse(bytes, &DecodeOptions::new()).unwrap();
+        assert_eq!(Ok(item), parse_bytes(&mut bytes.iter().peekable()));
+        assert_eq!(
+            vec!(
+                DecodeWarning::UnsortedKey { offset: 7, key: String::from("a") },
+                DecodeWarning::DuplicateKey { offset: 19, key: String::from("b") },
+            ),
+            warnings
+        );
+        assert_eq!("key \"a\" out of order at byte 7", warnings[0].to_string());
+        assert_eq!(Ok((bencode!({"a": 1}), vec!())), parse(b"d1:ai1ee", &DecodeOptions::new()));
+
+        // a repeat that happens to sort after the key before it
+        let (_, warnings) = parse(b"d1:ai1e1:ci1e1:bi1e1:ci1ee", &DecodeOptions::new()).unwrap();
+        assert_eq!(
+            vec!(
+                DecodeWarning::UnsortedKey { offset: 13, key: String::from("b") },
+                DecodeWarning::DuplicateKey { offset: 19, key: String::from("c") },
+            ),
+            warnings
+        );
+
+        // numbers are only let through with lenient_numbers
+        let bytes = b"li03e03:abci-0ee";
+        assert_eq!(Err(BencodeError::IntParseLeadingZero), parse(bytes, &DecodeOptions::new()));
+        assert_eq!(
+            Ok((bencode!([3, "abc", 0]), vec!(
+                DecodeWarning::LeadingZero { offset: 1 },
+                DecodeWarning::LeadingZero { offset: 5 },
+                DecodeWarning::NegativeZero { offset: 11 },
+            ))),
+            parse(bytes, &DecodeOptions::new().lenient_numbers(true))
+        );
+        assert_eq!(Ok(bencode!([3, "abc", 0])), parse_bytes_with_options(&mut bytes.iter().peekable(), &DecodeOptions::new().lenient_numbers(true)));
+        assert_eq!(Ok(bencode!([3, "abc", 0])), parse_bytes_with_options(&mut bytes.iter().peekable(), &DecodeOptions::lenient()));
+        assert_eq!(Err(BencodeError::IntParseLeadingZero), parse_bytes_with_options(&mut bytes.iter().peekable(), &DecodeOptions::strict()));
+
+        // only a lone "-0" is a negative zero; offsets are at the `i`
+        assert_eq!(
+            Ok((bencode!([-1, 0]), vec!(
+                DecodeWarning::LeadingZero { offset: 1 },
+                DecodeWarning::NegativeZero { offset: 6 },
+            ))),
+            parse(b"li-01ei-0ee", &DecodeOptions::lenient())
+        );
+    }
+
+    #[test]
+    fn on_warning() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let seen = Rc::new(RefCell::new(vec!()));
+        let sink = Rc::clone(&seen);
+        let options = DecodeOptions::new()
+            .lenient_numbers(true)
+            .on_warning(move |w| sink.borrow_mut().push((w.offset(), w.to_string())));
+        let item = parse_bytes_with_options(&mut b"d1:bi01e1:ai1ee".iter().peekable(), &options);
+        assert_eq!(Ok(bencode!({"b": 1, "a": 1})), item);
+        assert_eq!(
+            vec!(
+                (4, String::from("number with a leading zero at byte 4")),
+                (8, String::from("key \"a\" out of order at byte 8")),
+            ),
+            *seen.borrow()
+        );
+
+        // and alongside a returned list
+        seen.borrow_mut().clear();
+        let (_, warnings) = parse_bytes_with_warnings(&mut b"d1:bi1e1:ai1ee".iter().peekable(), &options).unwrap();
+        assert_eq!(1, warnings.len());
+        assert_eq!(1, seen.borrow().len());
+    }
 }
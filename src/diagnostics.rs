@@ -0,0 +1,229 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::decoder::parse_bytes_with_options;
+use crate::tokenizer::{TokenKind, Tokenizer};
+use crate::{BencodeError, BencodeItem, DecodeOptions};
+
+// Parse errors rendered for people, the way a compiler would: what went wrong, where, and
+// a hexdump of the input around it with the offending bytes underlined.
+//
+//     error: invalid int "1x0" at byte 36: invalid digit found in string
+//      --> byte 35, at info/length
+//     00000010  3a 2f 2f 78 34 3a 69 6e  66 6f 64 36 3a 6c 65 6e  |://x4:infod6:len|
+//     00000020  67 74 68 69 31 78 30 65  65 65                    |gthi1x0eee|
+//                        ^^ ^^ ^^ ^^ ^^                               ^^^^^
+//
+// The path is found by re-reading the input up to the failure with a Tokenizer, so it's
+// only as good as the structure before that point.
+
+const ROW_LEN: usize = 16;
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub error: BencodeError,
+    // the bytes at fault: the token that didn't parse, or the single byte the parser
+    // stopped at; it may end one past the input when the input ran out
+    pub span: Range<usize>,
+    // slash-separated, like get_path; "" when the failure is at the top level
+    pub path: String,
+    // whole rows of the input around the span, starting at `window_start`
+    window_start: usize,
+    window: Vec<u8>,
+}
+
+pub fn diagnose(bytes: &[u8], options: &DecodeOptions) -> Result<BencodeItem, Diagnostic> {
+    let mut bytes_iter = bytes.iter().peekable();
+    parse_bytes_with_options(&mut bytes_iter, options)
+        .map_err(|error| Diagnostic::new(bytes, bytes.len() - bytes_iter.len(), error))
+}
+
+impl Diagnostic {
+    // `offset` is where the parser stopped
+    fn new(bytes: &[u8], offset: usize, error: BencodeError) -> Diagnostic {
+        let (path, token_start) = locate(bytes, offset);
+        let span = match token_start {
+            Some(start) => start..offset.max(start + 1),
+            None => offset..offset + 1,
+        };
+        // a row either side of the span, but never rows past the end of the input
+        let last_row = bytes.len().min(span.end - 1) / ROW_LEN;
+        let window_start = (span.start / ROW_LEN).saturating_sub(1) * ROW_LEN;
+        let window_end = bytes.len().min((last_row + 2) * ROW_LEN);
+        let window = bytes[window_start.min(window_end)..window_end].to_vec();
+        Diagnostic { error, span, path, window_start, window }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "error: {}", self.error)?;
+        write!(f, " --> byte {}", self.span.start)?;
+        if !self.path.is_empty() {
+            write!(f, ", at {}", self.path)?;
+        }
+        let window_end = self.window_start + self.window.len();
+        let mut row_start = self.window_start;
+        // the span can end past the input, so its row may hold no bytes at all
+        while row_start < window_end || row_start < self.span.end {
+            let row = &self.window[row_start.min(window_end) - self.window_start..window_end.min(row_start + ROW_LEN) - self.window_start];
+            writeln!(f)?;
+            write_row(f, row_start, row)?;
+            let marked = self.span.start.max(row_start)..self.span.end.min(row_start + ROW_LEN);
+            if !marked.is_empty() {
+                writeln!(f)?;
+                write_carets(f, marked.start - row_start..marked.end - row_start)?;
+            }
+            row_start += ROW_LEN;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// hexdump -C layout
+fn write_row(f: &mut fmt::Formatter, offset: usize, row: &[u8]) -> fmt::Result {
+    let mut hex = String::new();
+    let mut ascii = String::new();
+    for i in 0..ROW_LEN {
+        if i == ROW_LEN / 2 {
+            hex.push(' ');
+        }
+        match row.get(i) {
+            Some(b) => {
+                hex.push_str(&format!("{:02x} ", b));
+                ascii.push(if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' });
+            },
+            None => hex.push_str("   "),
+        }
+    }
+    write!(f, "{:08x}  {} |{}|", offset, hex, ascii)
+}
+
+// lined up with write_row, under both the hex and the ASCII columns
+fn write_carets(f: &mut fmt::Formatter, marked: Range<usize>) -> fmt::Result {
+    let mut hex = String::new();
+    let mut ascii = String::new();
+    for i in 0..ROW_LEN {
+        if i == ROW_LEN / 2 {
+            hex.push(' ');
+        }
+        let mark = marked.contains(&i);
+        hex.push_str(if mark { "^^ " } else { "   " });
+        ascii.push(if mark { '^' } else { ' ' });
+    }
+    let line = format!("{:8}  {}  {}", "", hex, ascii);
+    f.write_str(line.trim_end())
+}
+
+enum Frame {
+    // the index of the next element
+    List(usize),
+    // the key whose value comes next, or None when a key comes next
+    Dict(Option<String>),
+}
+
+impl Frame {
+    fn value_done(&mut self) {
+        match self {
+            Frame::List(i) => *i += 1,
+            Frame::Dict(key) => *key = None,
+        }
+    }
+}
+
+// Where in the tree `offset` falls, and if a token starting before it doesn't parse, where
+// that token starts
+fn locate(bytes: &[u8], offset: usize) -> (String, Option<usize>) {
+    let mut tokenizer = Tokenizer::new(bytes);
+    let mut stack: Vec<Frame> = vec!();
+    let mut bad_token = None;
+    while tokenizer.position() < offset {
+        let start = tokenizer.position();
+        let token = match tokenizer.next_token() {
+            Ok(Some(token)) => token,
+            Ok(None) => break,
+            Err(_) => {
+                bad_token = Some(start);
+                break
+            },
+        };
+        match (token.kind, stack.last_mut()) {
+            (TokenKind::DictStart, _) => stack.push(Frame::Dict(None)),
+            (TokenKind::ListStart, _) => stack.push(Frame::List(0)),
+            (TokenKind::End, _) => {
+                stack.pop();
+                if let Some(frame) = stack.last_mut() {
+                    frame.value_done();
+                }
+            },
+            (TokenKind::String(key), Some(Frame::Dict(next @ None))) => {
+                *next = Some(String::from_utf8_lossy(key).into_owned());
+            },
+            (_, Some(frame)) => frame.value_done(),
+            (_, None) => (),
+        }
+    }
+    let segments: Vec<String> = stack.iter().map_while(|frame| match frame {
+        Frame::List(i) => Some(i.to_string()),
+        Frame::Dict(key) => key.clone(),
+    }).collect();
+    (segments.join("/"), bad_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(bytes: &[u8]) -> Diagnostic {
+        diagnose(bytes, &DecodeOptions::new()).unwrap_err()
+    }
+
+    #[test]
+    fn render() {
+        let d = diagnostic(b"d8:announce7:udp://x4:infod6:lengthi1x0eee");
+        assert_eq!(35..40, d.span);
+        assert_eq!("info/length", d.path);
+        assert_eq!(
+            "error: invalid int \"1x0\" at byte 36: invalid digit found in string\n \
+             --> byte 35, at info/length\n\
+             00000010  3a 2f 2f 78 34 3a 69 6e  66 6f 64 36 3a 6c 65 6e  |://x4:infod6:len|\n\
+             00000020  67 74 68 69 31 78 30 65  65 65                    |gthi1x0eee|\n\
+             \x20                  ^^ ^^ ^^ ^^ ^^                               ^^^^^",
+            d.to_string()
+        );
+        assert_eq!(crate::ErrorKind::Syntax, d.error.kind());
+    }
+
+    #[test]
+    fn locations() {
+        let d = diagnostic(b"d1:ald1:bi1e1:ceee");
+        assert_eq!(BencodeError::UnexpectedEndMarker, d.error);
+        assert_eq!(15..16, d.span);
+        assert_eq!("a/0/c", d.path);
+
+        let d = diagnostic(b"li1ed1:ai2e");
+        assert_eq!(BencodeError::BytestreamEnded, d.error);
+        assert_eq!(11..12, d.span);
+        assert_eq!("1", d.path);
+        assert_eq!(
+            "error: input ended in the middle of a value\n \
+             --> byte 11, at 1\n\
+             00000000  6c 69 31 65 64 31 3a 61  69 32 65                 |li1ed1:ai2e|\n\
+             \x20                                           ^^                          ^",
+            d.to_string()
+        );
+
+        let d = diagnostic(b"x");
+        assert_eq!("", d.path);
+        assert_eq!(0..1, d.span);
+        assert!(d.to_string().starts_with("error: unrecognized byte: 120\n --> byte 0\n"));
+
+        assert_eq!(Ok(bencode!({"a": 1})), diagnose(b"d1:ai1ee", &DecodeOptions::new()).map_err(|d| d.error));
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, HashMap};
+use std::slice;
+use std::vec;
+
+use crate::{BencodeError, BencodeItem, PathSegment};
+use crate::path::path_string;
+use crate::walk::with_segment;
+
+// what dedup_keys does with a key that appears more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    // the entry get() would return
+    KeepFirst,
+    KeepLast,
+    // fail with DuplicateKey
+    Reject,
+}
+
+// A dict's entries in the order they were added (or decoded). Lookups are linear scans:
+// metainfo dicts have a handful of keys, and keeping the order is what lets a decoded
+// file be written back byte for byte. Duplicate keys can only come from decoding or
+// push; get, get_mut and remove all act on the first entry for a key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BencodeDict {
+    entries: Vec<(String, BencodeItem)>,
+}
+
+impl BencodeDict {
+    pub fn new() -> Self {
+        BencodeDict { entries: vec!() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        BencodeDict { entries: Vec::with_capacity(capacity) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    // the entry list and every key; values are left alone
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        for (k, _) in &mut self.entries {
+            k.shrink_to_fit();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&BencodeItem> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut BencodeItem> {
+        self.entries.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    // replaces the value in place if `key` is already there, otherwise appends
+    pub fn insert<K: Into<String>>(&mut self, key: K, value: BencodeItem) -> Option<BencodeItem> {
+        let key = key.into();
+        match self.get_mut(&key) {
+            Some(v) => Some(std::mem::replace(v, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    // appends even if `key` is already there
+    pub fn push<K: Into<String>>(&mut self, key: K, value: BencodeItem) {
+        self.entries.push((key.into(), value));
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<BencodeItem> {
+        let i = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(i).1)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, (String, BencodeItem)> {
+        self.entries.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, (String, BencodeItem)> {
+        self.entries.iter_mut()
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.entries.iter().map(|(k, _)| k.as_str())
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &BencodeItem> + ExactSizeIterator {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut BencodeItem> + ExactSizeIterator {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+
+    pub fn into_values(self) -> impl DoubleEndedIterator<Item = BencodeItem> + ExactSizeIterator {
+        self.entries.into_iter().map(|(_, v)| v)
+    }
+
+    pub fn as_slice(&self) -> &[(String, BencodeItem)] {
+        &self.entries
+    }
+
+    pub fn into_vec(self) -> Vec<(String, BencodeItem)> {
+        self.entries
+    }
+
+    pub fn retain<F: FnMut(&str, &BencodeItem) -> bool>(&mut self, mut f: F) {
+        self.entries.retain(|(k, v)| f(k, v));
+    }
+
+    // by key, and str order is raw byte order, which is what canonical bencode needs; stable,
+    // so duplicates keep their relative order
+    pub fn sort_keys(&mut self) {
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    // leaves one entry per key, each in the position of the entry that was kept
+    pub fn dedup_keys(&mut self, policy: DuplicatePolicy) -> Result<(), BencodeError> {
+        let mut seen = std::collections::HashSet::new();
+        let mut keep = vec!(false; self.entries.len());
+        let order: Box<dyn Iterator<Item = usize>> = match policy {
+            DuplicatePolicy::KeepLast => Box::new((0..self.entries.len()).rev()),
+            _ => Box::new(0..self.entries.len()),
+        };
+        for i in order {
+            let key = self.entries[i].0.as_str();
+            if seen.insert(key) {
+                keep[i] = true;
+            } else if policy == DuplicatePolicy::Reject {
+                return Err(BencodeError::DuplicateKey(String::from(key)))
+            }
+        }
+        let mut keep = keep.into_iter();
+        self.entries.retain(|_| keep.next().unwrap_or(false));
+        Ok(())
+    }
+
+    // Map conversions. Going to a map keeps the first entry for a duplicated key, like get;
+    // coming from a HashMap sorts the keys so the result doesn't depend on hash order.
+    pub fn into_btreemap(self) -> BTreeMap<String, BencodeItem> {
+        let mut map = BTreeMap::new();
+        for (k, v) in self.entries {
+            map.entry(k).or_insert(v);
+        }
+        map
+    }
+
+    pub fn into_hashmap(self) -> HashMap<String, BencodeItem> {
+        let mut map = HashMap::with_capacity(self.entries.len());
+        for (k, v) in self.entries {
+            map.entry(k).or_insert(v);
+        }
+        map
+    }
+
+    pub fn from_btreemap(map: BTreeMap<String, BencodeItem>) -> Self {
+        map.into_iter().collect()
+    }
+
+    pub fn from_hashmap(map: HashMap<String, BencodeItem>) -> Self {
+        let mut d: BencodeDict = map.into_iter().collect();
+        d.sort_keys();
+        d
+    }
+
+    // for code that needs to place entries by position
+    pub(crate) fn entries_mut(&mut self) -> &mut Vec<(String, BencodeItem)> {
+        &mut self.entries
+    }
+}
+
+impl BencodeItem {
+    // sort_keys on every dict in the tree; with dedup_keys, the tree encodes canonically
+    pub fn sort_keys(&mut self) {
+        self.walk_mut(|_, node| {
+            if let BencodeItem::Dict(d) = node {
+                d.sort_keys();
+            }
+        });
+    }
+
+    // Drops dict entries and list items anywhere in the tree that `f` turns down. `f` sees
+    // the path of the node in question (the key or index is the last segment) and the
+    // node; a dropped node's children aren't visited.
+    pub fn retain_recursive<F: FnMut(&[PathSegment], &BencodeItem) -> bool>(&mut self, mut f: F) {
+        self.walk_mut(|path, node| match node {
+            BencodeItem::Dict(d) => d.retain(|k, v| f(&with_segment(path, PathSegment::Key(k)), v)),
+            BencodeItem::List(l) => {
+                let mut i = 0;
+                l.retain(|v| {
+                    i += 1;
+                    f(&with_segment(path, PathSegment::Index(i - 1)), v)
+                });
+            },
+            _ => (),
+        });
+    }
+
+    // dedup_keys on every dict in the tree; a rejected key is reported with its path
+    pub fn dedup_keys(&mut self, policy: DuplicatePolicy) -> Result<(), BencodeError> {
+        let mut res = Ok(());
+        self.walk_mut(|path, node| {
+            if let (Ok(()), BencodeItem::Dict(d)) = (&res, node) {
+                res = d.dedup_keys(policy).map_err(|e| match e {
+                    BencodeError::DuplicateKey(k) if !path.is_empty() => BencodeError::DuplicateKey(format!("{}/{}", path_string(path), k)),
+                    e => e,
+                });
+            }
+        });
+        res
+    }
+}
+
+impl From<Vec<(String, BencodeItem)>> for BencodeDict {
+    fn from(entries: Vec<(String, BencodeItem)>) -> Self {
+        BencodeDict { entries }
+    }
+}
+
+impl From<BTreeMap<String, BencodeItem>> for BencodeDict {
+    fn from(map: BTreeMap<String, BencodeItem>) -> Self {
+        BencodeDict::from_btreemap(map)
+    }
+}
+
+impl From<HashMap<String, BencodeItem>> for BencodeDict {
+    fn from(map: HashMap<String, BencodeItem>) -> Self {
+        BencodeDict::from_hashmap(map)
+    }
+}
+
+impl From<BencodeDict> for BTreeMap<String, BencodeItem> {
+    fn from(d: BencodeDict) -> Self {
+        d.into_btreemap()
+    }
+}
+
+impl From<BencodeDict> for HashMap<String, BencodeItem> {
+    fn from(d: BencodeDict) -> Self {
+        d.into_hashmap()
+    }
+}
+
+impl FromIterator<(String, BencodeItem)> for BencodeDict {
+    fn from_iter<I: IntoIterator<Item = (String, BencodeItem)>>(iter: I) -> Self {
+        BencodeDict { entries: iter.into_iter().collect() }
+    }
+}
+
+// map-like: a key that's already there gets its value replaced in place
+impl Extend<(String, BencodeItem)> for BencodeDict {
+    fn extend<I: IntoIterator<Item = (String, BencodeItem)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl IntoIterator for BencodeDict {
+    type Item = (String, BencodeItem);
+    type IntoIter = vec::IntoIter<(String, BencodeItem)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a BencodeDict {
+    type Item = &'a (String, BencodeItem);
+    type IntoIter = slice::Iter<'a, (String, BencodeItem)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut BencodeDict {
+    type Item = &'a mut (String, BencodeItem);
+    type IntoIter = slice::IterMut<'a, (String, BencodeItem)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AsBencodeBytes;
+
+    #[test]
+    fn map_api() {
+        let mut d = BencodeDict::new();
+        assert!(d.is_empty());
+        assert_eq!(None, d.insert("b", bencode!(1)));
+        assert_eq!(None, d.insert(String::from("a"), bencode!(2)));
+        assert_eq!(Some(bencode!(1)), d.insert("b", bencode!(3)));
+        // insertion order, not key order
+        assert_eq!(vec!("b", "a"), d.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>());
+        assert_eq!(2, d.len());
+        assert!(d.contains_key("a"));
+        assert_eq!(Some(&bencode!(3)), d.get("b"));
+
+        *d.get_mut("a").unwrap() = bencode!("x");
+        for (_, v) in &mut d {
+            if let BencodeItem::Int(i) = v {
+                *i += 1;
+            }
+        }
+        assert_eq!(Some(bencode!(4)), d.remove("b"));
+        assert_eq!(None, d.remove("b"));
+        assert_eq!(vec!((String::from("a"), bencode!("x"))), d.into_vec());
+    }
+
+    #[test]
+    fn iteration() {
+        let mut item = bencode!({"announce": "udp://a", "info": {"length": 1, "name": "x"}});
+        let torrent = item.as_dict().unwrap();
+        assert_eq!(vec!("announce", "info"), torrent.keys().collect::<Vec<_>>());
+        assert_eq!(Some(&bencode!({"length": 1, "name": "x"})), torrent.values().last());
+
+        let mut seen = vec!();
+        for (k, v) in torrent {
+            seen.push((k.as_str(), v.type_name()));
+        }
+        assert_eq!(vec!(("announce", "string"), ("info", "dict")), seen);
+
+        for v in item.get_mut("info").and_then(BencodeItem::as_dict_mut).unwrap().values_mut() {
+            *v = bencode!(0);
+        }
+        let info = item.as_dict().unwrap().get("info").unwrap().clone();
+        assert_eq!(vec!(bencode!(0), bencode!(0)), info.as_dict().unwrap().clone().into_values().collect::<Vec<_>>());
+        let pairs: Vec<(String, BencodeItem)> = info.as_dict().unwrap().clone().into_iter().collect();
+        assert_eq!(vec!((String::from("length"), bencode!(0)), (String::from("name"), bencode!(0))), pairs);
+    }
+
+    #[test]
+    fn sort_and_dedup() {
+        let mut item = bencode!({"b": 1, "a": {"z": 1, "y": 2, "z": 3}, "b": 2});
+        let mut first = item.clone();
+        first.dedup_keys(DuplicatePolicy::KeepFirst).unwrap();
+        assert_eq!(bencode!({"b": 1, "a": {"z": 1, "y": 2}}), first);
+        let mut last = item.clone();
+        last.dedup_keys(DuplicatePolicy::KeepLast).unwrap();
+        assert_eq!(bencode!({"a": {"y": 2, "z": 3}, "b": 2}), last);
+        assert_eq!(Err(BencodeError::DuplicateKey(String::from("b"))), item.clone().dedup_keys(DuplicatePolicy::Reject));
+        assert_eq!(Err(BencodeError::DuplicateKey(String::from("a/z"))), bencode!({"a": {"z": 1, "z": 1}}).dedup_keys(DuplicatePolicy::Reject));
+
+        item.sort_keys();
+        assert_eq!(bencode!({"a": {"y": 2, "z": 1, "z": 3}, "b": 1, "b": 2}), item);
+        item.dedup_keys(DuplicatePolicy::KeepFirst).unwrap();
+        assert_eq!(item.as_canonical_bytes(), item.as_bytes());
+        assert!(crate::verify_roundtrip(&item.as_bytes()).unwrap().issues.is_empty());
+    }
+
+    #[test]
+    fn retain() {
+        let mut item = bencode!({
+            "comment": "x",
+            "created by": "y",
+            "info": {"name": "a", "comment": "nested"},
+            "nodes": [["1.2.3.4", 6881], ["5.6.7.8", 6881]],
+        });
+        item.as_dict_mut().unwrap().retain(|k, _| k != "comment" && k != "created by");
+        assert_eq!(bencode!({"info": {"name": "a", "comment": "nested"}, "nodes": [["1.2.3.4", 6881], ["5.6.7.8", 6881]]}), item);
+
+        let mut visited = vec!();
+        item.retain_recursive(|path, node| {
+            visited.push(path.len());
+            *path.last().unwrap() != PathSegment::Key("comment") && node.get_index(0) != Some(&bencode!("5.6.7.8"))
+        });
+        assert_eq!(bencode!({"info": {"name": "a"}, "nodes": [["1.2.3.4", 6881]]}), item);
+        // the kept node's two children were visited, the dropped one's weren't
+        assert_eq!(vec!(1, 1, 2, 2, 2, 2, 3, 3), visited);
+
+        let mut list = bencode!([1, 2, 3]);
+        list.as_list_mut().unwrap().retain(|i| *i != 2);
+        assert_eq!(bencode!([1, 3]), list);
+    }
+
+    #[test]
+    fn maps() {
+        let d: BencodeDict = vec!(
+            (String::from("b"), bencode!(1)),
+            (String::from("a"), bencode!(2)),
+            (String::from("b"), bencode!(3)),
+        ).into();
+        let btree = d.clone().into_btreemap();
+        assert_eq!(vec!((&String::from("a"), &bencode!(2)), (&String::from("b"), &bencode!(1))), btree.iter().collect::<Vec<_>>());
+        assert_eq!(Some(&bencode!(1)), d.clone().into_hashmap().get("b"));
+        assert_eq!(2, HashMap::from(d).len());
+
+        let from_btree = BencodeDict::from_btreemap(btree.clone());
+        let from_hash = BencodeDict::from(btree.into_iter().collect::<HashMap<_, _>>());
+        assert_eq!(from_btree, from_hash);
+        assert_eq!(vec!("a", "b"), from_hash.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let files: BencodeItem = (1..=2).map(|i| bencode!({"length": i})).collect();
+        let mut d: BencodeDict = vec!((String::from("files"), files), (String::from("name"), bencode!("a"))).into_iter().collect();
+        d.extend(vec!((String::from("name"), bencode!("b")), (String::from("private"), bencode!(1))));
+        assert_eq!(bencode!({"files": [{"length": 1}, {"length": 2}], "name": "b", "private": 1}), BencodeItem::Dict(d.clone()));
+
+        let doubled: BencodeItem = d.into_iter().map(|(k, v)| (k.repeat(2), v)).collect();
+        assert_eq!(Some(&bencode!(1)), doubled.get("privateprivate"));
+        let mut list = bencode!([1]);
+        list.as_list_mut().unwrap().extend([bencode!(2), bencode!(3)]);
+        assert_eq!(bencode!([1, 2, 3]), list);
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut d: BencodeDict = vec!((String::from("a"), bencode!(1))).into();
+        d.push("a", bencode!(2));
+        assert_eq!(2, d.len());
+        assert_eq!(Some(&bencode!(1)), d.get("a"));
+        assert_eq!(Some(bencode!(1)), d.remove("a"));
+        assert_eq!(Some(&bencode!(2)), d.get("a"));
+    }
+}
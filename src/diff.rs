@@ -0,0 +1,252 @@
+use crate::{BencodeError, BencodeItem, PathSegment};
+use crate::path::path_string;
+use crate::walk::with_segment;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<'a> {
+    Added { path: Vec<PathSegment<'a>>, value: &'a BencodeItem },
+    Removed { path: Vec<PathSegment<'a>>, value: &'a BencodeItem },
+    Modified { path: Vec<PathSegment<'a>>, old: &'a BencodeItem, new: &'a BencodeItem },
+}
+
+impl<'a> Change<'a> {
+    pub fn path(&self) -> &[PathSegment<'a>] {
+        match self {
+            Change::Added { path, .. } | Change::Removed { path, .. } | Change::Modified { path, .. } => path,
+        }
+    }
+}
+
+enum Work<'a> {
+    Compare(Vec<PathSegment<'a>>, &'a BencodeItem, &'a BencodeItem),
+    Emit(Change<'a>),
+}
+
+// What it takes to get from `old` to `new`. Dicts are matched by key (the first entry for
+// a key, as with get) and lists by position, so an insertion in the middle of a list shows
+// up as modifications from there on plus an addition at the end. Anything else that
+// differs, including a change of type, is one Modified at that path. Changes come in
+// document order, with a dict's added keys after the rest of its changes.
+pub fn diff<'a>(old: &'a BencodeItem, new: &'a BencodeItem) -> Vec<Change<'a>> {
+    let mut changes = vec!();
+    let mut stack = vec!(Work::Compare(vec!(), old, new));
+    while let Some(work) = stack.pop() {
+        let (path, old, new) = match work {
+            Work::Emit(change) => {
+                changes.push(change);
+                continue;
+            },
+            Work::Compare(_, old, new) if old == new => continue,
+            Work::Compare(path, old, new) => (path, old, new),
+        };
+        let mut children = vec!();
+        match (old, new) {
+            (BencodeItem::Dict(o), BencodeItem::Dict(n)) => {
+                for (k, v) in first_entries(o.iter()) {
+                    let path = with_segment(&path, PathSegment::Key(k));
+                    children.push(match n.get(k) {
+                        Some(nv) => Work::Compare(path, v, nv),
+                        None => Work::Emit(Change::Removed { path, value: v }),
+                    });
+                }
+                for (k, v) in first_entries(n.iter()).filter(|(k, _)| !o.contains_key(k)) {
+                    children.push(Work::Emit(Change::Added { path: with_segment(&path, PathSegment::Key(k)), value: v }));
+                }
+            },
+            (BencodeItem::List(o), BencodeItem::List(n)) => {
+                for i in 0..o.len().max(n.len()) {
+                    let path = with_segment(&path, PathSegment::Index(i));
+                    children.push(match (o.get(i), n.get(i)) {
+                        (Some(ov), Some(nv)) => Work::Compare(path, ov, nv),
+                        (Some(ov), None) => Work::Emit(Change::Removed { path, value: ov }),
+                        (_, Some(nv)) => Work::Emit(Change::Added { path, value: nv }),
+                        (None, None) => unreachable!(),
+                    });
+                }
+            },
+            _ => changes.push(Change::Modified { path, old, new }),
+        }
+        // reversed, so they come off the stack in order
+        stack.extend(children.into_iter().rev());
+    }
+    changes
+}
+
+impl BencodeItem {
+    pub fn diff<'a>(&'a self, new: &'a BencodeItem) -> Vec<Change<'a>> {
+        diff(self, new)
+    }
+
+    // Applies changes as diff produces them. Every change is checked against what's there:
+    // a Modified or Removed value has to match, an Added path has to be free, otherwise
+    // it's a PatchConflict and the item is left partly patched. Removals go last and
+    // back to front, so removing several trailing list elements works.
+    pub fn apply_patch(&mut self, changes: &[Change]) -> Result<(), BencodeError> {
+        let (removals, rest): (Vec<&Change>, Vec<&Change>) = changes.iter().partition(|c| matches!(c, Change::Removed { .. }));
+        for change in rest.into_iter().chain(removals.into_iter().rev()) {
+            self.apply_change(change)?;
+        }
+        Ok(())
+    }
+
+    fn apply_change(&mut self, change: &Change) -> Result<(), BencodeError> {
+        let conflict = |msg: &str| BencodeError::PatchConflict(format!("{}: {}", path_string(change.path()), msg));
+        let (last, parent_path) = match change.path().split_last() {
+            Some(split) => split,
+            None => {
+                // the root itself
+                return match change {
+                    Change::Modified { old, new, .. } if self == *old => {
+                        *self = (*new).clone();
+                        Ok(())
+                    },
+                    _ => Err(conflict("can't add or remove the root, or it doesn't match")),
+                }
+            }
+        };
+        let parent = self.get_segments_mut(parent_path).ok_or_else(|| conflict("parent is missing"))?;
+        match (change, parent, last) {
+            (Change::Modified { old, new, .. }, parent, &segment) => match parent.get_segments_mut(&[segment]) {
+                Some(current) if current == *old => *current = (*new).clone(),
+                _ => return Err(conflict("value doesn't match")),
+            },
+            (Change::Removed { value, .. }, BencodeItem::Dict(d), PathSegment::Key(k)) if d.get(k) == Some(*value) => {
+                d.remove(k);
+            },
+            (Change::Removed { value, .. }, BencodeItem::List(l), PathSegment::Index(i)) if l.get(*i) == Some(*value) => {
+                l.remove(*i);
+            },
+            (Change::Added { value, .. }, BencodeItem::Dict(d), PathSegment::Key(k)) if !d.contains_key(k) => {
+                d.insert(*k, (*value).clone());
+            },
+            (Change::Added { value, .. }, BencodeItem::List(l), PathSegment::Index(i)) if *i <= l.len() => {
+                l.insert(*i, (*value).clone());
+            },
+            _ => return Err(conflict("doesn't apply")),
+        }
+        Ok(())
+    }
+
+    // Layers `overlay` on top: dicts on both sides merge key by key, recursively, and
+    // anything else in the overlay replaces what was there.
+    pub fn merge(&mut self, overlay: BencodeItem) {
+        match (self, overlay) {
+            (BencodeItem::Dict(base), BencodeItem::Dict(overlay)) => {
+                for (k, v) in overlay {
+                    match base.get_mut(&k) {
+                        Some(existing @ BencodeItem::Dict(_)) if matches!(v, BencodeItem::Dict(_)) => existing.merge(v),
+                        _ => {
+                            base.insert(k, v);
+                        },
+                    }
+                }
+            },
+            (base, overlay) => *base = overlay,
+        }
+    }
+}
+
+// skips entries whose key already appeared
+fn first_entries<'a, I>(entries: I) -> impl Iterator<Item = (&'a str, &'a BencodeItem)>
+where
+    I: Iterator<Item = &'a (String, BencodeItem)>,
+{
+    let mut seen = std::collections::HashSet::new();
+    entries.filter(move |(k, _)| seen.insert(k.as_str())).map(|(k, v)| (k.as_str(), v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes() {
+        let old = bencode!({
+            "announce": "udp://a",
+            "comment": "old",
+            "info": {"files": [{"length": 1}, {"length": 2}], "name": "x"},
+        });
+        let new = bencode!({
+            "announce": "udp://b",
+            "info": {"files": [{"length": 1}, {"length": 3}, {"length": 4}], "name": "x", "private": 1},
+            "created by": "mescal",
+        });
+        let changes = old.diff(&new);
+        assert_eq!(
+            vec!(
+                Change::Modified { path: vec!(PathSegment::Key("announce")), old: &bencode!("udp://a"), new: &bencode!("udp://b") },
+                Change::Removed { path: vec!(PathSegment::Key("comment")), value: &bencode!("old") },
+                Change::Modified {
+                    path: vec!(PathSegment::Key("info"), PathSegment::Key("files"), PathSegment::Index(1), PathSegment::Key("length")),
+                    old: &bencode!(2),
+                    new: &bencode!(3),
+                },
+                Change::Added {
+                    path: vec!(PathSegment::Key("info"), PathSegment::Key("files"), PathSegment::Index(2)),
+                    value: &bencode!({"length": 4}),
+                },
+                Change::Added { path: vec!(PathSegment::Key("info"), PathSegment::Key("private")), value: &bencode!(1) },
+                Change::Added { path: vec!(PathSegment::Key("created by")), value: &bencode!("mescal") },
+            ),
+            changes
+        );
+        assert!(old.diff(&old).is_empty());
+        assert_eq!(vec!(PathSegment::Key("comment")), changes[1].path());
+    }
+
+    #[test]
+    fn apply_patch() {
+        let old = bencode!({"announce": "a", "comment": "c", "info": {"files": [1, 2, 3], "name": "x"}});
+        for new in [
+            bencode!({"announce": "b", "info": {"files": [1], "name": "x", "private": 1}, "created by": "m"}),
+            bencode!({"announce": "a", "info": {"files": [1, 5, 3, 4, 5]}}),
+            bencode!([1]),
+            bencode!({}),
+        ] {
+            let mut patched = old.clone();
+            patched.apply_patch(&old.diff(&new)).unwrap();
+            assert!(patched.semantically_equals(&new), "{:?} vs {:?}", patched, new);
+        }
+
+        let mut changed = bencode!({"announce": "z"});
+        let new = bencode!({"announce": "b"});
+        assert_eq!(
+            Err(BencodeError::PatchConflict(String::from("announce: value doesn't match"))),
+            changed.apply_patch(&bencode!({"announce": "a"}).diff(&new))
+        );
+        assert_eq!(
+            Err(BencodeError::PatchConflict(String::from("x/y: parent is missing"))),
+            changed.apply_patch(&[Change::Added { path: vec!(PathSegment::Key("x"), PathSegment::Key("y")), value: &bencode!(1) }])
+        );
+        assert!(changed.apply_patch(&[Change::Added { path: vec!(PathSegment::Key("announce")), value: &bencode!(1) }]).is_err());
+    }
+
+    #[test]
+    fn merge() {
+        let mut base = bencode!({"announce": "a", "info": {"name": "x", "length": 1, "meta": {"a": 1}}, "list": [1, 2]});
+        base.merge(bencode!({"info": {"length": 2, "meta": "flat", "private": 1}, "list": [3], "comment": "c"}));
+        assert_eq!(
+            bencode!({
+                "announce": "a",
+                "info": {"name": "x", "length": 2, "meta": "flat", "private": 1},
+                "list": [3],
+                "comment": "c",
+            }),
+            base
+        );
+        base.merge(bencode!(1));
+        assert_eq!(bencode!(1), base);
+    }
+
+    #[test]
+    fn type_change_and_duplicates() {
+        let old = bencode!({"a": [1], "a": "ignored"});
+        let new = bencode!({"a": {"x": 1}});
+        assert_eq!(vec!(Change::Modified { path: vec!(PathSegment::Key("a")), old: &bencode!([1]), new: &bencode!({"x": 1}) }), old.diff(&new));
+        assert_eq!(
+            vec!(Change::Removed { path: vec!(PathSegment::Index(1)), value: &bencode!(2) }),
+            diff(&bencode!([1, 2]), &bencode!([1]))
+        );
+        assert_eq!(vec!(Change::Modified { path: vec!(), old: &bencode!(1), new: &bencode!(2) }), diff(&bencode!(1), &bencode!(2)));
+    }
+}
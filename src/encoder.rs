@@ -1,57 +1,489 @@
-use crate::{BencodeItem, c, ByteString};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::{BencodeDict, BencodeError, BencodeItem, ByteString, c};
 
 pub trait AsBencodeBytes {
     fn as_bytes(&self) -> Vec<u8>;
+    // same as as_bytes, but with dict entries sorted by raw key bytes as BEP 3 requires
+    fn as_canonical_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
 }
 
 impl AsBencodeBytes for BencodeItem {
+    // sized up front so encoding is a single allocation
     fn as_bytes(&self) -> Vec<u8> {
-        match self {
-            BencodeItem::String(s) => encode_string(s),
-            BencodeItem::Int(i) => encode_int(i),
-            BencodeItem::List(l) => encode_list(l),
-            BencodeItem::Dict(d) => encode_dict(d),
-        }
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        encode_into(self, &mut bytes);
+        bytes
+    }
+
+    fn as_canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        write_item(self, &mut bytes, true).expect("writing to a Vec can't fail");
+        bytes
     }
 }
 
-fn encode_dict(d: &Vec<(String, BencodeItem)>) -> Vec<u8> {
-    let mut bytes = vec!(c::M_DICT);
-    for (key, value) in d {
-        bytes.append(&mut encode_string(&ByteString::new(key.as_bytes().to_vec())));
-        bytes.append(&mut value.as_bytes());
+macro_rules! impl_as_bencode_bytes_int {
+    ($($t:ty),*) => {
+        $(impl AsBencodeBytes for $t {
+            fn as_bytes(&self) -> Vec<u8> {
+                let mut bytes = vec!();
+                write_int(&i64::from(*self), &mut bytes).expect("writing to a Vec can't fail");
+                bytes
+            }
+        })*
+    };
+}
+
+impl_as_bencode_bytes_int!(i64, u32);
+
+impl AsBencodeBytes for [u8] {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec!();
+        write_string(self, &mut bytes).expect("writing to a Vec can't fail");
+        bytes
+    }
+}
+
+impl AsBencodeBytes for Vec<u8> {
+    fn as_bytes(&self) -> Vec<u8> {
+        self.as_slice().as_bytes()
+    }
+}
+
+// note: str and String have an inherent as_bytes, so call these as AsBencodeBytes::as_bytes(&s)
+impl AsBencodeBytes for str {
+    fn as_bytes(&self) -> Vec<u8> {
+        AsBencodeBytes::as_bytes(str::as_bytes(self))
+    }
+}
+
+impl AsBencodeBytes for &str {
+    fn as_bytes(&self) -> Vec<u8> {
+        AsBencodeBytes::as_bytes(*self)
+    }
+}
+
+impl AsBencodeBytes for String {
+    fn as_bytes(&self) -> Vec<u8> {
+        AsBencodeBytes::as_bytes(self.as_str())
+    }
+}
+
+impl<T: AsBencodeBytes> AsBencodeBytes for [T] {
+    fn as_bytes(&self) -> Vec<u8> {
+        encode_seq(self, T::as_bytes)
+    }
+
+    fn as_canonical_bytes(&self) -> Vec<u8> {
+        encode_seq(self, T::as_canonical_bytes)
+    }
+}
+
+impl<T: AsBencodeBytes> AsBencodeBytes for Vec<T> {
+    fn as_bytes(&self) -> Vec<u8> {
+        self.as_slice().as_bytes()
+    }
+
+    fn as_canonical_bytes(&self) -> Vec<u8> {
+        self.as_slice().as_canonical_bytes()
+    }
+}
+
+// entries are sorted so the output doesn't depend on the map's iteration order
+impl<T: AsBencodeBytes> AsBencodeBytes for HashMap<String, T> {
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(&[u8], &T)> = self.iter().map(|(k, v)| (k.as_bytes(), v)).collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        encode_entries(entries, T::as_bytes)
+    }
+
+    fn as_canonical_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(&[u8], &T)> = self.iter().map(|(k, v)| (k.as_bytes(), v)).collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        encode_entries(entries, T::as_canonical_bytes)
+    }
+}
+
+impl<T: AsBencodeBytes> AsBencodeBytes for BTreeMap<ByteString, T> {
+    fn as_bytes(&self) -> Vec<u8> {
+        encode_entries(self.iter().map(|(k, v)| (k.bytes.as_slice(), v)), T::as_bytes)
+    }
+
+    fn as_canonical_bytes(&self) -> Vec<u8> {
+        encode_entries(self.iter().map(|(k, v)| (k.bytes.as_slice(), v)), T::as_canonical_bytes)
+    }
+}
+
+impl<T: AsBencodeBytes> AsBencodeBytes for BTreeMap<String, T> {
+    fn as_bytes(&self) -> Vec<u8> {
+        encode_map(self, T::as_bytes)
+    }
+
+    fn as_canonical_bytes(&self) -> Vec<u8> {
+        encode_map(self, T::as_canonical_bytes)
     }
-    bytes.push(c::M_END);
-    bytes
 }
 
-fn encode_list(l: &Vec<BencodeItem>) -> Vec<u8> {
+fn encode_seq<T>(items: &[T], encode: fn(&T) -> Vec<u8>) -> Vec<u8> {
     let mut bytes = vec!(c::M_LIST);
-    for item in l {
-        bytes.append(&mut item.as_bytes());
+    for item in items {
+        bytes.append(&mut encode(item));
     }
     bytes.push(c::M_END);
     bytes
 }
 
-fn encode_int(i: &i64) -> Vec<u8> {
-    let mut bytes = vec!(c::M_INT);
-    bytes.append(&mut i.to_string().into_bytes());
+// BTreeMap iterates in key order, which for String keys is raw byte order, so this is always canonical
+fn encode_map<T>(map: &BTreeMap<String, T>, encode: fn(&T) -> Vec<u8>) -> Vec<u8> {
+    encode_entries(map.iter().map(|(k, v)| (k.as_bytes(), v)), encode)
+}
+
+fn encode_entries<'a, T: 'a, I: IntoIterator<Item = (&'a [u8], &'a T)>>(entries: I, encode: fn(&T) -> Vec<u8>) -> Vec<u8> {
+    let mut bytes = vec!(c::M_DICT);
+    for (key, value) in entries {
+        write_string(key, &mut bytes).expect("writing to a Vec can't fail");
+        bytes.append(&mut encode(value));
+    }
     bytes.push(c::M_END);
     bytes
 }
 
-fn encode_string(s: &ByteString) -> Vec<u8> {
-    let mut bytes: Vec<u8> = vec!();
-    bytes.append(&mut s.bytes.len().to_string().into_bytes());
-    bytes.push(c::M_COLON);
-    bytes.append(&mut s.bytes.clone()); // TODO
-    bytes
+impl BencodeItem {
+    // iterative for the same reason the encoder is
+    pub fn encoded_len(&self) -> usize {
+        let mut len = 0;
+        let mut pending = vec!(self);
+        while let Some(item) = pending.pop() {
+            match item {
+                BencodeItem::String(s) => len += string_len(s.bytes.len()),
+                BencodeItem::Int(i) => len += 2 + int_len(*i),
+                BencodeItem::List(l) => {
+                    len += 2;
+                    pending.extend(l);
+                },
+                BencodeItem::Dict(d) => {
+                    len += 2;
+                    for (key, value) in d {
+                        len += string_len(key.len());
+                        pending.push(value);
+                    }
+                }
+            }
+        }
+        len
+    }
+}
+
+fn string_len(len: usize) -> usize {
+    digits(len as u64) + 1 + len
+}
+
+fn int_len(i: i64) -> usize {
+    digits(i.unsigned_abs()) + if i < 0 { 1 } else { 0 }
+}
+
+fn digits(mut n: u64) -> usize {
+    let mut count = 1;
+    while n >= 10 {
+        n /= 10;
+        count += 1;
+    }
+    count
+}
+
+// appends to `buf` rather than replacing its contents, so a scratch buffer can be reused
+pub fn encode_into(item: &BencodeItem, buf: &mut Vec<u8>) {
+    write_to(item, buf).expect("writing to a Vec can't fail");
+}
+
+#[cfg(feature = "bytes")]
+pub fn encode_into_bytes_mut(item: &BencodeItem, buf: &mut bytes::BytesMut) {
+    use bytes::BufMut;
+    write_to(item, &mut buf.writer()).expect("writing to a BytesMut can't fail");
+}
+
+#[cfg(feature = "bytes")]
+pub fn encode_to_bytes(item: &BencodeItem) -> bytes::Bytes {
+    let mut buf = bytes::BytesMut::with_capacity(item.encoded_len());
+    encode_into_bytes_mut(item, &mut buf);
+    buf.freeze()
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct EncodeOptions {
+    pub sort_keys: bool,
+    pub reject_duplicate_keys: bool,
+}
+
+impl EncodeOptions {
+    // writes the tree exactly as it is
+    pub const fn lenient() -> Self {
+        EncodeOptions { sort_keys: false, reject_duplicate_keys: false }
+    }
+
+    // only ever produces spec-compliant, canonical output
+    pub const fn strict() -> Self {
+        EncodeOptions::lenient().sort_keys(true).reject_duplicate_keys(true)
+    }
+
+    pub const fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    pub const fn reject_duplicate_keys(mut self, reject_duplicate_keys: bool) -> Self {
+        self.reject_duplicate_keys = reject_duplicate_keys;
+        self
+    }
+}
+
+pub fn encode_with_options(item: &BencodeItem, options: &EncodeOptions) -> Result<Vec<u8>, BencodeError> {
+    if options.reject_duplicate_keys {
+        check_duplicate_keys(item)?;
+    }
+    let mut bytes = Vec::with_capacity(item.encoded_len());
+    write_item(item, &mut bytes, options.sort_keys).expect("writing to a Vec can't fail");
+    Ok(bytes)
+}
+
+fn check_duplicate_keys(item: &BencodeItem) -> Result<(), BencodeError> {
+    let mut pending = vec!(item);
+    while let Some(item) = pending.pop() {
+        match item {
+            BencodeItem::List(l) => pending.extend(l),
+            BencodeItem::Dict(d) => {
+                let mut seen = HashSet::new();
+                for (key, value) in d {
+                    if !seen.insert(key) {
+                        return Err(BencodeError::DuplicateKey(key.clone()))
+                    }
+                    pending.push(value);
+                }
+            },
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+pub fn write_to<W: Write>(item: &BencodeItem, w: &mut W) -> io::Result<()> {
+    write_item(item, w, false)
+}
+
+// Containers still being written. Kept on the heap rather than the call stack so that
+// nesting depth is only bounded by memory.
+enum Frame<'a> {
+    List(std::slice::Iter<'a, BencodeItem>),
+    Dict(std::vec::IntoIter<&'a (String, BencodeItem)>),
+}
+
+fn write_item<W: Write>(item: &BencodeItem, w: &mut W, sort_keys: bool) -> io::Result<()> {
+    let mut stack: Vec<Frame> = vec!();
+    let mut next = Some(item);
+    loop {
+        if let Some(item) = next.take() {
+            match item {
+                BencodeItem::String(s) => write_string(&s.bytes, w)?,
+                BencodeItem::Int(i) => write_int(i, w)?,
+                BencodeItem::List(l) => {
+                    w.write_all(&[c::M_LIST])?;
+                    stack.push(Frame::List(l.iter()));
+                },
+                BencodeItem::Dict(d) => {
+                    w.write_all(&[c::M_DICT])?;
+                    stack.push(Frame::Dict(dict_entries(d, sort_keys).into_iter()));
+                }
+            }
+        }
+        match stack.last_mut() {
+            None => return Ok(()),
+            Some(Frame::List(items)) => match items.next() {
+                Some(item) => next = Some(item),
+                None => {
+                    stack.pop();
+                    w.write_all(&[c::M_END])?;
+                }
+            },
+            Some(Frame::Dict(entries)) => match entries.next() {
+                Some((key, value)) => {
+                    write_string(key.as_bytes(), w)?;
+                    next = Some(value);
+                },
+                None => {
+                    stack.pop();
+                    w.write_all(&[c::M_END])?;
+                }
+            }
+        }
+    }
+}
+
+fn dict_entries(d: &BencodeDict, sort_keys: bool) -> Vec<&(String, BencodeItem)> {
+    let mut entries: Vec<&(String, BencodeItem)> = d.iter().collect();
+    if sort_keys {
+        // stable, so duplicate keys keep their relative order
+        entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    }
+    entries
+}
+
+// fits u64::MAX, and i64::MIN with its sign
+pub(crate) type DigitBuf = [u8; 21];
+
+// formats into a stack buffer rather than going through to_string, which would
+// allocate for every int and every string length
+fn format_int(i: i64, buf: &mut DigitBuf) -> &[u8] {
+    let start = format_digits(i.unsigned_abs(), buf);
+    if i < 0 {
+        buf[start - 1] = c::M_DASH;
+        return &buf[start - 1..];
+    }
+    &buf[start..]
+}
+
+fn format_len(len: usize, buf: &mut DigitBuf) -> &[u8] {
+    let start = format_digits(len as u64, buf);
+    &buf[start..]
+}
+
+// writes the digits right-aligned and returns where they start
+fn format_digits(mut n: u64, buf: &mut DigitBuf) -> usize {
+    let mut pos = buf.len();
+    loop {
+        pos -= 1;
+        buf[pos] = c::M_0 + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return pos;
+        }
+    }
+}
+
+fn write_int<W: Write>(i: &i64, w: &mut W) -> io::Result<()> {
+    w.write_all(&[c::M_INT])?;
+    w.write_all(format_int(*i, &mut [0; 21]))?;
+    w.write_all(&[c::M_END])
+}
+
+fn write_string<W: Write>(s: &[u8], w: &mut W) -> io::Result<()> {
+    w.write_all(format_len(s.len(), &mut [0; 21]))?;
+    w.write_all(&[c::M_COLON])?;
+    w.write_all(s)
+}
+
+// Writer-style encoders for emitting large documents progressively, without building
+// a BencodeItem tree first. Each container must be closed with end().
+pub struct DictEncoder<'a, W: Write> {
+    w: &'a mut W,
+    // a key has been written and its value hasn't yet
+    pending_key: bool,
+}
+
+pub struct ListEncoder<'a, W: Write> {
+    w: &'a mut W,
+}
+
+fn misuse(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+impl<'a, W: Write> DictEncoder<'a, W> {
+    pub fn begin(w: &'a mut W) -> io::Result<Self> {
+        w.write_all(&[c::M_DICT])?;
+        Ok(DictEncoder { w, pending_key: false })
+    }
+
+    pub fn key(&mut self, key: &str) -> io::Result<&mut Self> {
+        if self.pending_key {
+            return Err(misuse("dict key written twice in a row"))
+        }
+        write_string(key.as_bytes(), self.w)?;
+        self.pending_key = true;
+        Ok(self)
+    }
+
+    fn value(&mut self) -> io::Result<&mut W> {
+        if !self.pending_key {
+            return Err(misuse("dict value written without a key"))
+        }
+        self.pending_key = false;
+        Ok(self.w)
+    }
+
+    pub fn str<S: AsRef<[u8]>>(&mut self, s: S) -> io::Result<&mut Self> {
+        write_string(s.as_ref(), self.value()?)?;
+        Ok(self)
+    }
+
+    pub fn int(&mut self, i: i64) -> io::Result<&mut Self> {
+        write_int(&i, self.value()?)?;
+        Ok(self)
+    }
+
+    pub fn item(&mut self, item: &BencodeItem) -> io::Result<&mut Self> {
+        write_to(item, self.value()?)?;
+        Ok(self)
+    }
+
+    pub fn dict(&mut self) -> io::Result<DictEncoder<'_, W>> {
+        DictEncoder::begin(self.value()?)
+    }
+
+    pub fn list(&mut self) -> io::Result<ListEncoder<'_, W>> {
+        ListEncoder::begin(self.value()?)
+    }
+
+    pub fn end(self) -> io::Result<()> {
+        if self.pending_key {
+            return Err(misuse("dict ended with a key missing its value"))
+        }
+        self.w.write_all(&[c::M_END])
+    }
+}
+
+impl<'a, W: Write> ListEncoder<'a, W> {
+    pub fn begin(w: &'a mut W) -> io::Result<Self> {
+        w.write_all(&[c::M_LIST])?;
+        Ok(ListEncoder { w })
+    }
+
+    pub fn str<S: AsRef<[u8]>>(&mut self, s: S) -> io::Result<&mut Self> {
+        write_string(s.as_ref(), self.w)?;
+        Ok(self)
+    }
+
+    pub fn int(&mut self, i: i64) -> io::Result<&mut Self> {
+        write_int(&i, self.w)?;
+        Ok(self)
+    }
+
+    pub fn item(&mut self, item: &BencodeItem) -> io::Result<&mut Self> {
+        write_to(item, self.w)?;
+        Ok(self)
+    }
+
+    pub fn dict(&mut self) -> io::Result<DictEncoder<'_, W>> {
+        DictEncoder::begin(self.w)
+    }
+
+    pub fn list(&mut self) -> io::Result<ListEncoder<'_, W>> {
+        ListEncoder::begin(self.w)
+    }
+
+    pub fn end(self) -> io::Result<()> {
+        self.w.write_all(&[c::M_END])
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ByteString;
 
     macro_rules! assert_bytes_eq {
         ($encoded:expr, $decoded:expr) => {
@@ -61,13 +493,13 @@ mod tests {
 
     macro_rules! bencode_string {
         ($literal:expr) => {
-            ByteString::new($literal.as_bytes().to_vec())
+            ByteString::from($literal)
         };
     }
 
     #[test]
     fn dict() {
-        assert_bytes_eq!(vec!(0x64, 0x65), BencodeItem::Dict(vec!()));
+        assert_bytes_eq!(vec!(0x64, 0x65), BencodeItem::Dict(vec!().into()));
 
         assert_bytes_eq!(
             vec!(0x64, 0x35, 0x3A, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x35, 0x3A, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x35, 0x3A, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x35, 0x3A, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x65),
@@ -75,14 +507,14 @@ mod tests {
                 vec!(
                     (String::from("Hello"), BencodeItem::String(bencode_string!("World"))),
                     (String::from("World"), BencodeItem::String(bencode_string!("Hello")))
-                )
+                ).into()
             )
         );
 
         assert_bytes_eq!(
             vec!(0x64, 0x35, 0x3A, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x69, 0x31, 0x32, 0x33, 0x65, 0x65),
             BencodeItem::Dict(
-                vec!((String::from("Hello"), BencodeItem::Int(123)))
+                vec!((String::from("Hello"), BencodeItem::Int(123))).into()
             )
         );
     }
@@ -130,4 +562,187 @@ mod tests {
         assert_bytes_eq!(vec!(0x69, 0x2D, 0x37, 0x65), BencodeItem::Int(-7));
         assert_bytes_eq!(vec!(0x69, 0x30, 0x65), BencodeItem::Int(0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn write() {
+        let item = BencodeItem::Dict(vec!(
+            (String::from("Hello"), BencodeItem::List(vec!(BencodeItem::Int(-7), BencodeItem::String(bencode_string!("World")))))
+        ).into());
+        let mut out: Vec<u8> = vec!();
+        write_to(&item, &mut out).unwrap();
+        assert_eq!(b"d5:Helloli-7e5:Worldee".to_vec(), out);
+
+        // errors from the underlying writer are passed through
+        let mut full = [0u8; 4];
+        let mut w = &mut full[..];
+        assert!(write_to(&item, &mut w).is_err());
+    }
+
+    #[test]
+    fn into_buffer() {
+        let mut buf = b"xx".to_vec();
+        encode_into(&BencodeItem::Int(7), &mut buf);
+        encode_into(&BencodeItem::String(bencode_string!("Hi")), &mut buf);
+        assert_eq!(b"xxi7e2:Hi".to_vec(), buf);
+
+        buf.clear();
+        encode_into(&BencodeItem::List(vec!()), &mut buf);
+        assert_eq!(b"le".to_vec(), buf);
+    }
+
+    #[test]
+    fn encoded_len() {
+        let items = vec!(
+            BencodeItem::Int(0),
+            BencodeItem::Int(-7),
+            BencodeItem::Int(i64::MIN),
+            BencodeItem::Int(i64::MAX),
+            BencodeItem::String(bencode_string!("")),
+            BencodeItem::String(ByteString::new(vec!(0x78; 1000))),
+            BencodeItem::List(vec!()),
+            BencodeItem::Dict(vec!(
+                (String::from("Hello"), BencodeItem::List(vec!(BencodeItem::Int(1337), BencodeItem::Dict(vec!().into())))),
+            ).into()),
+        );
+        for item in items {
+            let bytes = item.as_bytes();
+            assert_eq!(bytes.len(), item.encoded_len());
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        let item = BencodeItem::Dict(vec!(
+            (String::from("b"), BencodeItem::Int(1)),
+            (String::from("a"), BencodeItem::List(vec!(
+                BencodeItem::Dict(vec!(
+                    (String::from("z"), BencodeItem::Int(2)),
+                    (String::from("y"), BencodeItem::Int(3)),
+                ).into()),
+            ))),
+        ).into());
+        assert_eq!(b"d1:bi1e1:ald1:zi2e1:yi3eeee".to_vec(), item.as_bytes());
+        assert_eq!(b"d1:ald1:yi3e1:zi2eee1:bi1ee".to_vec(), item.as_canonical_bytes());
+    }
+
+    #[test]
+    fn streaming() {
+        let mut out: Vec<u8> = vec!();
+        let mut d = DictEncoder::begin(&mut out).unwrap();
+        d.key("announce").unwrap().str("udp://x").unwrap();
+        d.key("interval").unwrap().int(1800).unwrap();
+        {
+            d.key("peers").unwrap();
+            let mut peers = d.list().unwrap();
+            for port in [6881, 6882] {
+                let mut peer = peers.dict().unwrap();
+                peer.key("port").unwrap().int(port).unwrap();
+                peer.end().unwrap();
+            }
+            peers.item(&BencodeItem::List(vec!())).unwrap();
+            peers.end().unwrap();
+        }
+        d.end().unwrap();
+        assert_eq!(b"d8:announce7:udp://x8:intervali1800e5:peersld4:porti6881eed4:porti6882eeleee".to_vec(), out);
+
+        let mut out: Vec<u8> = vec!();
+        let mut d = DictEncoder::begin(&mut out).unwrap();
+        assert!(d.int(1).is_err());
+        assert!(d.key("a").unwrap().key("b").is_err());
+        assert!(d.end().is_err());
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 200_000;
+        let mut item = BencodeItem::Int(1);
+        for _ in 0..depth {
+            item = BencodeItem::List(vec!(item));
+        }
+        let bytes = item.as_bytes();
+        assert_eq!(depth * 2 + 3, bytes.len());
+        assert_eq!(b"llli1eeee".to_vec(), bytes[depth - 3..depth + 6].to_vec());
+
+        // dropping is recursive, so take the tree apart by hand
+        while let BencodeItem::List(mut l) = item {
+            item = l.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(b"i-7e".to_vec(), (-7i64).as_bytes());
+        assert_eq!(b"i4294967295e".to_vec(), u32::MAX.as_bytes());
+        assert_eq!(b"5:Hello".to_vec(), AsBencodeBytes::as_bytes(&"Hello"));
+        assert_eq!(b"5:Hello".to_vec(), AsBencodeBytes::as_bytes(&String::from("Hello")));
+        assert_eq!(b"1:\x8a".to_vec(), AsBencodeBytes::as_bytes(&[0x8Au8][..]));
+        assert_eq!(b"0:".to_vec(), Vec::<u8>::new().as_bytes());
+        assert_eq!(b"li1ei2ee".to_vec(), vec!(1i64, 2).as_bytes());
+        assert_eq!(b"ll1:aee".to_vec(), vec!(vec!("a")).as_bytes());
+
+        let mut map = BTreeMap::new();
+        map.insert(String::from("b"), BencodeItem::Dict(vec!(
+            (String::from("z"), BencodeItem::Int(1)),
+            (String::from("y"), BencodeItem::Int(2)),
+        ).into()));
+        map.insert(String::from("a"), BencodeItem::Int(3));
+        assert_eq!(b"d1:ai3e1:bd1:zi1e1:yi2eee".to_vec(), map.as_bytes());
+        assert_eq!(b"d1:ai3e1:bd1:yi2e1:zi1eee".to_vec(), map.as_canonical_bytes());
+
+        let mut hash_map = HashMap::new();
+        for key in ["c", "a", "b"] {
+            hash_map.insert(String::from(key), 1i64);
+        }
+        assert_eq!(b"d1:ai1e1:bi1e1:ci1ee".to_vec(), hash_map.as_bytes());
+
+        let mut bytes_map = BTreeMap::new();
+        bytes_map.insert(ByteString::new(vec!(0xFF)), 1i64);
+        bytes_map.insert(ByteString::new(vec!(0x01)), 2i64);
+        assert_eq!(b"d1:\x01i2e1:\xffi1ee".to_vec(), bytes_map.as_bytes());
+
+        assert_eq!(b"li1ei2ee".to_vec(), [1i64, 2][..].as_bytes());
+    }
+
+    #[test]
+    fn options() {
+        let item = BencodeItem::Dict(vec!(
+            (String::from("b"), BencodeItem::Int(1)),
+            (String::from("a"), BencodeItem::List(vec!(BencodeItem::Dict(vec!(
+                (String::from("x"), BencodeItem::Int(2)),
+                (String::from("x"), BencodeItem::Int(3)),
+            ).into())))),
+        ).into());
+        assert_eq!(Ok(item.as_bytes()), encode_with_options(&item, &EncodeOptions::default()));
+        assert_eq!(Ok(item.as_canonical_bytes()), encode_with_options(&item, &EncodeOptions { sort_keys: true, ..Default::default() }));
+        assert_eq!(
+            Err(BencodeError::DuplicateKey(String::from("x"))),
+            encode_with_options(&item, &EncodeOptions { reject_duplicate_keys: true, ..Default::default() })
+        );
+        assert_eq!(Ok(item.as_bytes()), encode_with_options(&item, &EncodeOptions::lenient()));
+        assert_eq!(Ok(item.as_canonical_bytes()), encode_with_options(&item, &EncodeOptions::lenient().sort_keys(true)));
+        assert_eq!(Err(BencodeError::DuplicateKey(String::from("x"))), encode_with_options(&item, &EncodeOptions::strict()));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes() {
+        let item = BencodeItem::List(vec!(BencodeItem::Int(1), BencodeItem::String(bencode_string!("Hi"))));
+        let mut buf = bytes::BytesMut::from(&b"xx"[..]);
+        encode_into_bytes_mut(&item, &mut buf);
+        assert_eq!(&b"xxli1e2:Hie"[..], &buf[..]);
+
+        let frozen = encode_to_bytes(&item);
+        assert_eq!(item.as_bytes(), frozen.to_vec());
+        assert_eq!(&b"i1e"[..], &frozen.slice(1..4)[..]);
+    }
+
+    #[test]
+    fn digits() {
+        for i in [0, 7, -7, 10, -10, 1337, i64::MAX, i64::MIN] {
+            assert_eq!(i.to_string().as_bytes(), format_int(i, &mut [0; 21]));
+        }
+        for len in [0, 9, 10, 100000, usize::MAX] {
+            assert_eq!(len.to_string().as_bytes(), format_len(len, &mut [0; 21]));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{bencode, AsBencodeBytes, BencodeItem, ByteString};
+
+// Small, made-up documents for tests and examples. Everything is generated here, so there's
+// no real content to license: hosts are example.com/192.0.2.0 and hashes are filler bytes.
+// Each function returns the canonical encoding, keys sorted.
+
+pub fn sample_v1() -> Vec<u8> {
+    bencode!({
+        "announce": "http://tracker.example.com/announce",
+        "announce-list": [["http://tracker.example.com/announce"], ["udp://backup.example.com:6969"]],
+        "comment": "mescal sample",
+        "created by": "mescal",
+        "creation date": 1_700_000_000i64,
+        "info": {
+            "files": [
+                {"length": 20000, "path": ["docs", "readme.txt"]},
+                {"length": 12768, "path": ["data.bin"]},
+            ],
+            "name": "sample",
+            "piece length": 16384,
+            "pieces": filler(1, 2 * 20),
+        },
+    }).as_bytes()
+}
+
+// BEP 52. Files fit in a single piece, which keeps `piece layers` empty: its keys are raw
+// hashes and dict keys here have to be UTF-8.
+pub fn sample_v2() -> Vec<u8> {
+    bencode!({
+        "announce": "http://tracker.example.com/announce",
+        "creation date": 1_700_000_000i64,
+        "info": {
+            "file tree": {
+                "data.bin": {"": {"length": 16384, "pieces root": filler(2, 32)}},
+                "docs": {"readme.txt": {"": {"length": 1000, "pieces root": filler(3, 32)}}},
+            },
+            "meta version": 2,
+            "name": "sample",
+            "piece length": 16384,
+        },
+        "piece layers": {},
+    }).as_bytes()
+}
+
+// compact (BEP 23) announce response with two peers
+pub fn sample_tracker_response() -> Vec<u8> {
+    let peers = ByteString::new(vec!(192, 0, 2, 1, 0x1A, 0xE1, 192, 0, 2, 2, 0x1A, 0xE2));
+    bencode!({
+        "complete": 5,
+        "incomplete": 2,
+        "interval": 1800,
+        "min interval": 900,
+        "peers": peers,
+    }).as_bytes()
+}
+
+// deterministic stand-in for hash output
+fn filler(seed: u8, len: usize) -> BencodeItem {
+    let bytes = (0..len).map(|i| seed.wrapping_mul(31).wrapping_add((i as u8).wrapping_mul(7))).collect();
+    BencodeItem::String(ByteString::new(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conformance, parse_bytes};
+
+    #[test]
+    fn samples_are_canonical() {
+        for sample in [sample_v1(), sample_v2(), sample_tracker_response()] {
+            let report = conformance::check_bytes(&sample);
+            assert!(report.passed(), "{:?}", report.failures().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn sample_v1_pieces() {
+        let item = parse_bytes(&mut sample_v1().iter().peekable()).unwrap();
+        let info: BencodeItem = item.required_field("info").unwrap();
+        let pieces: ByteString = info.required_field("pieces").unwrap();
+        let piece_length: i64 = info.required_field("piece length").unwrap();
+        assert_eq!(2, pieces.chunks_exact::<20>().unwrap().count());
+        assert_eq!(2 * piece_length, 20000 + 12768);
+    }
+}
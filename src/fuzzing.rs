@@ -0,0 +1,81 @@
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{BencodeDict, BencodeItem, ByteString};
+
+// For fuzzers. Trees are at most MAX_DEPTH containers deep, so generation always ends, and
+// dict keys are UTF-8 like the decoder requires, so anything generated encodes and decodes
+// back to itself. Keys come in whatever order and may repeat, as decoded input can.
+
+const MAX_DEPTH: usize = 8;
+
+impl<'a> Arbitrary<'a> for ByteString {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ByteString::new(Vec::arbitrary(u)?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        Vec::<u8>::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for BencodeItem {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_item(u, MAX_DEPTH)
+    }
+}
+
+fn arbitrary_item(u: &mut Unstructured, depth: usize) -> Result<BencodeItem> {
+    // past the depth limit only leaves are left to choose from
+    let kinds = if depth == 0 { 2 } else { 4 };
+    Ok(match u.choose_index(kinds)? {
+        0 => BencodeItem::Int(i64::arbitrary(u)?),
+        1 => BencodeItem::String(ByteString::arbitrary(u)?),
+        2 => {
+            let len = u.arbitrary_len::<BencodeItem>()?;
+            BencodeItem::List((0..len).map(|_| arbitrary_item(u, depth - 1)).collect::<Result<_>>()?)
+        },
+        _ => {
+            let len = u.arbitrary_len::<(String, BencodeItem)>()?;
+            let mut d = BencodeDict::with_capacity(len);
+            for _ in 0..len {
+                d.push(String::arbitrary(u)?, arbitrary_item(u, depth - 1)?);
+            }
+            BencodeItem::Dict(d)
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_bytes, AsBencodeBytes};
+
+    #[test]
+    fn round_trip() {
+        // a cheap xorshift so the input is varied but the same on every run
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let data: Vec<u8> = (0..1 << 16).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect();
+
+        let mut u = Unstructured::new(&data);
+        let mut containers = 0;
+        while !u.is_empty() {
+            let item = BencodeItem::arbitrary(&mut u).unwrap();
+            containers += item.walk().filter(|(_, node)| node.as_list().is_some() || node.as_dict().is_some()).count();
+            assert_eq!(Ok(&item), parse_bytes(&mut item.as_bytes().iter().peekable()).as_ref());
+        }
+        assert!(containers > 0);
+    }
+
+    #[test]
+    fn depth_is_bounded() {
+        // all ones keeps choosing dicts with one more entry
+        let data = [0xFF; 4096];
+        let item = BencodeItem::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        assert!(item.walk().all(|(path, _)| path.len() <= MAX_DEPTH));
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{self, Write};
+
+use crate::BencodeItem;
+use crate::encoder;
+
+// Abstraction over whatever digest implementation the caller's environment mandates.
+// Anything that hashes (info-hashes, piece hashes, fingerprints) goes through this.
+pub trait Hasher {
+    type Digest: AsRef<[u8]>;
+
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self) -> Self::Digest;
+}
+
+pub fn digest<H: Hasher + Default>(data: &[u8]) -> H::Digest {
+    let mut hasher = H::default();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+// hashes the encoding of `item` without materializing it
+pub fn digest_item<H: Hasher>(item: &BencodeItem, mut hasher: H) -> H::Digest {
+    encoder::write_to(item, &mut HasherWriter(&mut hasher)).expect("hashing can't fail");
+    hasher.finalize()
+}
+
+struct HasherWriter<'a, H>(&'a mut H);
+
+impl<H: Hasher> Write for HasherWriter<'_, H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// FNV-1a, 64-bit, big-endian. Not cryptographic; for fingerprints that have to stay the
+// same across builds, which DefaultHasher's don't.
+pub struct Fnv1a(u64);
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(FNV_OFFSET)
+    }
+}
+
+impl Hasher for Fnv1a {
+    type Digest = [u8; 8];
+
+    fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn finalize(self) -> Self::Digest {
+        self.0.to_be_bytes()
+    }
+}
+
+#[cfg(feature = "sha1")]
+#[derive(Default)]
+pub struct Sha1(sha1::Sha1);
+
+#[cfg(feature = "sha1")]
+impl Hasher for Sha1 {
+    type Digest = [u8; 20];
+
+    fn update(&mut self, data: &[u8]) {
+        sha1::Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self) -> Self::Digest {
+        sha1::Digest::finalize(self.0).into()
+    }
+}
+
+#[cfg(feature = "sha2")]
+#[derive(Default)]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha2")]
+impl Hasher for Sha256 {
+    type Digest = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self) -> Self::Digest {
+        sha2::Digest::finalize(self.0).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AsBencodeBytes;
+
+    // stand-in backend, as a caller with its own crypto provider would write
+    #[derive(Default)]
+    struct Recorder(Vec<u8>);
+
+    impl Hasher for Recorder {
+        type Digest = Vec<u8>;
+
+        fn update(&mut self, data: &[u8]) {
+            self.0.extend_from_slice(data);
+        }
+
+        fn finalize(self) -> Self::Digest {
+            self.0
+        }
+    }
+
+    #[test]
+    fn custom_backend() {
+        let item = BencodeItem::List(vec!(BencodeItem::Int(1), BencodeItem::Int(2)));
+        assert_eq!(item.as_bytes(), digest_item(&item, Recorder::default()));
+        assert_eq!(b"abc".to_vec(), digest::<Recorder>(b"abc"));
+    }
+
+    #[test]
+    fn fnv1a() {
+        assert_eq!(0xcbf29ce484222325u64.to_be_bytes(), digest::<Fnv1a>(b""));
+        assert_eq!(0xaf63dc4c8601ec8cu64.to_be_bytes(), digest::<Fnv1a>(b"a"));
+    }
+
+    #[cfg(feature = "sha1")]
+    #[test]
+    fn sha1() {
+        assert_eq!(
+            [0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50, 0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d],
+            digest::<Sha1>(b"abc")
+        );
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            [0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23,
+             0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00, 0x15, 0xad],
+            digest::<Sha256>(b"abc")
+        );
+    }
+}
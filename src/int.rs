@@ -0,0 +1,130 @@
+use std::fmt;
+use std::ops::RangeBounds;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{BencodeError, BencodeItem, FromBencode, ToBencode};
+
+// An int with helpers for the things metainfo ints usually are: sizes, timestamps and counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BencodeInt(pub i64);
+
+const SIZE_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+impl BencodeInt {
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+
+    pub fn to_u64(self) -> Result<u64, BencodeError> {
+        self.convert("u64")
+    }
+
+    pub fn to_u32(self) -> Result<u32, BencodeError> {
+        self.convert("u32")
+    }
+
+    pub fn to_usize(self) -> Result<usize, BencodeError> {
+        self.convert("usize")
+    }
+
+    pub fn in_range<R: RangeBounds<i64>>(self, range: R) -> Result<i64, BencodeError> {
+        if range.contains(&self.0) {
+            return Ok(self.0)
+        }
+        Err(BencodeError::ValueOutOfRange(format!("{} is outside {:?}..{:?}", self.0, range.start_bound(), range.end_bound())))
+    }
+
+    // "16 KiB", "1.5 GiB"; whole values drop the fraction
+    pub fn as_bytes_size(self) -> Result<String, BencodeError> {
+        let bytes = self.to_u64()?;
+        let mut unit = 0;
+        let mut value = bytes as f64;
+        while value >= 1024.0 && unit + 1 < SIZE_UNITS.len() {
+            value /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 || value.fract() == 0.0 {
+            Ok(format!("{} {}", value, SIZE_UNITS[unit]))
+        } else {
+            Ok(format!("{:.1} {}", value, SIZE_UNITS[unit]))
+        }
+    }
+
+    // seconds since the epoch, as used by `creation date`
+    pub fn as_unix_time(self) -> Result<SystemTime, BencodeError> {
+        let secs = self.to_u64()?;
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+            .ok_or_else(|| BencodeError::ValueOutOfRange(format!("{} is not a representable time", secs)))
+    }
+
+    fn convert<T: TryFrom<i64>>(self, type_name: &str) -> Result<T, BencodeError> {
+        T::try_from(self.0).map_err(|_| BencodeError::ValueOutOfRange(format!("{} doesn't fit in {}", self.0, type_name)))
+    }
+}
+
+impl From<i64> for BencodeInt {
+    fn from(i: i64) -> Self {
+        BencodeInt(i)
+    }
+}
+
+impl From<BencodeInt> for i64 {
+    fn from(i: BencodeInt) -> Self {
+        i.0
+    }
+}
+
+impl fmt::Display for BencodeInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ToBencode for BencodeInt {
+    fn to_bencode(&self) -> BencodeItem {
+        BencodeItem::Int(self.0)
+    }
+}
+
+impl FromBencode for BencodeInt {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        i64::from_bencode(item).map(BencodeInt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn getters() {
+        assert_eq!(Ok(7), BencodeInt(7).to_u32());
+        assert_eq!(Err(BencodeError::ValueOutOfRange(String::from("-1 doesn't fit in u64"))), BencodeInt(-1).to_u64());
+        assert!(BencodeInt(1 << 32).to_u32().is_err());
+        assert_eq!(Ok(16384), BencodeInt(16384).in_range(1..=1 << 24));
+        assert!(BencodeInt(0).in_range(1..).is_err());
+    }
+
+    #[test]
+    fn sizes_and_times() {
+        assert_eq!(Ok(String::from("0 B")), BencodeInt(0).as_bytes_size());
+        assert_eq!(Ok(String::from("1023 B")), BencodeInt(1023).as_bytes_size());
+        assert_eq!(Ok(String::from("16 KiB")), BencodeInt(16384).as_bytes_size());
+        assert_eq!(Ok(String::from("1.5 GiB")), BencodeInt(3 << 29).as_bytes_size());
+        assert_eq!(Ok(String::from("8 EiB")), BencodeInt(i64::MAX).as_bytes_size());
+        assert!(BencodeInt(-5).as_bytes_size().is_err());
+
+        assert_eq!(Ok(UNIX_EPOCH + Duration::from_secs(1_700_000_000)), BencodeInt(1_700_000_000).as_unix_time());
+        assert!(BencodeInt(-1).as_unix_time().is_err());
+    }
+
+    #[test]
+    fn field() {
+        let item = bencode!({"creation date": 1_700_000_000i64, "piece length": 262144});
+        let date: BencodeInt = item.required_field("creation date").unwrap();
+        assert!(date.as_unix_time().is_ok());
+        let piece_length: BencodeInt = item.required_field("piece length").unwrap();
+        assert_eq!(Ok(String::from("256 KiB")), piece_length.as_bytes_size());
+        assert_eq!(bencode!(262144), piece_length.to_bencode());
+    }
+}
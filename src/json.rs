@@ -0,0 +1,223 @@
+use serde_json::{Map, Number, Value};
+
+use crate::c::HEX;
+use crate::{BencodeDict, BencodeError, BencodeItem, ByteString};
+
+// Conversion to and from serde_json values. JSON has no byte strings and JavaScript can't
+// hold every i64, so both are written as tagged strings ("hex:8a0f", "int:9007199254740993")
+// that from_json turns back into what they were. Text that happens to start with one of
+// the tags is written tagged as well, which is what keeps the round trip lossless; only
+// dict key order and duplicate keys are lost, since JSON objects come back sorted. With
+// LossyUtf8 there's no binary tag to fall back on, so such text gets a "str:" prefix.
+
+const BASE64: &str = "base64:";
+const INT: &str = "int:";
+const STR: &str = "str:";
+
+// the largest integer a double holds exactly
+const MAX_SAFE_INT: i64 = (1 << 53) - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryPolicy {
+    Hex,
+    Base64,
+    // invalid sequences become U+FFFD; one way only, from_json leaves such strings alone
+    LossyUtf8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntPolicy {
+    Number,
+    // ints a double can't hold exactly become "int:..." strings
+    StringIfUnsafe,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct JsonPolicy {
+    pub binary: BinaryPolicy,
+    pub ints: IntPolicy,
+}
+
+impl Default for JsonPolicy {
+    fn default() -> Self {
+        JsonPolicy::new()
+    }
+}
+
+impl JsonPolicy {
+    pub const fn new() -> Self {
+        JsonPolicy { binary: BinaryPolicy::Hex, ints: IntPolicy::Number }
+    }
+
+    pub const fn binary(mut self, binary: BinaryPolicy) -> Self {
+        self.binary = binary;
+        self
+    }
+
+    pub const fn ints(mut self, ints: IntPolicy) -> Self {
+        self.ints = ints;
+        self
+    }
+
+    fn tags(&self) -> impl Iterator<Item = &'static str> {
+        let binary = match self.binary {
+            BinaryPolicy::Hex => Some(HEX),
+            BinaryPolicy::Base64 => Some(BASE64),
+            BinaryPolicy::LossyUtf8 if self.ints == IntPolicy::StringIfUnsafe => Some(STR),
+            BinaryPolicy::LossyUtf8 => None,
+        };
+        let int = match self.ints {
+            IntPolicy::StringIfUnsafe => Some(INT),
+            IntPolicy::Number => None,
+        };
+        binary.into_iter().chain(int)
+    }
+}
+
+impl BencodeItem {
+    pub fn to_json(&self, policy: &JsonPolicy) -> Value {
+        match self {
+            BencodeItem::Int(i) if policy.ints == IntPolicy::StringIfUnsafe && !(-MAX_SAFE_INT..=MAX_SAFE_INT).contains(i) => {
+                Value::String(format!("{}{}", INT, i))
+            },
+            BencodeItem::Int(i) => Value::Number(Number::from(*i)),
+            BencodeItem::String(s) => Value::String(string_to_json(s, policy)),
+            BencodeItem::List(l) => Value::Array(l.iter().map(|child| child.to_json(policy)).collect()),
+            BencodeItem::Dict(d) => {
+                // keys are str already, so they never need tagging
+                Value::Object(d.iter().map(|(k, v)| (k.clone(), v.to_json(policy))).collect::<Map<_, _>>())
+            }
+        }
+    }
+
+    pub fn from_json(value: &Value, policy: &JsonPolicy) -> Result<BencodeItem, BencodeError> {
+        match value {
+            Value::Number(n) => n.as_i64()
+                .map(BencodeItem::Int)
+                .ok_or(BencodeError::Json(format!("{} doesn't fit a bencode int", n))),
+            Value::String(s) => string_from_json(s, policy),
+            Value::Array(a) => a.iter().map(|v| BencodeItem::from_json(v, policy)).collect(),
+            Value::Object(o) => {
+                let mut d = BencodeDict::with_capacity(o.len());
+                for (k, v) in o {
+                    d.push(k.clone(), BencodeItem::from_json(v, policy)
+                        .map_err(|e| match e {
+                            BencodeError::Json(msg) => BencodeError::Json(format!("{}: {}", k, msg)),
+                            e => e,
+                        })?);
+                }
+                Ok(BencodeItem::Dict(d))
+            },
+            Value::Bool(_) | Value::Null => Err(BencodeError::Json(format!("{} has no bencode equivalent", value))),
+        }
+    }
+}
+
+fn string_to_json(s: &ByteString, policy: &JsonPolicy) -> String {
+    let tagged = |text: &str| policy.tags().any(|tag| text.starts_with(tag));
+    match (s.as_utf8(), policy.binary) {
+        (Some(text), _) if !tagged(text) => String::from(text),
+        (_, BinaryPolicy::Hex) => format!("{}{}", HEX, s.to_hex()),
+        (_, BinaryPolicy::Base64) => format!("{}{}", BASE64, s.to_base64()),
+        (_, BinaryPolicy::LossyUtf8) => {
+            let text = String::from_utf8_lossy(&s.bytes);
+            if tagged(&text) { format!("{}{}", STR, text) } else { text.into_owned() }
+        },
+    }
+}
+
+fn string_from_json(s: &str, policy: &JsonPolicy) -> Result<BencodeItem, BencodeError> {
+    let bytes = match (policy.binary, policy.ints) {
+        (BinaryPolicy::Hex, _) if s.starts_with(HEX) => ByteString::from_hex(&s[HEX.len()..])
+            .ok_or_else(|| BencodeError::Json(format!("invalid hex {}", &s[HEX.len()..])))?,
+        (BinaryPolicy::Base64, _) if s.starts_with(BASE64) => ByteString::from_base64(&s[BASE64.len()..])?,
+        (BinaryPolicy::LossyUtf8, IntPolicy::StringIfUnsafe) if s.starts_with(STR) => ByteString::from(&s[STR.len()..]),
+        (_, IntPolicy::StringIfUnsafe) if s.starts_with(INT) => {
+            return s[INT.len()..].parse::<i64>()
+                .map(BencodeItem::Int)
+                .map_err(|e| BencodeError::Json(format!("{}: {}", s, e)))
+        },
+        _ => ByteString::from(s),
+    };
+    Ok(BencodeItem::String(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(item: &BencodeItem, policy: &JsonPolicy) -> Result<BencodeItem, BencodeError> {
+        BencodeItem::from_json(&item.to_json(policy), policy)
+    }
+
+    #[test]
+    fn to_json() {
+        let item = bencode!({
+            "name": "a",
+            "pieces": ByteString::new(vec!(0x8A, 0xFF)),
+            "files": [{"length": 1}, "hex:ab"],
+            "big": 9007199254740993i64,
+        });
+        assert_eq!(
+            json!({"name": "a", "pieces": "hex:8aff", "files": [{"length": 1}, "hex:6865783a6162"], "big": 9007199254740993i64}),
+            item.to_json(&JsonPolicy::new())
+        );
+        let policy = JsonPolicy::new().binary(BinaryPolicy::Base64).ints(IntPolicy::StringIfUnsafe);
+        assert_eq!(
+            json!({"name": "a", "pieces": "base64:iv8=", "files": [{"length": 1}, "hex:ab"], "big": "int:9007199254740993"}),
+            item.to_json(&policy)
+        );
+        assert_eq!(
+            json!({"name": "a", "pieces": "\u{FFFD}\u{FFFD}", "files": [{"length": 1}, "hex:ab"], "big": 9007199254740993i64}),
+            item.to_json(&JsonPolicy::new().binary(BinaryPolicy::LossyUtf8))
+        );
+    }
+
+    #[test]
+    fn lossless() {
+        let item = bencode!({
+            "a": ["hex:", "base64:x", "int:1", ByteString::new((0..=255).collect()), ""],
+            "b": [i64::MIN, i64::MAX, 0, -9007199254740991i64],
+            "c": {},
+        });
+        for binary in [BinaryPolicy::Hex, BinaryPolicy::Base64] {
+            for ints in [IntPolicy::Number, IntPolicy::StringIfUnsafe] {
+                let policy = JsonPolicy::new().binary(binary).ints(ints);
+                assert_eq!(Ok(item.clone()), round_trip(&item, &policy), "{:?}", policy);
+            }
+        }
+    }
+
+    #[test]
+    fn lossy_utf8_escapes_tags() {
+        let policy = JsonPolicy::new().binary(BinaryPolicy::LossyUtf8).ints(IntPolicy::StringIfUnsafe);
+        let item = bencode!(["int:5", "int:abc", "str:x", "hex:ab", "x", i64::MAX]);
+        assert_eq!(json!(["str:int:5", "str:int:abc", "str:str:x", "hex:ab", "x", "int:9223372036854775807"]), item.to_json(&policy));
+        assert_eq!(Ok(item.clone()), round_trip(&item, &policy));
+
+        // invalid UTF-8 is still lost, but doesn't come back as an int
+        let item = bencode!([ByteString::new(b"int:\xFF".to_vec())]);
+        assert_eq!(json!(["str:int:\u{FFFD}"]), item.to_json(&policy));
+        assert_eq!(Ok(bencode!(["int:\u{FFFD}"])), round_trip(&item, &policy));
+
+        // without string ints there's nothing to escape
+        let policy = JsonPolicy::new().binary(BinaryPolicy::LossyUtf8);
+        assert_eq!(json!(["int:5", "str:x"]), bencode!(["int:5", "str:x"]).to_json(&policy));
+        assert_eq!(Ok(bencode!(["str:x"])), BencodeItem::from_json(&json!(["str:x"]), &policy));
+    }
+
+    #[test]
+    fn from_json() {
+        let policy = JsonPolicy::new();
+        assert_eq!(
+            Ok(bencode!({"a": [1, "x"], "b": ByteString::new(vec!(0xFF))})),
+            BencodeItem::from_json(&json!({"b": "hex:ff", "a": [1, "x"]}), &policy)
+        );
+        assert_eq!(Err(BencodeError::Json(String::from("a: 1.5 doesn't fit a bencode int"))), BencodeItem::from_json(&json!({"a": 1.5}), &policy));
+        assert_eq!(Err(BencodeError::Json(String::from("true has no bencode equivalent"))), BencodeItem::from_json(&json!([true]), &policy));
+        assert_eq!(Err(BencodeError::Json(String::from("invalid hex zz"))), BencodeItem::from_json(&json!("hex:zz"), &policy));
+        assert_eq!(Ok(bencode!("int:1")), BencodeItem::from_json(&json!("int:1"), &policy));
+    }
+}
@@ -1,23 +1,276 @@
 use std::fs;
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub use types::BencodeError;
+pub use types::{BencodeError, ErrorKind, IoError, Result};
 pub use types::BencodeItem;
 pub use types::ByteString;
+pub use dict::{BencodeDict, DuplicatePolicy};
+pub use int::BencodeInt;
+#[cfg(feature = "derive")]
+pub use mescal_derive::{FromBencode, ToBencode};
+pub use convert::FromBencode;
+pub use convert::ToBencode;
 pub use encoder::AsBencodeBytes;
+pub use encoder::encode_into;
+#[cfg(feature = "bytes")]
+pub use encoder::{encode_into_bytes_mut, encode_to_bytes};
+pub use encoder::encode_with_options;
+pub use encoder::EncodeOptions;
+pub use encoder::DictEncoder;
+pub use encoder::ListEncoder;
+pub use encoder::write_to;
 pub use decoder::parse_bytes;
+pub use decoder::parse_bytes_with_options;
+pub use decoder::parse_bytes_with_warnings;
+pub use decoder::{DecodeOptions, DecodeWarning};
+pub use limiter::Limiter;
+#[cfg(feature = "json")]
+pub use json::{BinaryPolicy, IntPolicy, JsonPolicy};
+pub use path::PathSegment;
+#[cfg(feature = "serde")]
+pub use ser::{to_bytes, to_item};
+#[cfg(feature = "serde")]
+pub use de::{from_bytes, from_item};
+pub use pretty::{BinaryStyle, PrettyPrinter, Summary};
+pub use query::{Query, QueryMatch};
+pub use snapshot::Snapshot;
+pub use diff::{diff, Change};
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::{diagnose, Diagnostic};
+pub use text::from_text;
+pub use tokenizer::{Token, TokenKind, Tokenizer};
+pub use walk::Walk;
+pub use recovery::resume_position;
+pub use roundtrip::{verify_roundtrip, RoundTripIssue, RoundTripReport};
+#[cfg(feature = "tokio")]
+pub use async_io::{digest_blocking, parse_bytes_blocking, write_to_async};
 
+pub mod conformance;
+pub mod fixtures;
+pub mod hash;
+pub mod peerwire;
+#[cfg(feature = "proptest")]
+pub mod testing;
+pub mod torrent;
+
+// lets derive output, which refers to ::mescal, be used inside this crate's own tests
+#[cfg(all(test, feature = "derive"))]
+extern crate self as mescal;
+
+#[macro_use]
+mod macros;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+#[cfg(feature = "base64")]
+mod base64;
 mod c;
+#[cfg(feature = "cbor")]
+mod cbor;
 mod types;
+mod dict;
 mod decoder;
 mod encoder;
+mod convert;
+mod compare;
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
+mod diff;
+mod int;
+#[cfg(feature = "json")]
+mod json;
+mod limiter;
+mod memory;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod path;
+mod pretty;
+mod query;
+mod snapshot;
+mod recovery;
+mod roundtrip;
+#[cfg(feature = "serde")]
+mod ser;
+mod text;
+mod tokenizer;
+mod transform;
+mod walk;
+#[cfg(feature = "tokio")]
+mod async_io;
+
+pub fn open<P>(path: P) -> Result<BencodeItem> where P: AsRef<Path> + std::fmt::Display {
+    let bytes = fs::read(&path).map_err(|e| with_path(e, &path.as_ref().display()))?;
+    decoder::parse_with_context(&bytes, &DecodeOptions::default())
+}
+
+// Like open, but gives up with TimedOut once `timeout` has passed, whether the time went
+// on reading or on parsing. A read that's stuck in the OS can't be interrupted, so it's
+// left to finish on its own thread.
+pub fn open_with_timeout<P: AsRef<Path>>(path: P, timeout: Duration) -> Result<BencodeItem> {
+    let path = path.as_ref().to_path_buf();
+    with_timeout(timeout, move || Ok(fs::read(&path).map_err(|e| with_path(e, &path.display()))?))
+}
+
+// io::Error's own messages never say which file they're about
+fn with_path(e: std::io::Error, path: &dyn std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(e.kind(), format!("{}: {}", path, e))
+}
+
+pub fn read_with_timeout<R: Read + Send + 'static>(mut reader: R, timeout: Duration) -> Result<BencodeItem> {
+    with_timeout(timeout, move || {
+        let mut bytes = vec!();
+        reader.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    })
+}
+
+fn with_timeout<F>(timeout: Duration, read: F) -> Result<BencodeItem>
+where
+    F: FnOnce() -> Result<Vec<u8>> + Send + 'static,
+{
+    let deadline = Instant::now().checked_add(timeout);
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let res = read().and_then(|bytes| {
+            let mut options = DecodeOptions::new();
+            if let Some(deadline) = deadline {
+                options = options.should_cancel(move || Instant::now() >= deadline);
+            }
+            decoder::parse_with_context(&bytes, &options)
+        });
+        // the receiver is gone if we already timed out
+        let _ = tx.send(res);
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(Err(BencodeError::Cancelled)) | Err(mpsc::RecvTimeoutError::Timeout) => Err(BencodeError::TimedOut),
+        Ok(res) => res,
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(std::io::Error::other("reader thread panicked").into()),
+    }
+}
+
+// Tells apart the temporary files of saves running at once in this process
+static SAVE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// Writes to a temporary file next to `path` and renames it into place, so readers
+// never observe a half-written file. The directory is synced after the rename, so the
+// new file survives a crash as well.
+pub fn save<P: AsRef<Path>>(path: P, item: &BencodeItem) -> Result<()> {
+    let path = path.as_ref();
+    let file_name = match path.file_name() {
+        Some(n) => n.to_string_lossy(),
+        None => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("not a file path: {}", path.display())).into()),
+    };
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}.{}.tmp", file_name, std::process::id(), SAVE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let write = || -> std::io::Result<()> {
+        let mut w = BufWriter::new(fs::File::create(&tmp_path)?);
+        write_to(item, &mut w)?;
+        w.flush()?;
+        w.get_ref().sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        sync_parent(path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        e.into()
+    })
+}
+
+// Directories can't be opened as files on Windows, where the rename is durable on its own
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    // a bare file name's parent is ""
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_open() {
+        let dir = std::env::temp_dir().join(format!("mescal-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("x.torrent");
+
+        let item = bencode!({"announce": "udp://x", "info": {"length": 1}});
+        save(&path, &item).unwrap();
+        save(&path, &item).unwrap();
+        assert_eq!(Ok(item), open(path.to_str().unwrap()));
+        // nothing left behind but the file itself
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+
+        assert!(save(dir.join("missing").join("x.torrent"), &BencodeItem::Int(1)).is_err());
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_concurrently() {
+        let dir = std::env::temp_dir().join(format!("mescal-save-threads-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("x.torrent");
+
+        let threads: Vec<_> = (0..8).map(|i| {
+            let path = path.clone();
+            thread::spawn(move || save(path, &bencode!({"i": i})))
+        }).collect();
+        for t in threads {
+            t.join().unwrap().unwrap();
+        }
+        assert!(matches!(open(path.to_str().unwrap()), Ok(BencodeItem::Dict(_))));
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_corrupt() {
+        let path = std::env::temp_dir().join(format!("mescal-corrupt-{}.torrent", std::process::id()));
+        fs::write(&path, b"d8:announce7:udp://x4:infod6:lengthi1x0eee").unwrap();
+        let err = open(path.to_str().unwrap()).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(ErrorKind::Syntax, err.kind());
+        assert_eq!(
+            "invalid int \"1x0\" at byte 36: invalid digit found in string near byte 37: 6c 65 6e 67 74 68 69 31 [78] 30 65 65 65 | lengthi1[x]0eee",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn timeouts() {
+        struct Stalled;
+        impl Read for Stalled {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                thread::sleep(Duration::from_secs(1));
+                Ok(0)
+            }
+        }
+
+        assert_eq!(Err(BencodeError::TimedOut), read_with_timeout(Stalled, Duration::from_millis(20)));
+        assert_eq!(Ok(bencode!([1, "a"])), read_with_timeout(&b"li1e1:ae"[..], Duration::from_secs(5)));
+        assert_eq!(
+            Err(BencodeError::Parse { offset: 4, excerpt: String::from("6c 69 31 65 [end] | li1e[end]"), error: Box::new(BencodeError::BytestreamEnded) }),
+            read_with_timeout(&b"li1e"[..], Duration::from_secs(5))
+        );
 
-pub fn open<P>(path: P) -> Result<BencodeItem, BencodeError> where P: AsRef<Path> + std::fmt::Display {
-    let res = &fs::read(&path);
-    match res {
-        Err(e) => Err(
-            BencodeError::FileRead(format!("couldn't read path {}: {}", path, e))
-        ),
-        Ok(b) => decoder::parse_bytes(&mut b.iter().peekable()),
+        let path = std::env::temp_dir().join(format!("mescal-timeout-{}.torrent", std::process::id()));
+        save(&path, &bencode!({"a": 1})).unwrap();
+        assert_eq!(Ok(bencode!({"a": 1})), open_with_timeout(&path, Duration::from_secs(5)));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(open_with_timeout(&path, Duration::from_secs(5)), Err(BencodeError::Io(_))));
     }
 }
@@ -0,0 +1,112 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::{BencodeError, BencodeItem, DecodeOptions};
+use crate::decoder;
+
+// Every source that sends anything is remembered until evict_idle is called, so a
+// long-running service should call it periodically; otherwise memory grows with the
+// number of distinct sources ever seen.
+pub struct Limiter<K> {
+    window: Duration,
+    max_bytes: usize,
+    max_messages: usize,
+    // what each accepted message is parsed with; network_untrusted unless replaced
+    options: DecodeOptions,
+    // per source: (arrival time, size) of every message accepted within the window
+    sources: HashMap<K, VecDeque<(Instant, usize)>>,
+}
+
+impl<K: Hash + Eq> Limiter<K> {
+    pub fn new(window: Duration, max_bytes: usize, max_messages: usize) -> Self {
+        Limiter { window, max_bytes, max_messages, options: DecodeOptions::network_untrusted(), sources: HashMap::new() }
+    }
+
+    pub fn decode_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn parse_bytes(&mut self, source: K, bytes: &[u8]) -> Result<BencodeItem, BencodeError> {
+        self.parse_bytes_at(source, bytes, Instant::now())
+    }
+
+    pub fn parse_bytes_at(&mut self, source: K, bytes: &[u8], now: Instant) -> Result<BencodeItem, BencodeError> {
+        let window = self.window;
+        let history = self.sources.entry(source).or_default();
+        while let Some(&(t, _)) = history.front() {
+            if now.duration_since(t) < window {
+                break;
+            }
+            history.pop_front();
+        }
+
+        let used_bytes: usize = history.iter().map(|(_, len)| len).sum();
+        if history.len() >= self.max_messages || used_bytes + bytes.len() > self.max_bytes {
+            return Err(BencodeError::Throttled)
+        }
+        history.push_back((now, bytes.len()));
+
+        decoder::parse_bytes_with_options(&mut bytes.iter().peekable(), &self.options)
+    }
+
+    // drops bookkeeping for sources that haven't sent anything within the window
+    pub fn evict_idle(&mut self, now: Instant) {
+        let window = self.window;
+        self.sources.retain(|_, history| match history.back() {
+            Some(&(t, _)) => now.duration_since(t) < window,
+            None => false,
+        });
+    }
+
+    pub fn tracked_sources(&self) -> usize {
+        self.sources.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttles_per_source() {
+        let mut limiter = Limiter::new(Duration::from_secs(10), 100, 2);
+        let start = Instant::now();
+
+        assert_eq!(Ok(BencodeItem::Int(1)), limiter.parse_bytes_at("a", b"i1e", start));
+        assert_eq!(Ok(BencodeItem::Int(2)), limiter.parse_bytes_at("a", b"i2e", start));
+        assert_eq!(Err(BencodeError::Throttled), limiter.parse_bytes_at("a", b"i3e", start));
+        // other sources have their own budget
+        assert_eq!(Ok(BencodeItem::Int(4)), limiter.parse_bytes_at("b", b"i4e", start));
+        // the window slides
+        let later = start + Duration::from_secs(10);
+        assert_eq!(Ok(BencodeItem::Int(5)), limiter.parse_bytes_at("a", b"i5e", later));
+
+        limiter.evict_idle(later);
+        assert_eq!(1, limiter.tracked_sources());
+    }
+
+    #[test]
+    fn throttles_on_bytes() {
+        let mut limiter = Limiter::new(Duration::from_secs(10), 5, 100);
+        let now = Instant::now();
+
+        assert!(limiter.parse_bytes_at(1, b"i1e", now).is_ok());
+        assert_eq!(Err(BencodeError::Throttled), limiter.parse_bytes_at(1, b"i22e", now));
+        assert!(limiter.parse_bytes_at(1, b"0:", now).is_ok());
+    }
+
+    #[test]
+    fn decode_limits() {
+        let mut deep = vec!(b'l'; 80_000);
+        deep.extend(vec!(b'e'; 80_000));
+        let mut limiter = Limiter::new(Duration::from_secs(10), 1024 * 1024, 100);
+        let now = Instant::now();
+        assert_eq!(Err(BencodeError::Cancelled), limiter.parse_bytes_at(1, &deep, now));
+
+        let mut limiter = Limiter::new(Duration::from_secs(10), 1024, 100).decode_options(DecodeOptions::new().max_depth(1));
+        assert!(limiter.parse_bytes_at(1, b"li1ee", now).is_ok());
+        assert_eq!(Err(BencodeError::Cancelled), limiter.parse_bytes_at(1, b"llee", now));
+    }
+}
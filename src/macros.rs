@@ -0,0 +1,93 @@
+// JSON-like literal syntax for building trees:
+//
+//     bencode!({"announce": "udp://tracker", "info": {"length": 123, "files": [1, 2]}})
+//
+// Leaf values go through ToBencode. Integer literals that don't fit in an i32 need a
+// suffix (5000000000i64), since that's what an unsuffixed literal falls back to.
+#[macro_export]
+macro_rules! bencode {
+    // list elements, munched one at a time into `[$elems]`
+    (@list [$($elems:expr,)*]) => {
+        ::std::vec![$($elems,)*]
+    };
+    (@list [$($elems:expr,)*] [$($inner:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($elems,)* $crate::bencode!([$($inner)*]),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($elems,)* $crate::bencode!({$($inner)*}),] $($($rest)*)?)
+    };
+    (@list [$($elems:expr,)*] $next:expr $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($elems,)* $crate::bencode!($next),] $($($rest)*)?)
+    };
+
+    // dict entries, same approach
+    (@dict [$($entries:expr,)*]) => {
+        ::std::vec![$($entries,)*]
+    };
+    (@dict [$($entries:expr,)*] $key:literal : [$($inner:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@dict [$($entries,)* (::std::string::String::from($key), $crate::bencode!([$($inner)*])),] $($($rest)*)?)
+    };
+    (@dict [$($entries:expr,)*] $key:literal : {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@dict [$($entries,)* (::std::string::String::from($key), $crate::bencode!({$($inner)*})),] $($($rest)*)?)
+    };
+    (@dict [$($entries:expr,)*] $key:literal : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@dict [$($entries,)* (::std::string::String::from($key), $crate::bencode!($value)),] $($($rest)*)?)
+    };
+
+    ([$($tt:tt)*]) => {
+        $crate::BencodeItem::List($crate::bencode!(@list [] $($tt)*))
+    };
+    ({$($tt:tt)*}) => {
+        $crate::BencodeItem::Dict($crate::BencodeDict::from($crate::bencode!(@dict [] $($tt)*)))
+    };
+    ($other:expr) => {
+        $crate::ToBencode::to_bencode(&$other)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BencodeItem, ByteString};
+
+    macro_rules! bencode_string {
+        ($literal:expr) => {
+            BencodeItem::String(ByteString::from($literal))
+        };
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(BencodeItem::Int(-7), bencode!(-7));
+        assert_eq!(bencode_string!("Hello"), bencode!("Hello"));
+        assert_eq!(BencodeItem::List(vec!()), bencode!([]));
+        assert_eq!(BencodeItem::Dict(vec!().into()), bencode!({}));
+        let name = String::from("x");
+        assert_eq!(bencode_string!("x"), bencode!(name));
+        assert_eq!(BencodeItem::Int(5000000000), bencode!(5000000000i64));
+    }
+
+    #[test]
+    fn nested() {
+        let length = 123i64;
+        assert_eq!(
+            BencodeItem::Dict(vec!(
+                (String::from("announce"), bencode_string!("udp://tracker")),
+                (String::from("info"), BencodeItem::Dict(vec!(
+                    (String::from("length"), BencodeItem::Int(123)),
+                    (String::from("files"), BencodeItem::List(vec!(
+                        BencodeItem::List(vec!(bencode_string!("a"), BencodeItem::Int(-1))),
+                        BencodeItem::Dict(vec!((String::from("path"), BencodeItem::List(vec!()))).into()),
+                        BencodeItem::Int(3),
+                    ))),
+                ).into())),
+            ).into()),
+            bencode!({
+                "announce": "udp://tracker",
+                "info": {
+                    "length": length,
+                    "files": [["a", -1], {"path": []}, 1 + 2],
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use std::mem::size_of;
+
+use crate::{BencodeDict, BencodeItem};
+
+impl BencodeItem {
+    // Bytes allocated on the heap for this tree: string buffers, list and dict storage and
+    // dict keys, counted by capacity. The item itself isn't included, nor is allocator
+    // overhead, so the real figure is somewhat higher.
+    pub fn approx_heap_size(&self) -> usize {
+        self.walk().map(|(_, node)| match node {
+            BencodeItem::Int(_) => 0,
+            BencodeItem::String(s) => s.bytes.capacity(),
+            BencodeItem::List(l) => l.capacity() * size_of::<BencodeItem>(),
+            BencodeItem::Dict(d) => dict_heap_size(d),
+        }).sum()
+    }
+
+    // drops spare capacity everywhere in the tree, e.g. before caching a decoded torrent
+    pub fn shrink_to_fit(&mut self) {
+        self.walk_mut(|_, node| match node {
+            BencodeItem::Int(_) => {},
+            BencodeItem::String(s) => s.bytes.shrink_to_fit(),
+            BencodeItem::List(l) => l.shrink_to_fit(),
+            BencodeItem::Dict(d) => d.shrink_to_fit(),
+        });
+    }
+}
+
+fn dict_heap_size(d: &BencodeDict) -> usize {
+    d.capacity() * size_of::<(String, BencodeItem)>() + d.iter().map(|(k, _)| k.capacity()).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ByteString;
+
+    #[test]
+    fn approx_heap_size() {
+        assert_eq!(0, bencode!(1).approx_heap_size());
+        assert_eq!(0, bencode!([]).approx_heap_size());
+        assert_eq!(3, bencode!(ByteString::new(vec!(1, 2, 3))).approx_heap_size());
+
+        let item = bencode!({"ab": [1, "xyz"]});
+        let expected = size_of::<(String, BencodeItem)>() + 2 + 2 * size_of::<BencodeItem>() + 3;
+        assert_eq!(expected, item.approx_heap_size());
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut pieces = Vec::with_capacity(1000);
+        pieces.extend_from_slice(&[0xFF; 20]);
+        let mut files = Vec::with_capacity(100);
+        files.push(bencode!({"length": 1}));
+        let mut info = BencodeDict::with_capacity(50);
+        info.insert(String::with_capacity(64) + "pieces", BencodeItem::String(ByteString::new(pieces)));
+        info.insert("files", BencodeItem::List(files));
+        let mut item = BencodeItem::Dict(info);
+
+        let before = item.approx_heap_size();
+        let expected = item.clone();
+        item.shrink_to_fit();
+        assert_eq!(expected, item);
+        assert!(item.approx_heap_size() < before / 10, "{} -> {}", before, item.approx_heap_size());
+        assert_eq!(20, item["pieces"].as_raw_bytes().unwrap().len());
+        assert_eq!(Some(2), item.as_dict().map(BencodeDict::capacity));
+    }
+}
@@ -0,0 +1,96 @@
+use rmpv::Value;
+
+use crate::convert::in_field;
+use crate::{BencodeDict, BencodeError, BencodeItem, ByteString};
+
+// Conversion to and from rmpv's MessagePack values, with the same mapping as for CBOR:
+// UTF-8 strings become str and the rest bin, dicts become maps in stored order, and the
+// round trip from bencode is exact. Bools become 0/1; nil, floats and ext types are
+// errors.
+
+impl From<&BencodeItem> for Value {
+    fn from(item: &BencodeItem) -> Value {
+        match item {
+            BencodeItem::Int(i) => Value::from(*i),
+            BencodeItem::String(s) => match s.as_utf8() {
+                Some(text) => Value::from(text),
+                None => Value::Binary(s.bytes.clone()),
+            },
+            BencodeItem::List(l) => Value::Array(l.iter().map(Value::from).collect()),
+            BencodeItem::Dict(d) => Value::Map(d.iter().map(|(k, v)| (Value::from(k.as_str()), Value::from(v))).collect()),
+        }
+    }
+}
+
+impl TryFrom<&Value> for BencodeItem {
+    type Error = BencodeError;
+
+    fn try_from(value: &Value) -> Result<BencodeItem, BencodeError> {
+        match value {
+            Value::Integer(i) => i.as_i64()
+                .map(BencodeItem::Int)
+                .ok_or(BencodeError::ValueOutOfRange(format!("{} doesn't fit a bencode int", i))),
+            Value::Boolean(b) => Ok(BencodeItem::Int(*b as i64)),
+            // str holding invalid UTF-8 is possible in MessagePack, and kept as is
+            Value::String(s) => Ok(BencodeItem::String(ByteString::from(s.as_bytes()))),
+            Value::Binary(b) => Ok(BencodeItem::String(ByteString::from(b.as_slice()))),
+            Value::Array(a) => a.iter().enumerate()
+                .map(|(i, v)| BencodeItem::try_from(v).map_err(|e| in_field(&format!("[{}]", i), e)))
+                .collect(),
+            Value::Map(m) => {
+                let mut d = BencodeDict::with_capacity(m.len());
+                for (k, v) in m {
+                    let key = match k {
+                        Value::String(k) => k.as_str().map(String::from).ok_or(BencodeError::DictKeyParse)?,
+                        Value::Binary(b) => String::from_utf8(b.clone()).map_err(|_| BencodeError::DictKeyParse)?,
+                        _ => return Err(BencodeError::UnexpectedType(String::from("map keys must be strings"))),
+                    };
+                    let value = BencodeItem::try_from(v).map_err(|e| in_field(&key, e))?;
+                    d.push(key, value);
+                }
+                Ok(BencodeItem::Dict(d))
+            },
+            Value::F32(_) | Value::F64(_) => Err(BencodeError::UnexpectedType(String::from("bencode has no floats"))),
+            Value::Nil => Err(BencodeError::UnexpectedType(String::from("bencode has no nil"))),
+            Value::Ext(kind, _) => Err(BencodeError::UnexpectedType(format!("bencode has no ext types, found type {}", kind))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let item = bencode!({
+            "b": [1, -2, i64::MIN, "x", ByteString::new(vec!(0xFF, 0))],
+            "a": {},
+            "a": [],
+        });
+        let value = Value::from(&item);
+        assert_eq!(
+            Value::Map(vec!(
+                (Value::from("b"), Value::Array(vec!(
+                    Value::from(1), Value::from(-2), Value::from(i64::MIN), Value::from("x"), Value::Binary(vec!(0xFF, 0)),
+                ))),
+                (Value::from("a"), Value::Map(vec!())),
+                (Value::from("a"), Value::Array(vec!())),
+            )),
+            value
+        );
+        assert_eq!(Ok(item), BencodeItem::try_from(&value));
+    }
+
+    #[test]
+    fn from_msgpack() {
+        let value = Value::Map(vec!((Value::from("k"), Value::Array(vec!(Value::from(true), Value::from(1.5))))));
+        assert_eq!(Err(BencodeError::UnexpectedType(String::from("k: [1]: bencode has no floats"))), BencodeItem::try_from(&value));
+        assert_eq!(
+            Err(BencodeError::ValueOutOfRange(String::from("18446744073709551615 doesn't fit a bencode int"))),
+            BencodeItem::try_from(&Value::from(u64::MAX))
+        );
+        assert_eq!(Err(BencodeError::UnexpectedType(String::from("map keys must be strings"))), BencodeItem::try_from(&Value::Map(vec!((Value::Nil, Value::Nil)))));
+        assert_eq!(Ok(bencode!({"k": 1})), BencodeItem::try_from(&Value::Map(vec!((Value::Binary(b"k".to_vec()), Value::from(true))))));
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{BencodeDict, BencodeError, BencodeItem};
+
+// One step of a path into a tree. The string form "info/files/0/path" is split on '/',
+// and each piece is a list index or a dict key depending on what it's applied to; typed
+// segments are for keys that contain '/' or look like numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+impl BencodeItem {
+    // "" is the item itself; a trailing '/' reaches the empty key, as in BEP 52 file trees
+    pub fn get_path(&self, path: &str) -> Option<&BencodeItem> {
+        split(path).try_fold(self, |item, segment| match item {
+            BencodeItem::List(l) => l.get(segment.parse::<usize>().ok()?),
+            _ => item.get(segment),
+        })
+    }
+
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut BencodeItem> {
+        self.get_split_mut(split(path))
+    }
+
+    fn get_split_mut<'p>(&mut self, mut segments: impl Iterator<Item = &'p str>) -> Option<&mut BencodeItem> {
+        segments.try_fold(self, |item, segment| match item {
+            BencodeItem::List(l) => l.get_mut(segment.parse::<usize>().ok()?),
+            _ => item.get_mut(segment),
+        })
+    }
+
+    pub fn get_segments(&self, path: &[PathSegment]) -> Option<&BencodeItem> {
+        path.iter().try_fold(self, |item, segment| match segment {
+            PathSegment::Key(k) => item.get(k),
+            PathSegment::Index(i) => item.get_index(*i),
+        })
+    }
+
+    pub fn get_segments_mut(&mut self, path: &[PathSegment]) -> Option<&mut BencodeItem> {
+        path.iter().try_fold(self, |item, segment| match segment {
+            PathSegment::Key(k) => item.get_mut(k),
+            PathSegment::Index(i) => item.get_index_mut(*i),
+        })
+    }
+}
+
+// Editing by path. Missing dict keys along the way are created as empty dicts, and new
+// keys go in sorted position, so a canonical tree stays canonical. Lists are never
+// extended implicitly: an index has to exist, except that insert_path may append.
+impl BencodeItem {
+    // replaces whatever is at `path`, returning it
+    pub fn set_path(&mut self, path: &str, value: BencodeItem) -> Result<Option<BencodeItem>, BencodeError> {
+        let (parent, last) = match split_last(path) {
+            Some((parents, last)) => (self.create_path(&parents, path)?, last),
+            None => return Ok(Some(std::mem::replace(self, value))),
+        };
+        match parent {
+            BencodeItem::List(l) => {
+                let i = list_index(l, last, path)?;
+                Ok(Some(std::mem::replace(&mut l[i], value)))
+            },
+            BencodeItem::Dict(d) => match d.iter_mut().find(|(k, _)| k == last) {
+                Some((_, v)) => Ok(Some(std::mem::replace(v, value))),
+                None => {
+                    insert_sorted(d, last, value);
+                    Ok(None)
+                }
+            },
+            other => Err(not_a_container(parent_path(path), other)),
+        }
+    }
+
+    // adds without replacing: shifts list elements along, refuses existing dict keys
+    pub fn insert_path(&mut self, path: &str, value: BencodeItem) -> Result<(), BencodeError> {
+        let (parent, last) = match split_last(path) {
+            Some((parents, last)) => (self.create_path(&parents, path)?, last),
+            None => return Err(BencodeError::ValueOutOfRange(String::from("can't insert at the root"))),
+        };
+        match parent {
+            BencodeItem::List(l) => match last.parse::<usize>() {
+                Ok(i) if i <= l.len() => {
+                    l.insert(i, value);
+                    Ok(())
+                },
+                _ => Err(BencodeError::ValueOutOfRange(format!("{}: no index {} in a list of {}", path, last, l.len()))),
+            },
+            BencodeItem::Dict(d) => {
+                if d.iter().any(|(k, _)| k == last) {
+                    return Err(BencodeError::DuplicateKey(format!("{}: key already present", path)))
+                }
+                insert_sorted(d, last, value);
+                Ok(())
+            },
+            other => Err(not_a_container(parent_path(path), other)),
+        }
+    }
+
+    // the removed value, or None if nothing was there
+    pub fn remove_path(&mut self, path: &str) -> Option<BencodeItem> {
+        let (parents, last) = split_last(path)?;
+        let parent = self.get_split_mut(parents.into_iter())?;
+        match parent {
+            BencodeItem::List(l) => {
+                let i = last.parse::<usize>().ok().filter(|i| *i < l.len())?;
+                Some(l.remove(i))
+            },
+            BencodeItem::Dict(d) => d.remove(last),
+            _ => None,
+        }
+    }
+
+    // `parents` are the leading segments of `path`, which is for error messages
+    fn create_path(&mut self, parents: &[&str], path: &str) -> Result<&mut BencodeItem, BencodeError> {
+        let mut item = self;
+        let mut end = 0usize;
+        for &segment in parents {
+            let parent = &path[..end.saturating_sub(1)];
+            end += segment.len() + 1;
+            item = match item {
+                BencodeItem::List(l) => {
+                    let i = list_index(l, segment, &path[..end - 1])?;
+                    &mut l[i]
+                },
+                BencodeItem::Dict(d) => {
+                    let i = match d.iter().position(|(k, _)| k == segment) {
+                        Some(i) => i,
+                        None => insert_sorted(d, segment, BencodeItem::Dict(vec!().into())),
+                    };
+                    &mut d.entries_mut()[i].1
+                },
+                other => return Err(not_a_container(parent, other)),
+            };
+        }
+        Ok(item)
+    }
+}
+
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("")
+}
+
+fn list_index(l: &[BencodeItem], segment: &str, path: &str) -> Result<usize, BencodeError> {
+    match segment.parse::<usize>() {
+        Ok(i) if i < l.len() => Ok(i),
+        _ => Err(BencodeError::ValueOutOfRange(format!("{}: no index {} in a list of {}", path, segment, l.len()))),
+    }
+}
+
+fn insert_sorted(d: &mut BencodeDict, key: &str, value: BencodeItem) -> usize {
+    let i = d.iter().position(|(k, _)| k.as_bytes() > key.as_bytes()).unwrap_or(d.len());
+    d.entries_mut().insert(i, (String::from(key), value));
+    i
+}
+
+// `path` is where the non-container was found, "" for the root
+fn not_a_container(path: &str, found: &BencodeItem) -> BencodeError {
+    let msg = format!("expected dict or list, found {}", found.type_name());
+    if path.is_empty() {
+        return BencodeError::UnexpectedType(msg)
+    }
+    BencodeError::UnexpectedType(format!("{}: {}", path, msg))
+}
+
+// the slash-separated form of a typed path
+pub(crate) fn path_string(path: &[PathSegment]) -> String {
+    let segments: Vec<String> = path.iter().map(|s| match s {
+        PathSegment::Key(k) => String::from(*k),
+        PathSegment::Index(i) => i.to_string(),
+    }).collect();
+    segments.join("/")
+}
+
+fn split(path: &str) -> impl Iterator<Item = &str> {
+    // "".split('/') would yield one empty segment
+    path.split('/').filter(move |_| !path.is_empty())
+}
+
+// the segments leading to the last one, and the last one; None for the root
+fn split_last(path: &str) -> Option<(Vec<&str>, &str)> {
+    let mut segments: Vec<&str> = split(path).collect();
+    let last = segments.pop()?;
+    Some((segments, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_path() {
+        let mut item = bencode!({
+            "info": {"files": [{"length": 1, "path": ["a", "b"]}], "0": "key"},
+            "file tree": {"a/b": {"": {"length": 2}}},
+        });
+        assert_eq!(Some(&item), item.get_path(""));
+        assert_eq!(Some(&bencode!(["a", "b"])), item.get_path("info/files/0/path"));
+        assert_eq!(Some(&bencode!("b")), item.get_path("info/files/0/path/1"));
+        assert_eq!(Some(&bencode!("key")), item.get_path("info/0"));
+        assert_eq!(None, item.get_path("info/files/x"));
+        assert_eq!(None, item.get_path("info/files/1"));
+        assert_eq!(None, item.get_path("info/files/0/length/0"));
+
+        let segments = [PathSegment::Key("file tree"), PathSegment::Key("a/b"), PathSegment::Key(""), PathSegment::Key("length")];
+        assert_eq!(Some(&bencode!(2)), item.get_segments(&segments));
+        assert_eq!(None, item.get_segments(&[PathSegment::Index(0)]));
+
+        *item.get_path_mut("info/files/0/length").unwrap() = bencode!(5);
+        *item.get_segments_mut(&segments).unwrap() = bencode!(6);
+        assert_eq!(Some(5), item.get_path("info/files/0/length").and_then(BencodeItem::as_int));
+        assert_eq!(Some(6), item.get_segments(&segments).and_then(BencodeItem::as_int));
+    }
+
+    #[test]
+    fn set_path() {
+        let mut item = bencode!({"announce": "udp://a", "info": {"files": [{"length": 1}]}});
+        assert_eq!(Ok(Some(bencode!("udp://a"))), item.set_path("announce", bencode!("udp://b")));
+        assert_eq!(Ok(Some(bencode!(1))), item.set_path("info/files/0/length", bencode!(2)));
+        assert_eq!(Ok(None), item.set_path("comment", bencode!("hi")));
+        assert_eq!(Ok(None), item.set_path("info/x/y", bencode!(3)));
+        assert_eq!(
+            bencode!({"announce": "udp://b", "comment": "hi", "info": {"files": [{"length": 2}], "x": {"y": 3}}}),
+            item
+        );
+
+        assert_eq!(
+            Err(BencodeError::ValueOutOfRange(String::from("info/files/1: no index 1 in a list of 1"))),
+            item.set_path("info/files/1/length", bencode!(0))
+        );
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("announce: expected dict or list, found string"))),
+            item.set_path("announce/x", bencode!(0))
+        );
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("info/x/y: expected dict or list, found int"))),
+            item.set_path("info/x/y/z/w", bencode!(0))
+        );
+        assert_eq!(
+            Err(BencodeError::UnexpectedType(String::from("expected dict or list, found int"))),
+            bencode!(1).insert_path("x", bencode!(0))
+        );
+        assert_eq!(Ok(Some(item.clone())), item.set_path("", bencode!(1)));
+        assert_eq!(bencode!(1), item);
+    }
+
+    #[test]
+    fn insert_and_remove_path() {
+        let mut item = bencode!({"b": [1, 3]});
+        item.insert_path("b/1", bencode!(2)).unwrap();
+        item.insert_path("b/3", bencode!(4)).unwrap();
+        item.insert_path("a", bencode!("first")).unwrap();
+        item.insert_path("c/d", bencode!({})).unwrap();
+        assert_eq!(bencode!({"a": "first", "b": [1, 2, 3, 4], "c": {"d": {}}}), item);
+        assert!(matches!(item.insert_path("a", bencode!(0)), Err(BencodeError::DuplicateKey(_))));
+        assert!(matches!(item.insert_path("b/9", bencode!(0)), Err(BencodeError::ValueOutOfRange(_))));
+        assert_eq!(Err(BencodeError::ValueOutOfRange(String::from("can't insert at the root"))), item.insert_path("", bencode!(0)));
+
+        assert_eq!(Some(bencode!("first")), item.remove_path("a"));
+        assert_eq!(Some(bencode!(2)), item.remove_path("b/1"));
+        assert_eq!(Some(bencode!({})), item.remove_path("c/d"));
+        assert_eq!(None, item.remove_path("a"));
+        assert_eq!(None, item.remove_path("b/9"));
+        assert_eq!(None, item.remove_path("x/y"));
+        assert_eq!(bencode!({"b": [1, 3, 4], "c": {}}), item);
+    }
+
+    #[test]
+    fn empty_key() {
+        let item = bencode!({"docs": {"": {"length": 3}}});
+        assert_eq!(Some(&bencode!({"length": 3})), item.get_path("docs/"));
+    }
+
+    #[test]
+    fn empty_segments_round_trip() {
+        let mut item = bencode!({});
+        for path in ["/a", "a//b", "c/", "//"] {
+            assert_eq!(Ok(None), item.set_path(path, bencode!(1)), "{}", path);
+            assert_eq!(Some(&bencode!(1)), item.get_path(path), "{}", path);
+        }
+        assert_eq!(bencode!({"": {"": {"": 1}, "a": 1}, "a": {"": {"b": 1}}, "c": {"": 1}}), item);
+
+        assert_eq!(Err(BencodeError::DuplicateKey(String::from("/a: key already present"))), item.insert_path("/a", bencode!(2)));
+        item.insert_path("/b", bencode!(2)).unwrap();
+        assert_eq!(Some(&bencode!(2)), item.get_path("/b"));
+        assert_eq!(Some(bencode!(1)), item.remove_path("/a"));
+        assert_eq!(None, item.get_path("/a"));
+        assert_eq!(Some(&bencode!({"": {"b": 1}})), item.get_path("a"));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::BencodeError;
+
+// BEP 3 peer wire protocol: the fixed handshake and the length-prefixed messages that follow it.
+
+const PROTOCOL: &[u8; 19] = b"BitTorrent protocol";
+// largest frame we'll accept; comfortably fits 16 KiB blocks and the bitfield of a huge torrent
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+// reserved[5] & 0x10 advertises the BEP 10 extension protocol
+const EXTENSION_BYTE: usize = 5;
+const EXTENSION_BIT: u8 = 0x10;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Handshake {
+    pub reserved: [u8; 8],
+    pub info_hash: [u8; 20],
+    pub peer_id: [u8; 20],
+}
+
+impl Handshake {
+    pub const LEN: usize = 68;
+
+    pub fn new(info_hash: [u8; 20], peer_id: [u8; 20]) -> Self {
+        Handshake { reserved: [0; 8], info_hash, peer_id }
+    }
+
+    pub fn with_extensions(mut self) -> Self {
+        self.reserved[EXTENSION_BYTE] |= EXTENSION_BIT;
+        self
+    }
+
+    pub fn supports_extensions(&self) -> bool {
+        self.reserved[EXTENSION_BYTE] & EXTENSION_BIT != 0
+    }
+
+    pub fn to_bytes(&self) -> [u8; Handshake::LEN] {
+        let mut bytes = [0; Handshake::LEN];
+        bytes[0] = PROTOCOL.len() as u8;
+        bytes[1..20].copy_from_slice(PROTOCOL);
+        bytes[20..28].copy_from_slice(&self.reserved);
+        bytes[28..48].copy_from_slice(&self.info_hash);
+        bytes[48..68].copy_from_slice(&self.peer_id);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BencodeError> {
+        if bytes.len() < Handshake::LEN {
+            return Err(BencodeError::BytestreamEnded)
+        }
+        if bytes[0] as usize != PROTOCOL.len() || &bytes[1..20] != PROTOCOL {
+            return Err(BencodeError::PeerWire(String::from("not a BitTorrent handshake")))
+        }
+        let mut handshake = Handshake { reserved: [0; 8], info_hash: [0; 20], peer_id: [0; 20] };
+        handshake.reserved.copy_from_slice(&bytes[20..28]);
+        handshake.info_hash.copy_from_slice(&bytes[28..48]);
+        handshake.peer_id.copy_from_slice(&bytes[48..68]);
+        Ok(handshake)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Message {
+    KeepAlive,
+    Choke,
+    Unchoke,
+    Interested,
+    NotInterested,
+    Have(u32),
+    Bitfield(Vec<u8>),
+    Request { index: u32, begin: u32, length: u32 },
+    Piece { index: u32, begin: u32, block: Vec<u8> },
+    Cancel { index: u32, begin: u32, length: u32 },
+    Port(u16),
+    // BEP 10; the payload is usually bencoded
+    Extended { id: u8, payload: Vec<u8> },
+}
+
+mod id {
+    pub const CHOKE: u8 = 0;
+    pub const UNCHOKE: u8 = 1;
+    pub const INTERESTED: u8 = 2;
+    pub const NOT_INTERESTED: u8 = 3;
+    pub const HAVE: u8 = 4;
+    pub const BITFIELD: u8 = 5;
+    pub const REQUEST: u8 = 6;
+    pub const PIECE: u8 = 7;
+    pub const CANCEL: u8 = 8;
+    pub const PORT: u8 = 9;
+    pub const EXTENDED: u8 = 20;
+}
+
+impl Message {
+    // the full frame, length prefix included
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body: Vec<u8> = vec!();
+        match self {
+            Message::KeepAlive => (),
+            Message::Choke => body.push(id::CHOKE),
+            Message::Unchoke => body.push(id::UNCHOKE),
+            Message::Interested => body.push(id::INTERESTED),
+            Message::NotInterested => body.push(id::NOT_INTERESTED),
+            Message::Have(index) => {
+                body.push(id::HAVE);
+                body.extend_from_slice(&index.to_be_bytes());
+            },
+            Message::Bitfield(bits) => {
+                body.push(id::BITFIELD);
+                body.extend_from_slice(bits);
+            },
+            Message::Request { index, begin, length } => {
+                body.push(id::REQUEST);
+                for n in [index, begin, length] {
+                    body.extend_from_slice(&n.to_be_bytes());
+                }
+            },
+            Message::Cancel { index, begin, length } => {
+                body.push(id::CANCEL);
+                for n in [index, begin, length] {
+                    body.extend_from_slice(&n.to_be_bytes());
+                }
+            },
+            Message::Piece { index, begin, block } => {
+                body.push(id::PIECE);
+                body.extend_from_slice(&index.to_be_bytes());
+                body.extend_from_slice(&begin.to_be_bytes());
+                body.extend_from_slice(block);
+            },
+            Message::Port(port) => {
+                body.push(id::PORT);
+                body.extend_from_slice(&port.to_be_bytes());
+            },
+            Message::Extended { id, payload } => {
+                body.push(id::EXTENDED);
+                body.push(*id);
+                body.extend_from_slice(payload);
+            },
+        }
+        let mut frame = Vec::with_capacity(4 + body.len());
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.append(&mut body);
+        frame
+    }
+
+    // Decodes the first frame in `buf`. Returns None while the frame is still incomplete,
+    // otherwise the message and how many bytes of `buf` it took up.
+    pub fn decode_frame(buf: &[u8]) -> Result<Option<(Message, usize)>, BencodeError> {
+        if buf.len() < 4 {
+            return Ok(None)
+        }
+        let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(BencodeError::PeerWire(format!("message of {} bytes is too long", len)))
+        }
+        if buf.len() < 4 + len {
+            return Ok(None)
+        }
+        let body = &buf[4..4 + len];
+        Ok(Some((Message::from_body(body)?, 4 + len)))
+    }
+
+    fn from_body(body: &[u8]) -> Result<Message, BencodeError> {
+        let (&msg_id, payload) = match body.split_first() {
+            Some(split) => split,
+            None => return Ok(Message::KeepAlive),
+        };
+        let bad_len = || BencodeError::PeerWire(format!("bad payload length {} for message {}", payload.len(), msg_id));
+        let u32_at = |i: usize| u32::from_be_bytes([payload[i], payload[i + 1], payload[i + 2], payload[i + 3]]);
+        let msg = match msg_id {
+            id::CHOKE | id::UNCHOKE | id::INTERESTED | id::NOT_INTERESTED => {
+                if !payload.is_empty() {
+                    return Err(bad_len())
+                }
+                match msg_id {
+                    id::CHOKE => Message::Choke,
+                    id::UNCHOKE => Message::Unchoke,
+                    id::INTERESTED => Message::Interested,
+                    _ => Message::NotInterested,
+                }
+            },
+            id::HAVE if payload.len() == 4 => Message::Have(u32_at(0)),
+            id::BITFIELD => Message::Bitfield(payload.to_vec()),
+            id::REQUEST if payload.len() == 12 => Message::Request { index: u32_at(0), begin: u32_at(4), length: u32_at(8) },
+            id::CANCEL if payload.len() == 12 => Message::Cancel { index: u32_at(0), begin: u32_at(4), length: u32_at(8) },
+            id::PIECE if payload.len() >= 8 => Message::Piece { index: u32_at(0), begin: u32_at(4), block: payload[8..].to_vec() },
+            id::PORT if payload.len() == 2 => Message::Port(u16::from_be_bytes([payload[0], payload[1]])),
+            id::EXTENDED if !payload.is_empty() => Message::Extended { id: payload[0], payload: payload[1..].to_vec() },
+            id::HAVE | id::REQUEST | id::CANCEL | id::PIECE | id::PORT | id::EXTENDED => return Err(bad_len()),
+            other => return Err(BencodeError::PeerWire(format!("unknown message id {}", other))),
+        };
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake() {
+        let handshake = Handshake::new([1; 20], [2; 20]).with_extensions();
+        let bytes = handshake.to_bytes();
+        assert_eq!(19, bytes[0]);
+        assert_eq!(b"BitTorrent protocol", &bytes[1..20]);
+        assert_eq!([0, 0, 0, 0, 0, 0x10, 0, 0], bytes[20..28]);
+        assert!(Handshake::from_bytes(&bytes).unwrap().supports_extensions());
+        assert_eq!(Ok(handshake), Handshake::from_bytes(&bytes));
+        assert!(!Handshake::new([1; 20], [2; 20]).supports_extensions());
+
+        assert_eq!(Err(BencodeError::BytestreamEnded), Handshake::from_bytes(&bytes[..67]));
+        let mut bad = bytes;
+        bad[1] = b'b';
+        assert!(Handshake::from_bytes(&bad).is_err());
+    }
+
+    #[test]
+    fn messages() {
+        let messages = vec!(
+            Message::KeepAlive,
+            Message::Choke,
+            Message::Unchoke,
+            Message::Interested,
+            Message::NotInterested,
+            Message::Have(7),
+            Message::Bitfield(vec!(0xFF, 0x80)),
+            Message::Request { index: 1, begin: 16384, length: 16384 },
+            Message::Piece { index: 1, begin: 0, block: vec!(1, 2, 3) },
+            Message::Cancel { index: 1, begin: 16384, length: 16384 },
+            Message::Port(6881),
+            Message::Extended { id: 0, payload: b"d1:md6:ut_pexi1eee".to_vec() },
+        );
+        let mut stream: Vec<u8> = vec!();
+        for m in &messages {
+            stream.extend(m.to_bytes());
+        }
+
+        let mut decoded = vec!();
+        let mut buf = &stream[..];
+        while let Some((m, used)) = Message::decode_frame(buf).unwrap() {
+            decoded.push(m);
+            buf = &buf[used..];
+        }
+        assert_eq!(messages, decoded);
+        assert!(buf.is_empty());
+
+        assert_eq!(vec!(0, 0, 0, 5, 4, 0, 0, 0, 7), Message::Have(7).to_bytes());
+    }
+
+    #[test]
+    fn partial_and_invalid_frames() {
+        let frame = Message::Have(7).to_bytes();
+        for i in 0..frame.len() {
+            assert_eq!(Ok(None), Message::decode_frame(&frame[..i]));
+        }
+        assert!(Message::decode_frame(&[0, 0, 0, 2, 4, 0]).is_err());
+        assert!(Message::decode_frame(&[0, 0, 0, 1, 99]).is_err());
+        assert!(Message::decode_frame(&[0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt::{self, Write};
+
+use crate::BencodeItem;
+
+const SUMMARY_STRING_LEN: usize = 32;
+
+// How byte strings that aren't UTF-8 are shown: hex"8a0f" or b"\x8a\x0f"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryStyle {
+    Hex,
+    Escaped,
+}
+
+// A human-readable rendering of a tree, for logs and debugging. Unlike to_text it isn't
+// meant to be parsed back: long strings are cut short and deep containers collapsed, so a
+// real torrent prints as a page rather than megabytes of `pieces`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PrettyPrinter {
+    pub indent: usize,
+    pub max_depth: Option<usize>,
+    pub max_string_len: Option<usize>,
+    pub binary: BinaryStyle,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        PrettyPrinter::new()
+    }
+}
+
+impl PrettyPrinter {
+    // everything, indented by two
+    pub const fn new() -> Self {
+        PrettyPrinter { indent: 2, max_depth: None, max_string_len: None, binary: BinaryStyle::Hex }
+    }
+
+    pub const fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    // containers nested deeper than this are shown with just their size
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    // in bytes; longer strings are cut and followed by their full length
+    pub const fn max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = Some(max_string_len);
+        self
+    }
+
+    pub const fn binary(mut self, binary: BinaryStyle) -> Self {
+        self.binary = binary;
+        self
+    }
+
+    pub fn print(&self, item: &BencodeItem) -> String {
+        let mut out = String::new();
+        self.write(item, &mut out).unwrap();
+        out
+    }
+
+    pub fn write<W: Write>(&self, item: &BencodeItem, out: &mut W) -> fmt::Result {
+        self.write_item(item, 0, out)
+    }
+
+    fn write_item<W: Write>(&self, item: &BencodeItem, depth: usize, out: &mut W) -> fmt::Result {
+        let collapsed = self.max_depth == Some(depth);
+        match item {
+            BencodeItem::Int(i) => write!(out, "{}", i),
+            BencodeItem::String(s) => self.write_string(&s.bytes, self.max_string_len, out),
+            BencodeItem::List(l) if l.is_empty() => out.write_str("[]"),
+            BencodeItem::Dict(d) if d.is_empty() => out.write_str("{}"),
+            BencodeItem::List(l) if collapsed => write!(out, "[… {} items]", l.len()),
+            BencodeItem::Dict(d) if collapsed => write!(out, "{{… {} entries}}", d.len()),
+            BencodeItem::List(l) => {
+                out.write_str("[\n")?;
+                for (i, child) in l.iter().enumerate() {
+                    self.write_indent(depth + 1, out)?;
+                    self.write_item(child, depth + 1, out)?;
+                    out.write_str(if i + 1 < l.len() { ",\n" } else { "\n" })?;
+                }
+                self.write_indent(depth, out)?;
+                out.write_char(']')
+            },
+            BencodeItem::Dict(d) => {
+                out.write_str("{\n")?;
+                for (i, (key, value)) in d.iter().enumerate() {
+                    self.write_indent(depth + 1, out)?;
+                    // keys are short, and cutting them would hide which entry this is
+                    self.write_string(key.as_bytes(), None, out)?;
+                    out.write_str(": ")?;
+                    self.write_item(value, depth + 1, out)?;
+                    out.write_str(if i + 1 < d.len() { ",\n" } else { "\n" })?;
+                }
+                self.write_indent(depth, out)?;
+                out.write_char('}')
+            }
+        }
+    }
+
+    fn write_indent<W: Write>(&self, depth: usize, out: &mut W) -> fmt::Result {
+        write!(out, "{:1$}", "", depth * self.indent)
+    }
+
+    fn write_string<W: Write>(&self, bytes: &[u8], max_len: Option<usize>, out: &mut W) -> fmt::Result {
+        let mut end = max_len.unwrap_or(bytes.len()).min(bytes.len());
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                while !s.is_char_boundary(end) {
+                    end -= 1;
+                }
+                write!(out, "\"{}\"", s[..end].escape_debug())?;
+            },
+            Err(_) => match self.binary {
+                BinaryStyle::Hex => {
+                    out.write_str("hex\"")?;
+                    for b in &bytes[..end] {
+                        write!(out, "{:02x}", b)?;
+                    }
+                    out.write_char('"')?;
+                },
+                BinaryStyle::Escaped => write!(out, "b\"{}\"", bytes[..end].escape_ascii())?,
+            },
+        }
+        if end < bytes.len() {
+            write!(out, "… ({} bytes)", bytes.len())?;
+        }
+        Ok(())
+    }
+}
+
+// For logging: `{:?}` of a summary reads like Debug of plain Rust values (`{"length":
+// 1}` rather than `Dict(BencodeDict { entries: .. })`), and strings past the limit are cut
+// to a preview followed by `…(N bytes, sha1=…)`, the digest only with the sha1 feature.
+pub struct Summary<'a> {
+    item: &'a BencodeItem,
+    max_string_len: usize,
+}
+
+impl BencodeItem {
+    pub fn summary(&self) -> Summary<'_> {
+        Summary { item: self, max_string_len: SUMMARY_STRING_LEN }
+    }
+}
+
+impl Summary<'_> {
+    pub fn max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = max_string_len;
+        self
+    }
+
+    fn child<'b>(&self, item: &'b BencodeItem) -> Summary<'b> {
+        Summary { item, max_string_len: self.max_string_len }
+    }
+}
+
+impl fmt::Debug for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.item {
+            BencodeItem::Int(i) => write!(f, "{}", i),
+            BencodeItem::String(s) => {
+                let bytes = &s.bytes;
+                let end = bytes.len().min(self.max_string_len);
+                match std::str::from_utf8(bytes) {
+                    Ok(s) => {
+                        let end = (0..=end).rev().find(|i| s.is_char_boundary(*i)).unwrap_or(0);
+                        write!(f, "{:?}", &s[..end])?;
+                    },
+                    Err(_) => write!(f, "b\"{}\"", bytes[..end].escape_ascii())?,
+                }
+                if end < bytes.len() {
+                    write!(f, "…({} bytes", bytes.len())?;
+                    #[cfg(feature = "sha1")]
+                    {
+                        let digest = crate::hash::digest::<crate::hash::Sha1>(bytes);
+                        write!(f, ", sha1={}…", crate::ByteString::new(digest[..4].to_vec()).to_hex())?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            },
+            BencodeItem::List(l) => f.debug_list().entries(l.iter().map(|child| self.child(child))).finish(),
+            BencodeItem::Dict(d) => f.debug_map().entries(d.iter().map(|(k, v)| (k, self.child(v)))).finish(),
+        }
+    }
+}
+
+// Compact JSON, which is what `{:#}` prints. Strings that aren't UTF-8 become a JSON
+// string of their hex digits, so the output always parses, at the cost of not telling
+// those apart from text that happens to look like hex.
+pub(crate) fn write_json<W: Write>(item: &BencodeItem, out: &mut W) -> fmt::Result {
+    match item {
+        BencodeItem::Int(i) => write!(out, "{}", i),
+        BencodeItem::String(s) => match std::str::from_utf8(&s.bytes) {
+            Ok(s) => write_json_string(s, out),
+            Err(_) => write_json_string(&s.to_hex(), out),
+        },
+        BencodeItem::List(l) => {
+            out.write_char('[')?;
+            for (i, child) in l.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_json(child, out)?;
+            }
+            out.write_char(']')
+        },
+        BencodeItem::Dict(d) => {
+            out.write_char('{')?;
+            for (i, (key, value)) in d.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_json_string(key, out)?;
+                out.write_char(':')?;
+                write_json(value, out)?;
+            }
+            out.write_char('}')
+        }
+    }
+}
+
+fn write_json_string<W: Write>(s: &str, out: &mut W) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ByteString;
+
+    #[test]
+    fn print() {
+        let item = bencode!({
+            "announce": "udp://x",
+            "info": {"pieces": ByteString::new(vec!(0x8A, 0x00, 0x22, 0xFF)), "files": [{"path": ["a"]}], "meta": {}},
+        });
+        assert_eq!(
+            "{\n  \"announce\": \"udp://x\",\n  \"info\": {\n    \"pieces\": hex\"8a0022ff\",\n    \"files\": [\n      {\n        \"path\": [\n          \"a\"\n        ]\n      }\n    ],\n    \"meta\": {}\n  }\n}",
+            PrettyPrinter::new().print(&item)
+        );
+        assert_eq!(
+            "{\n \"announce\": \"udp:\"… (7 bytes),\n \"info\": {… 3 entries}\n}",
+            PrettyPrinter::new().indent(1).max_depth(1).max_string_len(4).print(&item)
+        );
+        assert_eq!("[… 1 items]", PrettyPrinter::new().max_depth(0).print(&bencode!([1])));
+    }
+
+    #[test]
+    fn strings() {
+        let binary = bencode!(ByteString::new(vec!(0x8A, 0x00, 0x22, 0xFF)));
+        assert_eq!("b\"\\x8a\\x00\\\"\\xff\"", PrettyPrinter::new().binary(BinaryStyle::Escaped).print(&binary));
+        assert_eq!("b\"\\x8a\"… (4 bytes)", PrettyPrinter::new().binary(BinaryStyle::Escaped).max_string_len(1).print(&binary));
+        assert_eq!("hex\"8a00\"… (4 bytes)", PrettyPrinter::new().max_string_len(2).print(&binary));
+        // never cuts a char in half
+        assert_eq!("\"a\"… (3 bytes)", PrettyPrinter::new().max_string_len(2).print(&bencode!("aü")));
+        assert_eq!("\"a\\\"\\n\"", PrettyPrinter::new().print(&bencode!("a\"\n")));
+    }
+
+    #[test]
+    fn summary() {
+        let item = bencode!({"name": "aü", "files": [{"length": 1}], "pieces": ByteString::new(vec!(0x8A; 100))});
+        let summary = format!("{:?}", item.summary().max_string_len(2));
+        assert!(summary.starts_with("{\"name\": \"a\"…(3 bytes"), "{}", summary);
+        #[cfg(not(feature = "sha1"))]
+        assert_eq!("{\"name\": \"a\"…(3 bytes), \"files\": [{\"length\": 1}], \"pieces\": b\"\\x8a\\x8a\"…(100 bytes)}", summary);
+        #[cfg(feature = "sha1")]
+        assert!(summary.ends_with("\"pieces\": b\"\\x8a\\x8a\"…(100 bytes, sha1=447efd08…)}"), "{}", summary);
+        assert_eq!("[\n    \"x\",\n    1,\n]", format!("{:#?}", bencode!(["x", 1]).summary()));
+    }
+
+    #[test]
+    fn json() {
+        let item = bencode!({"name": "a\"b\\\n\u{1}ü", "pieces": ByteString::new(vec!(0x8A, 0xFF)), "files": [1, -2, []], "x": {}});
+        assert_eq!(
+            "{\"name\":\"a\\\"b\\\\\\n\\u0001ü\",\"pieces\":\"8aff\",\"files\":[1,-2,[]],\"x\":{}}",
+            format!("{:#}", item)
+        );
+    }
+}
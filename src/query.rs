@@ -0,0 +1,383 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::{BencodeError, BencodeItem, PathSegment};
+
+// A compiled query over a tree, returning every match along with its path:
+//
+//     info.files[*].length          every file's length
+//     announce-list[*][0]           the first tracker of each tier
+//     info.files[?length > 1000]    files bigger than 1000 bytes
+//     "piece layers".*              every value of a dict
+//
+// Steps are dict keys separated by '.', then any number of brackets: [N] for a list index,
+// [*] for every child, [?field op literal] to keep the children matching a condition
+// (op is one of == != < <= > >=, the literal an int or a "string") and [?field] for the
+// ones that have `field` at all. A filter's field can itself be dotted (path.0). Keys that
+// contain .[]=!<> or start with a quote go in double quotes.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    source: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryMatch<'a> {
+    pub path: Vec<PathSegment<'a>>,
+    pub item: &'a BencodeItem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    Any,
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Filter {
+    field: Vec<String>,
+    condition: Option<(Op, Literal)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Int(i64),
+    Str(String),
+}
+
+impl Query {
+    pub fn compile(source: &str) -> Result<Query, BencodeError> {
+        let steps = Parser { chars: source.char_indices().peekable(), len: source.len() }.steps()?;
+        Ok(Query { source: String::from(source), steps })
+    }
+
+    pub fn matches<'a>(&self, item: &'a BencodeItem) -> Vec<QueryMatch<'a>> {
+        let mut frontier = vec!(QueryMatch { path: vec!(), item });
+        for step in &self.steps {
+            let mut next = vec!();
+            for m in frontier {
+                for (segment, child) in children(m.item) {
+                    let keep = match step {
+                        Step::Key(k) => segment == PathSegment::Key(k),
+                        Step::Index(i) => segment == PathSegment::Index(*i),
+                        Step::Any => true,
+                        Step::Filter(f) => f.test(child),
+                    };
+                    if keep {
+                        let mut path = m.path.clone();
+                        path.push(segment);
+                        next.push(QueryMatch { path, item: child });
+                    }
+                }
+            }
+            frontier = next;
+        }
+        frontier
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl BencodeItem {
+    pub fn query(&self, query: &str) -> Result<Vec<QueryMatch<'_>>, BencodeError> {
+        Ok(Query::compile(query)?.matches(self))
+    }
+}
+
+fn children(item: &BencodeItem) -> Vec<(PathSegment<'_>, &BencodeItem)> {
+    match item {
+        BencodeItem::List(l) => l.iter().enumerate().map(|(i, v)| (PathSegment::Index(i), v)).collect(),
+        BencodeItem::Dict(d) => d.iter().map(|(k, v)| (PathSegment::Key(k), v)).collect(),
+        _ => vec!(),
+    }
+}
+
+impl Filter {
+    fn test(&self, item: &BencodeItem) -> bool {
+        let target = self.field.iter().try_fold(item, |item, key| match item {
+            BencodeItem::List(l) => l.get(key.parse::<usize>().ok()?),
+            _ => item.get(key),
+        });
+        match (target, &self.condition) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(target), Some((op, literal))) => {
+                let ordering = match (target, literal) {
+                    (BencodeItem::Int(i), Literal::Int(l)) => i.cmp(l),
+                    (BencodeItem::String(s), Literal::Str(l)) => s.bytes.as_slice().cmp(l.as_bytes()),
+                    _ => return false,
+                };
+                match op {
+                    Op::Eq => ordering == Ordering::Equal,
+                    Op::Ne => ordering != Ordering::Equal,
+                    Op::Lt => ordering == Ordering::Less,
+                    Op::Le => ordering != Ordering::Greater,
+                    Op::Gt => ordering == Ordering::Greater,
+                    Op::Ge => ordering != Ordering::Less,
+                }
+            }
+        }
+    }
+}
+
+fn query_error(pos: usize, msg: &str) -> BencodeError {
+    BencodeError::QueryParse(format!("at {}: {}", pos, msg))
+}
+
+const RESERVED: &[char] = &['.', '[', ']', '=', '!', '<', '>'];
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    len: usize,
+}
+
+impl Parser<'_> {
+    fn pos(&mut self) -> usize {
+        let len = self.len;
+        self.chars.peek().map(|&(pos, _)| pos).unwrap_or(len)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.chars.next();
+            return true;
+        }
+        false
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), BencodeError> {
+        let pos = self.pos();
+        match self.chars.next() {
+            Some((_, next)) if next == c => Ok(()),
+            Some((_, next)) => Err(query_error(pos, &format!("expected '{}', found '{}'", c, next))),
+            None => Err(query_error(pos, &format!("expected '{}'", c))),
+        }
+    }
+
+    fn steps(mut self) -> Result<Vec<Step>, BencodeError> {
+        let mut steps = vec!();
+        // a leading bracket applies to the root, as in "[0].x"
+        let mut expect_key = self.peek().is_some() && self.peek() != Some('[');
+        loop {
+            if expect_key {
+                if self.eat('*') {
+                    steps.push(Step::Any);
+                } else {
+                    steps.push(Step::Key(self.key()?));
+                }
+            }
+            while self.eat('[') {
+                steps.push(self.bracket()?);
+            }
+            let pos = self.pos();
+            match self.chars.next() {
+                None => return Ok(steps),
+                Some((_, '.')) => expect_key = true,
+                Some((_, c)) => return Err(query_error(pos, &format!("unexpected '{}'", c))),
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<String, BencodeError> {
+        let pos = self.pos();
+        if self.peek() == Some('"') {
+            return self.quoted()
+        }
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            if RESERVED.contains(&c) {
+                break;
+            }
+            key.push(c);
+            self.chars.next();
+        }
+        if key.is_empty() {
+            return Err(query_error(pos, "expected a key"))
+        }
+        Ok(key)
+    }
+
+    fn quoted(&mut self) -> Result<String, BencodeError> {
+        let pos = self.pos();
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, c)) => s.push(c),
+                    None => break,
+                },
+                Some((_, c)) => s.push(c),
+                None => break,
+            }
+        }
+        Err(query_error(pos, "unterminated string"))
+    }
+
+    fn bracket(&mut self) -> Result<Step, BencodeError> {
+        self.skip_whitespace();
+        let pos = self.pos();
+        let step = if self.eat('*') {
+            Step::Any
+        } else if self.eat('?') {
+            Step::Filter(self.filter()?)
+        } else {
+            let mut digits = String::new();
+            while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+                digits.push(c);
+                self.chars.next();
+            }
+            Step::Index(digits.parse().map_err(|_| query_error(pos, "expected an index, '*' or a '?' filter"))?)
+        };
+        self.skip_whitespace();
+        self.expect(']')?;
+        Ok(step)
+    }
+
+    fn filter(&mut self) -> Result<Filter, BencodeError> {
+        let mut field = vec!();
+        loop {
+            self.skip_whitespace();
+            field.push(String::from(self.key()?.trim_end()));
+            if !self.eat('.') {
+                break;
+            }
+        }
+        self.skip_whitespace();
+        let pos = self.pos();
+        let op = match self.peek() {
+            Some(']') => return Ok(Filter { field, condition: None }),
+            Some('=') => {
+                self.chars.next();
+                self.expect('=')?;
+                Op::Eq
+            },
+            Some('!') => {
+                self.chars.next();
+                self.expect('=')?;
+                Op::Ne
+            },
+            Some('<') => {
+                self.chars.next();
+                if self.eat('=') { Op::Le } else { Op::Lt }
+            },
+            Some('>') => {
+                self.chars.next();
+                if self.eat('=') { Op::Ge } else { Op::Gt }
+            },
+            _ => return Err(query_error(pos, "expected a comparison or ']'")),
+        };
+        self.skip_whitespace();
+        Ok(Filter { field, condition: Some((op, self.literal()?)) })
+    }
+
+    fn literal(&mut self) -> Result<Literal, BencodeError> {
+        let pos = self.pos();
+        if self.peek() == Some('"') {
+            return Ok(Literal::Str(self.quoted()?))
+        }
+        let mut digits = String::new();
+        while let Some(c) = self.peek().filter(|c| *c == '-' || c.is_ascii_digit()) {
+            digits.push(c);
+            self.chars.next();
+        }
+        digits.parse().map(Literal::Int).map_err(|_| query_error(pos, "expected an int or a quoted string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> BencodeItem {
+        bencode!({
+            "announce-list": [["udp://a", "udp://a2"], ["udp://b"]],
+            "info": {
+                "files": [
+                    {"length": 10, "path": ["a"]},
+                    {"length": 2000, "path": ["b"]},
+                    {"length": 30, "path": ["c"], "attr": "x"},
+                ],
+                "piece length": 16384,
+            },
+        })
+    }
+
+    fn values<'a>(matches: &[QueryMatch<'a>]) -> Vec<&'a BencodeItem> {
+        matches.iter().map(|m| m.item).collect()
+    }
+
+    #[test]
+    fn wildcards() {
+        let item = sample();
+        let matches = item.query("info.files[*].length").unwrap();
+        assert_eq!(vec!(&bencode!(10), &bencode!(2000), &bencode!(30)), values(&matches));
+        assert_eq!(
+            vec!(PathSegment::Key("info"), PathSegment::Key("files"), PathSegment::Index(1), PathSegment::Key("length")),
+            matches[1].path
+        );
+        assert_eq!(Some(matches[1].item), item.get_segments(&matches[1].path));
+
+        assert_eq!(vec!(&bencode!("udp://a"), &bencode!("udp://b")), values(&item.query("announce-list[*][0]").unwrap()));
+        assert_eq!(vec!(&bencode!(16384)), values(&item.query("info.piece length").unwrap()));
+        assert_eq!(2, item.query("info.*").unwrap().len());
+        assert_eq!(vec!(&item), values(&item.query("").unwrap()));
+        assert!(item.query("info.missing[*]").unwrap().is_empty());
+        assert!(item.query("info.files[7]").unwrap().is_empty());
+        assert_eq!(vec!(&bencode!("udp://b")), values(&bencode!(["udp://b"]).query("[0]").unwrap()));
+    }
+
+    #[test]
+    fn filters() {
+        let item = sample();
+        let paths = |q| values(&item.query(q).unwrap()).iter().map(|m| m["path"][0].clone()).collect::<Vec<_>>();
+        assert_eq!(vec!(bencode!("b")), paths("info.files[?length > 1000]"));
+        assert_eq!(vec!(bencode!("a"), bencode!("c")), paths("info.files[? length <= 30 ]"));
+        assert_eq!(vec!(bencode!("c")), paths("info.files[?attr]"));
+        assert_eq!(vec!(bencode!("c")), paths("info.files[?attr == \"x\"]"));
+        assert_eq!(vec!(bencode!("a"), bencode!("b")), paths("info.files[?length != 30]"));
+        // type mismatches never match
+        assert!(paths("info.files[?length == \"10\"]").is_empty());
+        assert_eq!(vec!(&bencode!(10)), values(&item.query("info.files[?path.0 == \"a\"].length").unwrap()));
+    }
+
+    #[test]
+    fn compile_errors() {
+        assert_eq!(Err(BencodeError::QueryParse(String::from("at 5: expected a key"))), Query::compile("info..x"));
+        assert_eq!(Err(BencodeError::QueryParse(String::from("at 6: expected an index, '*' or a '?' filter"))), Query::compile("files[x]"));
+        assert_eq!(Err(BencodeError::QueryParse(String::from("at 7: expected ']'"))), Query::compile("files[0"));
+        assert_eq!(Err(BencodeError::QueryParse(String::from("at 12: expected an int or a quoted string"))), Query::compile("files[?a == b]"));
+        assert_eq!(Err(BencodeError::QueryParse(String::from("at 5: unexpected ']'"))), Query::compile("files]"));
+        assert_eq!("info.files[*]", Query::compile("info.files[*]").unwrap().to_string());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::c;
+use crate::tokenizer::{TokenKind, Tokenizer};
+
+// For consumers of many concatenated messages: after a message at the front of `bytes`
+// fails to parse, the offset just past it, where the next message plausibly starts.
+//
+// The message's structure is followed token by token, and tokens that don't parse are
+// stepped over the way they were probably meant: an int up to its 'e', a string by its
+// declared length even with leading zeros, and anything else a byte at a time. A stray
+// 'e' at the top level ends the message. None means the input ran out first, so either
+// the message isn't all there yet or there's nothing after it to resume at.
+//
+//     let mut start = 0;
+//     while start < bytes.len() {
+//         let mut iter = bytes[start..].iter().peekable();
+//         match parse_bytes(&mut iter) {
+//             Ok(item) => { handle(item); start = bytes.len() - iter.len(); },
+//             Err(_) => match resume_position(&bytes[start..]) {
+//                 Some(n) => start += n,
+//                 None => break,
+//             },
+//         }
+//     }
+pub fn resume_position(bytes: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    let mut depth = 0usize;
+    loop {
+        let mut tokenizer = Tokenizer::new(&bytes[pos..]);
+        match tokenizer.next_token() {
+            Ok(Some(token)) => {
+                pos += token.span.end;
+                match token.kind {
+                    TokenKind::DictStart | TokenKind::ListStart => depth += 1,
+                    TokenKind::End if depth == 0 => return Some(pos),
+                    TokenKind::End => depth -= 1,
+                    TokenKind::Int(_) | TokenKind::String(_) => (),
+                }
+            },
+            Ok(None) => return None,
+            Err(_) => pos += skip_malformed(&bytes[pos..])?,
+        }
+        if depth == 0 {
+            return Some(pos)
+        }
+    }
+}
+
+// how many bytes a token that didn't parse probably takes up
+fn skip_malformed(bytes: &[u8]) -> Option<usize> {
+    match bytes.first()? {
+        &c::M_INT => bytes.iter().position(|&b| b == c::M_END).map(|i| i + 1),
+        c::M_0..=c::M_9 => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+            if bytes.get(digits) != Some(&c::M_COLON) {
+                // not a length after all; what follows is garbage of its own
+                return Some(digits)
+            }
+            let len = std::str::from_utf8(&bytes[..digits]).ok()?.parse::<usize>().ok()?;
+            let end = (digits + 1).checked_add(len)?;
+            (end <= bytes.len()).then_some(end)
+        },
+        _ => Some(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_bytes;
+
+    #[test]
+    fn resume() {
+        // well-formed values end where they end
+        assert_eq!(Some(8), resume_position(b"d1:ai1eei2e"));
+        assert_eq!(Some(3), resume_position(b"i1ei2e"));
+
+        assert_eq!(Some(10), resume_position(b"li1x0e1:aei2e"));
+        assert_eq!(Some(15), resume_position(b"d1:a03:abc1:bxei2e"));
+        assert_eq!(Some(1), resume_position(b"ei1e"));
+        assert_eq!(Some(5), resume_position(b"l1x:ei1e"));
+        assert_eq!(None, resume_position(b"li1e"));
+        assert_eq!(None, resume_position(b"l5:abc"));
+        assert_eq!(None, resume_position(b""));
+    }
+
+    #[test]
+    fn skip_and_continue() {
+        let bytes = b"d1:ai1ee d1:ai0x1ee li2ei3ee";
+        let mut items = vec!();
+        let mut errors = 0;
+        let mut start = 0;
+        while start < bytes.len() {
+            let mut iter = bytes[start..].iter().peekable();
+            match parse_bytes(&mut iter) {
+                Ok(item) => {
+                    items.push(item);
+                    start = bytes.len() - iter.len();
+                },
+                Err(_) => {
+                    errors += 1;
+                    match resume_position(&bytes[start..]) {
+                        Some(n) => start += n,
+                        None => break,
+                    }
+                },
+            }
+        }
+        assert_eq!(vec!(bencode!({"a": 1}), bencode!([2, 3])), items);
+        // each space is an unrecognized byte of its own
+        assert_eq!(3, errors);
+    }
+}
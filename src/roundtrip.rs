@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+
+use crate::{AsBencodeBytes, BencodeError, BencodeItem};
+use crate::decoder;
+
+// Reasons re-encoding a decoded document may not reproduce it. Paths are slash-separated
+// keys and list indices leading to the offending dict, "" being the root.
+#[derive(Debug, PartialEq)]
+pub enum RoundTripIssue {
+    UnsortedKeys { path: String },
+    DuplicateKey { path: String, key: String },
+    TrailingData { len: usize },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RoundTripReport {
+    pub identical: bool,
+    // offset of the first byte where the re-encoding differs from the input
+    pub first_difference: Option<usize>,
+    pub issues: Vec<RoundTripIssue>,
+}
+
+pub fn verify_roundtrip(bytes: &[u8]) -> Result<RoundTripReport, BencodeError> {
+    let mut bytes_iter = bytes.iter().peekable();
+    let item = decoder::parse_bytes(&mut bytes_iter)?;
+    let trailing = bytes_iter.len();
+
+    let mut issues = vec!();
+    collect_issues(&item, &mut issues);
+    if trailing > 0 {
+        issues.push(RoundTripIssue::TrailingData { len: trailing });
+    }
+
+    let encoded = item.as_bytes();
+    let first_difference = if encoded == bytes {
+        None
+    } else {
+        Some(encoded.iter().zip(bytes).position(|(a, b)| a != b).unwrap_or(encoded.len().min(bytes.len())))
+    };
+    Ok(RoundTripReport { identical: first_difference.is_none(), first_difference, issues })
+}
+
+// Uses an explicit stack like the encoder, so deep trees don't overflow. Each entry keeps the
+// length of its parent's path: everything popped before it only grew the path past that point.
+fn collect_issues(item: &BencodeItem, issues: &mut Vec<RoundTripIssue>) {
+    let mut path = String::new();
+    let mut stack: Vec<(&BencodeItem, usize, Option<String>)> = vec!((item, 0, None));
+    while let Some((item, parent_len, segment)) = stack.pop() {
+        path.truncate(parent_len);
+        if let Some(segment) = segment {
+            if parent_len > 0 {
+                path.push('/');
+            }
+            path.push_str(&segment);
+        }
+        match item {
+            BencodeItem::List(l) => {
+                for (i, child) in l.iter().enumerate().rev() {
+                    stack.push((child, path.len(), Some(i.to_string())));
+                }
+            },
+            BencodeItem::Dict(d) => {
+                if d.as_slice().windows(2).any(|w| w[0].0.as_bytes() > w[1].0.as_bytes()) {
+                    issues.push(RoundTripIssue::UnsortedKeys { path: path.clone() });
+                }
+                let mut seen = HashSet::new();
+                let mut reported = HashSet::new();
+                for (key, _) in d {
+                    if !seen.insert(key.as_str()) && reported.insert(key.as_str()) {
+                        issues.push(RoundTripIssue::DuplicateKey { path: path.clone(), key: key.clone() });
+                    }
+                }
+                for (key, value) in d.iter().rev() {
+                    stack.push((value, path.len(), Some(key.clone())));
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical() {
+        assert_eq!(
+            Ok(RoundTripReport { identical: true, first_difference: None, issues: vec!() }),
+            verify_roundtrip(b"d1:ai1e1:bld1:xdeeee")
+        );
+    }
+
+    #[test]
+    fn unsorted_and_duplicates() {
+        let report = verify_roundtrip(b"d1:bi1e1:ald1:yi1e1:xi2e1:xi3e1:xi4eeee").unwrap();
+        // decoding keeps order and duplicates, so these still encode back identically
+        assert!(report.identical);
+        assert_eq!(vec!(
+            RoundTripIssue::UnsortedKeys { path: String::new() },
+            RoundTripIssue::UnsortedKeys { path: String::from("a/0") },
+            RoundTripIssue::DuplicateKey { path: String::from("a/0"), key: String::from("x") },
+        ), report.issues);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 200_000;
+        let mut dict = crate::BencodeDict::new();
+        dict.push(String::from("b"), BencodeItem::Int(1));
+        dict.push(String::from("a"), BencodeItem::Int(2));
+        let mut item = BencodeItem::Dict(dict);
+        for _ in 0..depth {
+            item = BencodeItem::List(vec!(item));
+        }
+        let mut issues = vec!();
+        collect_issues(&item, &mut issues);
+        assert_eq!(vec!(RoundTripIssue::UnsortedKeys { path: vec!("0"; depth).join("/") }), issues);
+
+        // dropping is recursive, so take the tree apart by hand
+        while let BencodeItem::List(mut l) = item {
+            item = l.pop().unwrap();
+        }
+    }
+
+    #[test]
+    fn trailing_data() {
+        let report = verify_roundtrip(b"i1eXX").unwrap();
+        assert!(!report.identical);
+        assert_eq!(Some(3), report.first_difference);
+        assert_eq!(vec!(RoundTripIssue::TrailingData { len: 2 }), report.issues);
+
+        assert_eq!(Err(BencodeError::IntParseLeadingZero), verify_roundtrip(b"i01e"));
+    }
+}
@@ -0,0 +1,454 @@
+use std::fmt::Display;
+
+use serde::ser::{self, Serialize};
+
+use crate::c::HEX;
+use crate::{encode_with_options, BencodeDict, BencodeError, BencodeItem, ByteString, EncodeOptions};
+
+// Serde data format, writing side. Values are built into a BencodeItem first and then
+// encoded with sorted keys, since struct fields come in declaration order.
+//
+//     bool                     0 or 1
+//     integers                 int; u64 above i64::MAX is an error
+//     floats                   error, bencode has none
+//     char, str                string
+//     bytes                    string (serde_bytes, or a serialize_bytes impl)
+//     Some(x)                  x
+//     None                     left out of structs and maps, an error elsewhere
+//     unit, unit struct        empty list
+//     newtype struct           the inner value
+//     seq, tuple               list
+//     map, struct              dict; map keys must serialize as strings or ints
+//     enum variants            externally tagged: "Unit", {"Newtype": x}, {"Tuple": [..]},
+//                              {"Struct": {..}}
+//
+// The other enum representations are serde's own and work as usual: #[serde(tag = "y")]
+// puts the tag inside a struct variant's dict (KRPC messages, say), #[serde(tag, content)]
+// writes {"tag": .., "content": ..}, and #[serde(untagged)] tries each variant in turn.
+// Those, like #[serde(flatten)], buffer the value first, and a buffered 0 or 1 is just an
+// int, so a bool anywhere inside them fails to read; use an integer field there.
+
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, BencodeError> {
+    encode_with_options(&to_item(value)?, &EncodeOptions::lenient().sort_keys(true))
+}
+
+pub fn to_item<T: Serialize + ?Sized>(value: &T) -> Result<BencodeItem, BencodeError> {
+    required(value)
+}
+
+impl ser::Error for BencodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BencodeError::Serde(msg.to_string())
+    }
+}
+
+// Items can also go into other formats, e.g. as part of a JSON config. Binary ones get
+// raw byte strings; human-readable ones get non-UTF-8 strings as "hex:.." text, with text
+// that already starts with "hex:" tagged the same way so that it reads back unchanged.
+impl Serialize for BencodeItem {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BencodeItem::Int(i) => serializer.serialize_i64(*i),
+            BencodeItem::String(s) => match s.as_utf8() {
+                Some(text) if !(serializer.is_human_readable() && text.starts_with(HEX)) => serializer.serialize_str(text),
+                _ if serializer.is_human_readable() => serializer.serialize_str(&format!("{}{}", HEX, s.to_hex())),
+                _ => serializer.serialize_bytes(&s.bytes),
+            },
+            BencodeItem::List(l) => serializer.collect_seq(l),
+            BencodeItem::Dict(d) => serializer.collect_map(d.iter().map(|(k, v)| (k, v))),
+        }
+    }
+}
+
+// always as bytes, like serde_bytes::ByteBuf, so formats with a byte type use it
+impl Serialize for ByteString {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.bytes)
+    }
+}
+
+// For looking at a tree in a friendlier form than the text format; binary strings come
+// out as "hex:.." like in any other human-readable format.
+impl BencodeItem {
+    // TOML documents are tables, so this fails unless the item is a dict
+    #[cfg(feature = "toml")]
+    pub fn to_toml_string(&self) -> Result<String, BencodeError> {
+        toml::to_string_pretty(self).map_err(|e| BencodeError::Serde(e.to_string()))
+    }
+}
+
+fn required<T: Serialize + ?Sized>(value: &T) -> Result<BencodeItem, BencodeError> {
+    value.serialize(ItemSerializer)?
+        .ok_or(BencodeError::Serde(String::from("None can only be left out of a struct or map")))
+}
+
+fn key<T: Serialize + ?Sized>(key: &T) -> Result<String, BencodeError> {
+    match required(key)? {
+        BencodeItem::String(s) => String::from_utf8(s.bytes)
+            .map_err(|_| BencodeError::Serde(String::from("dict keys must be UTF-8"))),
+        BencodeItem::Int(i) => Ok(i.to_string()),
+        other => Err(BencodeError::Serde(format!("dict keys must be strings, found {}", other.type_name()))),
+    }
+}
+
+fn tagged(variant: &str, value: BencodeItem) -> BencodeItem {
+    let mut d = BencodeDict::with_capacity(1);
+    d.push(variant, value);
+    BencodeItem::Dict(d)
+}
+
+// Ok is None for a serialized Option::None
+struct ItemSerializer;
+
+impl ser::Serializer for ItemSerializer {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = VariantSerializer<MapSerializer>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(BencodeItem::Int(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, BencodeError> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Err(BencodeError::ValueOutOfRange(format!("{} doesn't fit a bencode int", v))),
+        }
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Self::Ok, BencodeError> {
+        Err(BencodeError::Serde(String::from("bencode has no floats")))
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Self::Ok, BencodeError> {
+        Err(BencodeError::Serde(String::from("bencode has no floats")))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, BencodeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(BencodeItem::String(ByteString::from(v))))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(BencodeItem::String(ByteString::from(v))))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, BencodeError> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, BencodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(BencodeItem::List(vec!())))
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, BencodeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> Result<Self::Ok, BencodeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> Result<Self::Ok, BencodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self, _: &'static str, _: u32, variant: &'static str, value: &T
+    ) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(tagged(variant, required(value)?)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, BencodeError> {
+        Ok(SeqSerializer { items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, BencodeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<SeqSerializer, BencodeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self, _: &'static str, _: u32, variant: &'static str, len: usize
+    ) -> Result<VariantSerializer<SeqSerializer>, BencodeError> {
+        Ok(VariantSerializer { variant, inner: self.serialize_seq(Some(len))? })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, BencodeError> {
+        Ok(MapSerializer { dict: BencodeDict::with_capacity(len.unwrap_or(0)), key: None })
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<MapSerializer, BencodeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self, _: &'static str, _: u32, variant: &'static str, len: usize
+    ) -> Result<VariantSerializer<MapSerializer>, BencodeError> {
+        Ok(VariantSerializer { variant, inner: self.serialize_map(Some(len))? })
+    }
+}
+
+struct SeqSerializer {
+    items: Vec<BencodeItem>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        self.items.push(required(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(BencodeItem::List(self.items)))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, BencodeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, BencodeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct MapSerializer {
+    dict: BencodeDict,
+    // between serialize_key and serialize_value
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, k: &T) -> Result<(), BencodeError> {
+        self.key = Some(key(k)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        let key = self.key.take().expect("serialize_value before serialize_key");
+        if let Some(value) = value.serialize(ItemSerializer)? {
+            self.dict.push(key, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(BencodeItem::Dict(self.dict)))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, k: &'static str, value: &T) -> Result<(), BencodeError> {
+        if let Some(value) = value.serialize(ItemSerializer)? {
+            self.dict.push(k, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, BencodeError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+// the variant's contents, wrapped in a one-entry dict under its name at the end
+struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(tagged(self.variant, BencodeItem::List(self.inner.items))))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<MapSerializer> {
+    type Ok = Option<BencodeItem>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, k: &'static str, value: &T) -> Result<(), BencodeError> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, k, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, BencodeError> {
+        Ok(Some(tagged(self.variant, BencodeItem::Dict(self.inner.dict))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Info {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: u32,
+        private: bool,
+        comment: Option<String>,
+        files: Vec<(u8, char)>,
+    }
+
+    #[derive(Serialize)]
+    enum Message {
+        Ping,
+        Have(u32),
+        Request(u32, u32),
+        Cancel { index: u32 },
+    }
+
+    #[test]
+    fn to_bytes() {
+        let info = Info { name: String::from("a"), piece_length: 16384, private: true, comment: None, files: vec!((1, 'x')) };
+        assert_eq!(
+            b"d5:fileslli1e1:xee4:name1:a12:piece lengthi16384e7:privatei1ee".to_vec(),
+            super::to_bytes(&info).unwrap()
+        );
+        assert_eq!(
+            Ok(bencode!({"name": "a", "piece length": 16384, "private": 1, "comment": "c", "files": []})),
+            to_item(&Info { comment: Some(String::from("c")), files: vec!(), ..info })
+        );
+    }
+
+    #[test]
+    fn enums() {
+        assert_eq!(Ok(bencode!("Ping")), to_item(&Message::Ping));
+        assert_eq!(Ok(bencode!({"Have": 3})), to_item(&Message::Have(3)));
+        assert_eq!(Ok(bencode!({"Request": [1, 2]})), to_item(&Message::Request(1, 2)));
+        assert_eq!(Ok(bencode!({"Cancel": {"index": 4}})), to_item(&Message::Cancel { index: 4 }));
+    }
+
+    #[test]
+    fn items() {
+        let item = bencode!({"b": [1, "x"], "a": ByteString::new(vec!(0xFF))});
+        assert_eq!(Ok(item.clone()), to_item(&item));
+        assert_eq!(Ok(b"d1:a1:\xFF1:bli1e1:xee".to_vec()), super::to_bytes(&item));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml() {
+        let item = bencode!({"announce": "udp://x", "info": {"length": 3, "pieces": ByteString::new(vec!(0xFF)), "path": ["a", "b"]}});
+        assert_eq!(
+            Ok(String::from("announce = \"udp://x\"\n\n[info]\nlength = 3\npieces = \"hex:ff\"\npath = [\n    \"a\",\n    \"b\",\n]\n")),
+            item.to_toml_string()
+        );
+        assert!(matches!(bencode!([1]).to_toml_string(), Err(BencodeError::Serde(_))));
+    }
+
+    #[test]
+    fn bytes() {
+        #[derive(Serialize)]
+        struct Info<'a> {
+            #[serde(with = "serde_bytes")]
+            pieces: Vec<u8>,
+            #[serde(with = "serde_bytes")]
+            root: &'a [u8],
+            hash: ByteString,
+            plain: Vec<u8>,
+        }
+
+        let info = Info { pieces: vec!(0xFF; 3), root: &[1], hash: ByteString::from("h"), plain: vec!(1) };
+        assert_eq!(
+            Ok(bencode!({"pieces": ByteString::new(vec!(0xFF; 3)), "root": ByteString::new(vec!(1)), "hash": "h", "plain": [1]})),
+            to_item(&info)
+        );
+    }
+
+    #[test]
+    fn maps_and_errors() {
+        let map: BTreeMap<u32, &str> = [(10, "b"), (9, "a")].into_iter().collect();
+        assert_eq!(b"d2:101:b1:91:ae".to_vec(), super::to_bytes(&map).unwrap());
+        assert_eq!(Ok(bencode!([])), to_item(&()));
+
+        assert_eq!(Err(BencodeError::Serde(String::from("bencode has no floats"))), to_item(&1.5));
+        assert_eq!(Err(BencodeError::ValueOutOfRange(String::from("18446744073709551615 doesn't fit a bencode int"))), to_item(&u64::MAX));
+        assert_eq!(Err(BencodeError::Serde(String::from("None can only be left out of a struct or map"))), to_item(&vec!(None::<u8>)));
+        let map: BTreeMap<(u8, u8), u8> = [((1, 2), 3)].into_iter().collect();
+        assert_eq!(Err(BencodeError::Serde(String::from("dict keys must be strings, found list"))), to_item(&map));
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use crate::BencodeItem;
+use crate::hash::{self, Fnv1a};
+
+// how deep the shape description goes before collapsing into ".."
+const SHAPE_DEPTH: usize = 3;
+// how many entries of each dict the shape lists before summing up the rest as ",..+N"
+const SHAPE_DICT_ENTRIES: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub struct Snapshot {
+    pub shape: String,
+    pub nodes: usize,
+    pub max_depth: usize,
+    pub string_bytes: usize,
+    pub encoded_len: usize,
+    pub fingerprint: u64,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "nodes={} depth={} string_bytes={} encoded_len={} fingerprint={:016x} shape={}",
+            self.nodes, self.max_depth, self.string_bytes, self.encoded_len, self.fingerprint, self.shape
+        )
+    }
+}
+
+impl BencodeItem {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot {
+            shape: shape(self, 0),
+            nodes: 0,
+            max_depth: 0,
+            string_bytes: 0,
+            encoded_len: 0,
+            fingerprint: 0,
+        };
+        collect_stats(self, &mut snapshot);
+
+        snapshot.encoded_len = self.encoded_len();
+        snapshot.fingerprint = u64::from_be_bytes(hash::digest_item(self, Fnv1a::default()));
+        snapshot
+    }
+}
+
+// with a stack of its own rather than recursion, so deep trees can't overflow ours
+fn collect_stats(item: &BencodeItem, snapshot: &mut Snapshot) {
+    let mut stack = vec!((item, 1));
+    while let Some((item, depth)) = stack.pop() {
+        snapshot.nodes += 1;
+        snapshot.max_depth = snapshot.max_depth.max(depth);
+        match item {
+            BencodeItem::String(s) => snapshot.string_bytes += s.bytes.len(),
+            BencodeItem::Int(_) => (),
+            BencodeItem::List(l) => stack.extend(l.iter().map(|child| (child, depth + 1))),
+            BencodeItem::Dict(d) => {
+                for (key, value) in d {
+                    snapshot.string_bytes += key.len();
+                    stack.push((value, depth + 1));
+                }
+            }
+        }
+    }
+}
+
+fn shape(item: &BencodeItem, depth: usize) -> String {
+    match item {
+        BencodeItem::String(s) => format!("str({})", s.bytes.len()),
+        BencodeItem::Int(_) => String::from("int"),
+        BencodeItem::List(_) | BencodeItem::Dict(_) if depth >= SHAPE_DEPTH => String::from(".."),
+        // lists are described by their first element, which is what they usually all look like
+        BencodeItem::List(l) => match l.first() {
+            Some(first) => format!("[{}x{}]", l.len(), shape(first, depth + 1)),
+            None => String::from("[]"),
+        },
+        BencodeItem::Dict(d) => {
+            let mut entries: Vec<String> = d.iter()
+                .take(SHAPE_DICT_ENTRIES)
+                .map(|(key, value)| format!("{}:{}", key, shape(value, depth + 1)))
+                .collect();
+            if d.len() > SHAPE_DICT_ENTRIES {
+                entries.push(format!("..+{}", d.len() - SHAPE_DICT_ENTRIES));
+            }
+            format!("{{{}}}", entries.join(","))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AsBencodeBytes, ByteString};
+
+    #[test]
+    fn snapshot() {
+        let item = BencodeItem::Dict(vec!(
+            (String::from("announce"), BencodeItem::String(ByteString::from("udp://x"))),
+            (String::from("info"), BencodeItem::Dict(vec!(
+                (String::from("files"), BencodeItem::List(vec!(
+                    BencodeItem::Dict(vec!((String::from("length"), BencodeItem::Int(1))).into()),
+                    BencodeItem::Dict(vec!((String::from("length"), BencodeItem::Int(2))).into()),
+                ))),
+            ).into())),
+        ).into());
+        let s = item.snapshot();
+
+        assert_eq!("{announce:str(7),info:{files:[2x..]}}", s.shape);
+        assert_eq!(8, s.nodes);
+        assert_eq!(5, s.max_depth);
+        assert_eq!(8 + 7 + 4 + 5 + 6 + 6, s.string_bytes);
+        assert_eq!(item.as_bytes().len(), s.encoded_len);
+        assert_eq!(s.fingerprint, item.snapshot().fingerprint);
+        assert_ne!(s.fingerprint, BencodeItem::Int(1).snapshot().fingerprint);
+        // FNV-1a of "i1e", the same on every build
+        assert_eq!(0x2aebf0192b3bda3e, BencodeItem::Int(1).snapshot().fingerprint);
+    }
+
+    #[test]
+    fn large_and_deep() {
+        let wide: BencodeItem = (0..20).map(|i| (format!("k{:02}", i), BencodeItem::Int(i))).collect();
+        assert_eq!("{k00:int,k01:int,k02:int,k03:int,k04:int,k05:int,k06:int,k07:int,..+12}", wide.snapshot().shape);
+
+        let mut deep = BencodeItem::Int(0);
+        for _ in 0..200_000 {
+            deep = BencodeItem::List(vec!(deep));
+        }
+        let s = deep.snapshot();
+        assert_eq!(200_001, s.nodes);
+        assert_eq!(200_001, s.max_depth);
+        assert_eq!("[1x[1x[1x..]]]", s.shape);
+        // dropping is recursive, so take the tree apart by hand
+        while let BencodeItem::List(mut l) = deep {
+            deep = l.pop().unwrap();
+        }
+    }
+}
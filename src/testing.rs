@@ -0,0 +1,74 @@
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::{AsBencodeBytes, BencodeDict, BencodeItem, ByteString, DuplicatePolicy};
+
+// proptest strategies for property tests over bencode: random trees, canonical ones, and
+// trees together with their encodings. Containers nest at most `depth` deep and hold at
+// most `width` children, so generated cases stay small enough to shrink well.
+
+pub fn ints() -> impl Strategy<Value = BencodeItem> {
+    any::<i64>().prop_map(BencodeItem::Int)
+}
+
+pub fn byte_strings() -> impl Strategy<Value = BencodeItem> {
+    vec(any::<u8>(), 0..32).prop_map(|b| BencodeItem::String(ByteString::new(b)))
+}
+
+// any tree, dict keys in any order and possibly repeated, as decoded input can be
+pub fn items() -> impl Strategy<Value = BencodeItem> {
+    items_with(4, 6)
+}
+
+pub fn items_with(depth: u32, width: usize) -> impl Strategy<Value = BencodeItem> {
+    let leaf = prop_oneof!(ints(), byte_strings());
+    leaf.prop_recursive(depth, depth * width as u32, width as u32, move |inner| prop_oneof!(
+        vec(inner.clone(), 0..width).prop_map(BencodeItem::List),
+        vec((".{0,8}", inner), 0..width).prop_map(|entries| BencodeItem::Dict(BencodeDict::from(entries))),
+    ))
+}
+
+// trees whose dicts have sorted, unique keys, i.e. what a strict encoder writes
+pub fn canonical_items() -> impl Strategy<Value = BencodeItem> {
+    items().prop_map(|mut item| {
+        item.dedup_keys(DuplicatePolicy::KeepFirst).unwrap();
+        item.sort_keys();
+        item
+    })
+}
+
+// a tree and the bytes it encodes to, for testing decoders
+pub fn encoded_items() -> impl Strategy<Value = (BencodeItem, Vec<u8>)> {
+    items().prop_map(|item| {
+        let bytes = item.as_bytes();
+        (item, bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_with_options, parse_bytes, EncodeOptions};
+
+    proptest! {
+        #[test]
+        fn round_trip((item, bytes) in encoded_items()) {
+            prop_assert_eq!(Ok(item), parse_bytes(&mut bytes.iter().peekable()));
+        }
+
+        #[test]
+        fn canonical_is_stable(item in canonical_items()) {
+            let bytes = encode_with_options(&item, &EncodeOptions::strict()).unwrap();
+            prop_assert_eq!(&item.as_bytes(), &bytes);
+            let mut again = item.clone();
+            again.sort_keys();
+            prop_assert_eq!(item, again);
+        }
+
+        #[test]
+        fn bounded(item in items_with(2, 3)) {
+            prop_assert!(item.walk().all(|(path, _)| path.len() <= 2));
+            prop_assert!(item.walk().all(|(_, node)| node.as_list().map_or(0, |l| l.len()) < 3));
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::{BencodeDict, BencodeError, BencodeItem, ByteString};
+
+// A lossless, editable text form of a tree:
+//
+//     {
+//       "announce": "udp://tracker",
+//       "info": {
+//         "length": 123,
+//         "pieces": hex"8a0f...",
+//         "files": []
+//       }
+//     }
+//
+// Strings that are valid UTF-8 are quoted with escapes (\" \\ \n \r \t \u{..}),
+// anything else is written as hex"..".
+
+const INDENT: &str = "  ";
+
+impl BencodeItem {
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        write_text(self, 0, &mut out);
+        out
+    }
+}
+
+pub fn from_text(text: &str) -> Result<BencodeItem, BencodeError> {
+    let mut parser = Parser { chars: text.char_indices().peekable() };
+    let item = parser.item()?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(item),
+        Some((pos, c)) => Err(text_error(pos, &format!("unexpected '{}' after the document", c))),
+    }
+}
+
+fn write_text(item: &BencodeItem, depth: usize, out: &mut String) {
+    match item {
+        BencodeItem::Int(i) => write!(out, "{}", i).unwrap(),
+        BencodeItem::String(s) => write_string(&s.bytes, out),
+        BencodeItem::List(l) if l.is_empty() => out.push_str("[]"),
+        BencodeItem::Dict(d) if d.is_empty() => out.push_str("{}"),
+        BencodeItem::List(l) => {
+            out.push_str("[\n");
+            for (i, child) in l.iter().enumerate() {
+                out.push_str(&INDENT.repeat(depth + 1));
+                write_text(child, depth + 1, out);
+                out.push_str(if i + 1 < l.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&INDENT.repeat(depth));
+            out.push(']');
+        },
+        BencodeItem::Dict(d) => {
+            out.push_str("{\n");
+            for (i, (key, value)) in d.iter().enumerate() {
+                out.push_str(&INDENT.repeat(depth + 1));
+                write_string(key.as_bytes(), out);
+                out.push_str(": ");
+                write_text(value, depth + 1, out);
+                out.push_str(if i + 1 < d.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&INDENT.repeat(depth));
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(bytes: &[u8], out: &mut String) {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => {
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        },
+        Err(_) => {
+            out.push_str("hex\"");
+            for b in bytes {
+                write!(out, "{:02x}", b).unwrap();
+            }
+            out.push('"');
+        }
+    }
+}
+
+fn text_error(pos: usize, msg: &str) -> BencodeError {
+    BencodeError::TextParse(format!("at {}: {}", pos, msg))
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn next_char(&mut self) -> Result<(usize, char), BencodeError> {
+        self.chars.next().ok_or(BencodeError::TextParse(String::from("unexpected end of text")))
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), BencodeError> {
+        self.skip_whitespace();
+        self.take(expected)
+    }
+
+    // like expect, but within a token, where whitespace isn't allowed
+    fn take(&mut self, expected: char) -> Result<(), BencodeError> {
+        match self.next_char()? {
+            (_, c) if c == expected => Ok(()),
+            (pos, c) => Err(text_error(pos, &format!("expected '{}', found '{}'", expected, c))),
+        }
+    }
+
+    // true if the next non-whitespace char is `c`, consuming it
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if let Some(&(_, next)) = self.chars.peek() {
+            if next == c {
+                self.chars.next();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn item(&mut self) -> Result<BencodeItem, BencodeError> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some(&(_, '[')) => {
+                self.chars.next();
+                let mut l = vec!();
+                while !self.eat(']') {
+                    l.push(self.item()?);
+                    if !self.eat(',') {
+                        self.expect(']')?;
+                        break;
+                    }
+                }
+                Ok(BencodeItem::List(l))
+            },
+            Some(&(_, '{')) => {
+                self.chars.next();
+                let mut d = BencodeDict::new();
+                while !self.eat('}') {
+                    self.skip_whitespace();
+                    let key_pos = self.chars.peek().map(|&(pos, _)| pos).unwrap_or(0);
+                    let key = match self.item()? {
+                        BencodeItem::String(s) => String::try_from(&s)
+                            .map_err(|_| text_error(key_pos, "dict keys must be UTF-8"))?,
+                        _ => return Err(text_error(key_pos, "dict keys must be strings")),
+                    };
+                    self.expect(':')?;
+                    d.push(key, self.item()?);
+                    if !self.eat(',') {
+                        self.expect('}')?;
+                        break;
+                    }
+                }
+                Ok(BencodeItem::Dict(d))
+            },
+            Some(&(_, '"')) => Ok(BencodeItem::String(ByteString::from(self.quoted()?))),
+            Some(&(_, 'h')) => {
+                for expected in ['h', 'e', 'x'] {
+                    self.take(expected)?;
+                }
+                self.hex()
+            },
+            Some(&(_, c)) if c == '-' || c.is_ascii_digit() => self.int(),
+            Some(&(pos, c)) => Err(text_error(pos, &format!("unexpected '{}'", c))),
+            None => Err(BencodeError::TextParse(String::from("unexpected end of text"))),
+        }
+    }
+
+    fn int(&mut self) -> Result<BencodeItem, BencodeError> {
+        let (start, first) = self.next_char()?;
+        let mut digits = String::from(first);
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.chars.next();
+        }
+        digits.parse::<i64>()
+            .map(BencodeItem::Int)
+            .map_err(|e| text_error(start, &format!("invalid int {}: {}", digits, e)))
+    }
+
+    fn quoted(&mut self) -> Result<String, BencodeError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next_char()? {
+                (_, '"') => return Ok(s),
+                (pos, '\\') => match self.next_char()? {
+                    (_, '"') => s.push('"'),
+                    (_, '\\') => s.push('\\'),
+                    (_, 'n') => s.push('\n'),
+                    (_, 'r') => s.push('\r'),
+                    (_, 't') => s.push('\t'),
+                    (_, 'u') => {
+                        self.take('{')?;
+                        let mut code = String::new();
+                        loop {
+                            match self.next_char()? {
+                                (_, '}') => break,
+                                (_, c) => code.push(c),
+                            }
+                        }
+                        // from_str_radix would also take a sign
+                        let hex = !code.is_empty() && code.chars().all(|c| c.is_ascii_hexdigit());
+                        match u32::from_str_radix(&code, 16).ok().filter(|_| hex).and_then(char::from_u32) {
+                            Some(c) => s.push(c),
+                            None => return Err(text_error(pos, &format!("invalid escape \\u{{{}}}", code))),
+                        }
+                    },
+                    (_, c) => return Err(text_error(pos, &format!("invalid escape \\{}", c))),
+                },
+                (_, c) => s.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<BencodeItem, BencodeError> {
+        self.take('"')?;
+        let mut bytes = vec!();
+        loop {
+            let (pos, hi) = self.next_char()?;
+            if hi == '"' {
+                return Ok(BencodeItem::String(ByteString::new(bytes)));
+            }
+            let (_, lo) = self.next_char()?;
+            match (hi.to_digit(16), lo.to_digit(16)) {
+                (Some(hi), Some(lo)) => bytes.push((hi * 16 + lo) as u8),
+                _ => return Err(text_error(pos, &format!("invalid hex byte {}{}", hi, lo))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_text() {
+        let item = bencode!({
+            "announce": "udp://x",
+            "info": {"pieces": ByteString::new(vec!(0x8A, 0x00)), "files": [], "meta": {}},
+            "list": [1, -2, "a\"b\n"],
+        });
+        assert_eq!(
+            "{\n  \"announce\": \"udp://x\",\n  \"info\": {\n    \"pieces\": hex\"8a00\",\n    \"files\": [],\n    \"meta\": {}\n  },\n  \"list\": [\n    1,\n    -2,\n    \"a\\\"b\\n\"\n  ]\n}",
+            item.to_text()
+        );
+        assert_eq!(Ok(item.clone()), from_text(&item.to_text()));
+    }
+
+    #[test]
+    fn round_trip() {
+        let item = bencode!(["\u{1}\t\\", "", ByteString::new(vec!(0xFF)), [[]], {"ü": 0}]);
+        assert_eq!(Ok(item.clone()), from_text(&item.to_text()));
+    }
+
+    #[test]
+    fn hand_written() {
+        assert_eq!(
+            Ok(bencode!({"a": [1, 2], "b": "x"})),
+            from_text(" { \"a\" : [ 1 , 2 , ] , \"b\":\"x\" } ")
+        );
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 5: expected ':', found '1'"))), from_text("{\"a\" 1}"));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 1: dict keys must be strings"))), from_text("{1: 1}"));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 4: invalid hex byte zz"))), from_text("hex\"zz\""));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 2: unexpected '2' after the document"))), from_text("1 2"));
+        assert_eq!(Err(BencodeError::TextParse(String::from("unexpected end of text"))), from_text("[1,"));
+    }
+
+    #[test]
+    fn whitespace_inside_tokens() {
+        assert_eq!(Ok(bencode!("A")), from_text("\"\\u{41}\""));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 1: invalid escape \\u{4 1}"))), from_text("\"\\u{4 1}\""));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 1: invalid escape \\u{ 41}"))), from_text("\"\\u{ 41}\""));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 1: invalid escape \\u{+41}"))), from_text("\"\\u{+41}\""));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 3: expected '{', found ' '"))), from_text("\"\\u {41}\""));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 1: expected 'e', found ' '"))), from_text("h ex\"00\""));
+        assert_eq!(Err(BencodeError::TextParse(String::from("at 3: expected '\"', found ' '"))), from_text("hex \"00\""));
+        assert_eq!(Ok(bencode!([ByteString::new(vec!(0))])), from_text("[ hex\"00\" ]"));
+    }
+}
@@ -0,0 +1,220 @@
+use std::ops::Range;
+
+use crate::c;
+use crate::decoder::ascii_bytes_to_int;
+use crate::BencodeError;
+
+// The token layer: a flat stream of markers and leaves with their byte spans, for building
+// parsers that don't want a whole BencodeItem tree (schema-directed decoding, skipping
+// large values, pulling out a raw span to hash).
+//
+//     d3:fooi1ee  =>  DictStart 0..1, String(b"foo") 1..6, Int(1) 6..9, End 9..10
+//
+// Ints and string lengths are validated the same way parse_bytes validates them, but
+// structure isn't: matching starts with ends and keeping dict keys strings is up to the
+// caller.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind<'a> {
+    DictStart,
+    ListStart,
+    End,
+    Int(i64),
+    // borrowed straight from the input
+    String(&'a [u8]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub span: Range<usize>,
+}
+
+pub struct Tokenizer<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    peeked: Option<Token<'a>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Tokenizer { bytes, pos: 0, peeked: None }
+    }
+
+    // offset of the next token, peeked or not
+    pub fn position(&self) -> usize {
+        match &self.peeked {
+            Some(t) => t.span.start,
+            None => self.pos,
+        }
+    }
+
+    // None once the input is used up
+    pub fn next_token(&mut self) -> Result<Option<Token<'a>>, BencodeError> {
+        match self.peeked.take() {
+            Some(t) => Ok(Some(t)),
+            None => self.read(),
+        }
+    }
+
+    pub fn peek_token(&mut self) -> Result<Option<&Token<'a>>, BencodeError> {
+        if self.peeked.is_none() {
+            self.peeked = self.read()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    // consumes one whole value, nested or not, returning where it was
+    pub fn skip_value(&mut self) -> Result<Range<usize>, BencodeError> {
+        let start = self.position();
+        let mut depth = 0usize;
+        loop {
+            let token = self.next_token()?.ok_or(BencodeError::BytestreamEnded)?;
+            match token.kind {
+                TokenKind::DictStart | TokenKind::ListStart => depth += 1,
+                TokenKind::End if depth == 0 => return Err(BencodeError::UnexpectedEndMarker),
+                TokenKind::End => depth -= 1,
+                TokenKind::Int(_) | TokenKind::String(_) => (),
+            }
+            if depth == 0 {
+                return Ok(start..token.span.end)
+            }
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<Token<'a>>, BencodeError> {
+        let start = self.pos;
+        let kind = match self.bytes.get(start) {
+            None => return Ok(None),
+            Some(&c::M_DICT) => {
+                self.pos += 1;
+                TokenKind::DictStart
+            },
+            Some(&c::M_LIST) => {
+                self.pos += 1;
+                TokenKind::ListStart
+            },
+            Some(&c::M_END) => {
+                self.pos += 1;
+                TokenKind::End
+            },
+            Some(&c::M_INT) => TokenKind::Int(self.read_int()?),
+            Some(c::M_0..=c::M_9) => TokenKind::String(self.read_string()?),
+            Some(b) => return Err(BencodeError::UnrecognizedByte(format!("unrecognized byte: {}", b))),
+        };
+        Ok(Some(Token { kind, span: start..self.pos }))
+    }
+
+    fn read_int(&mut self) -> Result<i64, BencodeError> {
+        let digits_start = self.pos + 1;
+        let end = match self.bytes[digits_start..].iter().position(|&b| b == c::M_END) {
+            Some(i) => digits_start + i,
+            None => return Err(BencodeError::BytestreamEnded),
+        };
+        let digits = &self.bytes[digits_start..end];
+        match digits {
+            [] => return Err(BencodeError::UnexpectedEndMarker),
+            [c::M_DASH, c::M_0] => return Err(BencodeError::IntParseNegativeZero),
+            [c::M_0, _, ..] | [c::M_DASH, c::M_0, _, ..] => return Err(BencodeError::IntParseLeadingZero),
+            _ => (),
+        }
+        let i = ascii_bytes_to_int(digits, digits_start)?;
+        self.pos = end + 1;
+        Ok(i)
+    }
+
+    fn read_string(&mut self) -> Result<&'a [u8], BencodeError> {
+        let len_start = self.pos;
+        let colon = loop {
+            match self.bytes.get(self.pos) {
+                Some(&c::M_COLON) => break self.pos,
+                Some(c::M_0..=c::M_9) => self.pos += 1,
+                Some(_) => return Err(BencodeError::StrLenInvalidByte),
+                None => return Err(BencodeError::BytestreamEnded),
+            }
+        };
+        let len_digits = &self.bytes[len_start..colon];
+        if len_digits.len() > 1 && len_digits[0] == c::M_0 {
+            return Err(BencodeError::StrParseLeadingZero)
+        }
+        let len = usize::try_from(ascii_bytes_to_int(len_digits, len_start)?).map_err(|_| BencodeError::StrParse)?;
+        let data_start = colon + 1;
+        if self.bytes.len() - data_start < len {
+            return Err(BencodeError::BytestreamEnded)
+        }
+        self.pos = data_start + len;
+        Ok(&self.bytes[data_start..self.pos])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(bytes: &[u8]) -> Result<Vec<TokenKind<'_>>, BencodeError> {
+        let mut tokenizer = Tokenizer::new(bytes);
+        let mut res = vec!();
+        while let Some(t) = tokenizer.next_token()? {
+            res.push(t.kind);
+        }
+        Ok(res)
+    }
+
+    #[test]
+    fn tokens() {
+        let mut tokenizer = Tokenizer::new(b"d3:fooli1ei-2ee0:e");
+        let expected = vec!(
+            (TokenKind::DictStart, 0..1),
+            (TokenKind::String(b"foo"), 1..6),
+            (TokenKind::ListStart, 6..7),
+            (TokenKind::Int(1), 7..10),
+            (TokenKind::Int(-2), 10..14),
+            (TokenKind::End, 14..15),
+            (TokenKind::String(b""), 15..17),
+            (TokenKind::End, 17..18),
+        );
+        for (kind, span) in expected {
+            assert_eq!(span.start, tokenizer.position());
+            let token = Token { kind, span };
+            assert_eq!(Ok(Some(&token)), tokenizer.peek_token());
+            assert_eq!(Ok(Some(&token)), tokenizer.peek_token());
+            assert_eq!(Ok(Some(token)), tokenizer.next_token());
+        }
+        assert_eq!(Ok(None), tokenizer.peek_token());
+        assert_eq!(Ok(None), tokenizer.next_token());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Err(BencodeError::IntParseLeadingZero), kinds(b"i03e"));
+        assert_eq!(Err(BencodeError::IntParseNegativeZero), kinds(b"i-0e"));
+        assert_eq!(Err(BencodeError::IntParseLeadingZero), kinds(b"i-01e"));
+        assert_eq!(Err(BencodeError::UnexpectedEndMarker), kinds(b"ie"));
+        assert_eq!(Err(BencodeError::BytestreamEnded), kinds(b"i12"));
+        assert_eq!(Err(BencodeError::StrParseLeadingZero), kinds(b"01:a"));
+        assert_eq!(Err(BencodeError::StrLenInvalidByte), kinds(b"1x:a"));
+        assert_eq!(Err(BencodeError::BytestreamEnded), kinds(b"5:abc"));
+        assert!(matches!(kinds(b"x"), Err(BencodeError::UnrecognizedByte(_))));
+        // structure is the caller's business
+        assert_eq!(Ok(vec!(TokenKind::End, TokenKind::Int(0))), kinds(b"ei0e"));
+    }
+
+    #[test]
+    fn skip_value() {
+        // pull the raw span of "info" out without building a tree
+        let bytes = b"d8:announce3:url4:infod6:lengthi1e5:filesleee";
+        let mut tokenizer = Tokenizer::new(bytes);
+        tokenizer.next_token().unwrap();
+        let mut info = None;
+        while let Some(Token { kind: TokenKind::String(key), .. }) = tokenizer.next_token().unwrap() {
+            let span = tokenizer.skip_value().unwrap();
+            if key == b"info" {
+                info = Some(span);
+            }
+        }
+        assert_eq!(b"d6:lengthi1e5:fileslee", &bytes[info.unwrap()]);
+
+        assert_eq!(Err(BencodeError::BytestreamEnded), Tokenizer::new(b"li1e").skip_value());
+        assert_eq!(Err(BencodeError::UnexpectedEndMarker), Tokenizer::new(b"e").skip_value());
+    }
+}
@@ -0,0 +1,306 @@
+use crate::convert::in_field;
+use crate::tokenizer::{TokenKind, Tokenizer};
+use crate::{parse_bytes, AsBencodeBytes, BencodeError, BencodeItem, ByteString, FromBencode};
+
+// BEP 3 metainfo (.torrent) files, typed and checked: the parts of the format every
+// client needs, with the rest left in the BencodeItem it came from. BEP 12 announce
+// lists and the BEP 27 private flag are included; v2 (BEP 52) file trees aren't.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Torrent {
+    // missing in trackerless torrents
+    pub announce: Option<String>,
+    // tiers of tracker URLs, tried in order; empty if the key is absent
+    pub announce_list: Vec<Vec<String>>,
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+    // seconds since the Unix epoch
+    pub creation_date: Option<i64>,
+    pub info: Info,
+    // the info dict as it was encoded, for hashing
+    info_bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Info {
+    // the file name, or the directory name of a multi-file torrent
+    pub name: String,
+    pub piece_length: u64,
+    // one SHA-1 hash per piece
+    pub pieces: Vec<[u8; 20]>,
+    pub private: bool,
+    // exactly one of the two is set: `length` for a single file, `files` for several
+    pub length: Option<u64>,
+    pub files: Option<Vec<File>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    pub length: u64,
+    // directories then the file name, relative to Info::name
+    pub path: Vec<String>,
+}
+
+impl Torrent {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Torrent, BencodeError> {
+        let mut bytes_iter = bytes.iter().peekable();
+        let item = parse_bytes(&mut bytes_iter)?;
+        if bytes_iter.len() != 0 {
+            return Err(BencodeError::TrailingData { len: bytes_iter.len() })
+        }
+        let mut torrent = Torrent::from_bencode(&item)?;
+        // the info hash is over the bytes as they were, which a re-encoding of a
+        // non-canonical file wouldn't reproduce
+        if let Some(span) = info_span(bytes)? {
+            torrent.info_bytes = bytes[span].to_vec();
+        }
+        Ok(torrent)
+    }
+
+    // The info dict exactly as encoded, what the info hash is computed over. A Torrent
+    // from from_bytes has the original bytes; one from from_bencode has the re-encoding of
+    // what was parsed, which is the same unless the input was parsed leniently.
+    pub fn info_bytes(&self) -> &[u8] {
+        &self.info_bytes
+    }
+
+    // the BEP 3 info hash, the SHA-1 of info_bytes
+    #[cfg(feature = "sha1")]
+    pub fn info_hash_v1(&self) -> [u8; 20] {
+        crate::hash::digest::<crate::hash::Sha1>(&self.info_bytes)
+    }
+
+    // info_hash_v1 as 40 lowercase hex digits, as in magnet links
+    #[cfg(feature = "sha1")]
+    pub fn info_hash_v1_hex(&self) -> String {
+        ByteString::new(self.info_hash_v1().to_vec()).to_hex()
+    }
+}
+
+// where the top-level "info" value is in an encoded torrent; the first one, like
+// required_field, if the key is repeated
+fn info_span(bytes: &[u8]) -> Result<Option<std::ops::Range<usize>>, BencodeError> {
+    let mut tokenizer = Tokenizer::new(bytes);
+    if !matches!(tokenizer.next_token()?, Some(t) if t.kind == TokenKind::DictStart) {
+        return Ok(None)
+    }
+    loop {
+        match tokenizer.next_token()? {
+            Some(t) if t.kind == TokenKind::String(b"info") => return tokenizer.skip_value().map(Some),
+            Some(t) if t.kind == TokenKind::End => return Ok(None),
+            Some(_) => {
+                tokenizer.skip_value()?;
+            },
+            None => return Ok(None),
+        }
+    }
+}
+
+impl Info {
+    // saturates rather than overflowing, though an Info from from_bencode never gets there
+    pub fn total_length(&self) -> u64 {
+        self.checked_total_length().unwrap_or(u64::MAX)
+    }
+
+    // None if the file lengths add up to more than u64 can hold
+    fn checked_total_length(&self) -> Option<u64> {
+        match (&self.files, self.length) {
+            (Some(files), _) => files.iter().try_fold(0u64, |total, f| total.checked_add(f.length)),
+            (None, length) => Some(length.unwrap_or(0)),
+        }
+    }
+}
+
+impl FromBencode for Torrent {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        Ok(Torrent {
+            announce: item.optional_field("announce")?,
+            announce_list: item.optional_field("announce-list")?.unwrap_or_default(),
+            comment: item.optional_field("comment")?,
+            created_by: item.optional_field("created by")?,
+            creation_date: item.optional_field("creation date")?,
+            info: item.required_field("info")?,
+            info_bytes: item.get("info").map(AsBencodeBytes::as_bytes).unwrap_or_default(),
+        })
+    }
+}
+
+impl FromBencode for Info {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        let piece_length: u64 = item.required_field("piece length")?;
+        if piece_length == 0 {
+            return Err(in_field("piece length", BencodeError::ValueOutOfRange(String::from("must be positive"))))
+        }
+        let pieces: ByteString = item.required_field("pieces")?;
+        let pieces: Vec<[u8; 20]> = pieces.chunks_exact::<20>()
+            .map_err(|e| in_field("pieces", e))?
+            .copied()
+            .collect();
+        let info = Info {
+            name: item.required_field("name")?,
+            piece_length,
+            pieces,
+            private: item.optional_field("private")?.unwrap_or(false),
+            length: item.optional_field("length")?,
+            files: item.optional_field("files")?,
+        };
+        match (&info.length, &info.files) {
+            (Some(_), Some(_)) => {
+                return Err(BencodeError::UnexpectedType(String::from("both \"length\" and \"files\" present")))
+            },
+            (None, None) => return Err(BencodeError::MissingKey(String::from("missing key \"length\" or \"files\""))),
+            _ => (),
+        }
+        let total_length = info.checked_total_length().ok_or_else(|| {
+            in_field("files", BencodeError::ValueOutOfRange(String::from("total length doesn't fit in u64")))
+        })?;
+        let expected = total_length.div_ceil(piece_length);
+        if info.pieces.len() as u64 != expected {
+            return Err(in_field("pieces", BencodeError::ValueOutOfRange(format!(
+                "{} hashes for {} bytes in pieces of {}, expected {}",
+                info.pieces.len(), total_length, piece_length, expected
+            ))))
+        }
+        Ok(info)
+    }
+}
+
+impl FromBencode for File {
+    fn from_bencode(item: &BencodeItem) -> Result<Self, BencodeError> {
+        let file = File {
+            length: item.required_field("length")?,
+            path: item.required_field("path")?,
+        };
+        if file.path.is_empty() {
+            return Err(in_field("path", BencodeError::ValueOutOfRange(String::from("empty"))))
+        }
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bencode, fixtures, AsBencodeBytes};
+
+    #[test]
+    fn sample() {
+        let torrent = Torrent::from_bytes(&fixtures::sample_v1()).unwrap();
+        assert_eq!(Some("http://tracker.example.com/announce"), torrent.announce.as_deref());
+        assert_eq!(
+            vec!(vec!(String::from("http://tracker.example.com/announce")), vec!(String::from("udp://backup.example.com:6969"))),
+            torrent.announce_list
+        );
+        assert_eq!(Some(1_700_000_000), torrent.creation_date);
+        assert_eq!(Some("mescal"), torrent.created_by.as_deref());
+
+        let info = &torrent.info;
+        assert_eq!("sample", info.name);
+        assert_eq!(16384, info.piece_length);
+        assert_eq!(2, info.pieces.len());
+        assert!(!info.private);
+        assert_eq!(None, info.length);
+        assert_eq!(
+            Some(vec!(
+                File { length: 20000, path: vec!(String::from("docs"), String::from("readme.txt")) },
+                File { length: 12768, path: vec!(String::from("data.bin")) },
+            )),
+            info.files
+        );
+        assert_eq!(32768, info.total_length());
+    }
+
+    #[test]
+    fn single_file() {
+        let item = bencode!({
+            "info": {"length": 5, "name": "a.txt", "piece length": 4, "pieces": ByteString::new(vec!(0; 40)), "private": 1},
+        });
+        let torrent = Torrent::from_bytes(&item.as_bytes()).unwrap();
+        assert_eq!(None, torrent.announce);
+        assert!(torrent.announce_list.is_empty());
+        assert_eq!(Some(5), torrent.info.length);
+        assert_eq!(None, torrent.info.files);
+        assert!(torrent.info.private);
+    }
+
+    #[test]
+    fn validation() {
+        let check = |info: BencodeItem| Torrent::from_bencode(&bencode!({"info": info})).unwrap_err().to_string();
+
+        assert_eq!("missing key \"info\"", Torrent::from_bencode(&bencode!({})).unwrap_err().to_string());
+        assert_eq!(
+            "info: missing key \"length\" or \"files\"",
+            check(bencode!({"name": "x", "piece length": 4, "pieces": ""}))
+        );
+        assert_eq!(
+            "info: both \"length\" and \"files\" present",
+            check(bencode!({"files": [], "length": 1, "name": "x", "piece length": 4, "pieces": ByteString::new(vec!(0; 20))}))
+        );
+        assert_eq!(
+            "info: piece length: must be positive",
+            check(bencode!({"length": 1, "name": "x", "piece length": 0, "pieces": ""}))
+        );
+        assert_eq!(
+            "info: pieces: length 19 is not a multiple of 20",
+            check(bencode!({"length": 1, "name": "x", "piece length": 4, "pieces": ByteString::new(vec!(0; 19))}))
+        );
+        assert_eq!(
+            "info: pieces: 1 hashes for 5 bytes in pieces of 4, expected 2",
+            check(bencode!({"length": 5, "name": "x", "piece length": 4, "pieces": ByteString::new(vec!(0; 20))}))
+        );
+        assert_eq!(
+            "info: files: [0]: path: empty",
+            check(bencode!({"files": [{"length": 1, "path": []}], "name": "x", "piece length": 4, "pieces": ByteString::new(vec!(0; 20))}))
+        );
+        let huge = i64::MAX;
+        assert_eq!(
+            "info: files: total length doesn't fit in u64",
+            check(bencode!({
+                "files": [{"length": huge, "path": ["a"]}, {"length": huge, "path": ["b"]}, {"length": huge, "path": ["c"]}],
+                "name": "x", "piece length": 4, "pieces": ""
+            }))
+        );
+        assert_eq!(
+            "info: private: 2 isn't a bool, expected 0 or 1",
+            check(bencode!({"length": 1, "name": "x", "piece length": 4, "pieces": ByteString::new(vec!(0; 20)), "private": 2}))
+        );
+
+        let mut bytes = bencode!({"info": {"length": 1, "name": "x", "piece length": 4, "pieces": ByteString::new(vec!(0; 20))}}).as_bytes();
+        assert!(Torrent::from_bytes(&bytes).is_ok());
+        bytes.push(b'x');
+        assert_eq!(Err(BencodeError::TrailingData { len: 1 }), Torrent::from_bytes(&bytes));
+        assert_eq!("1 bytes after the end of the value", Torrent::from_bytes(&bytes).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn info_bytes() {
+        let info = b"d4:name1:x6:lengthi1e12:piece lengthi4e6:pieces20:\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0e";
+        let mut bytes = b"d8:announce7:udp://x4:info".to_vec();
+        bytes.extend_from_slice(info);
+        bytes.extend_from_slice(b"4:infoi1ee");
+
+        // keys out of order: the original bytes, not a canonical re-encoding, and the first
+        // "info" if there are two
+        let torrent = Torrent::from_bytes(&bytes).unwrap();
+        assert_eq!(&info[..], torrent.info_bytes());
+
+        let mut item = parse_bytes(&mut bytes.iter().peekable()).unwrap();
+        assert_eq!(&info[..], Torrent::from_bencode(&item).unwrap().info_bytes());
+        item.sort_keys();
+        assert_ne!(torrent.info_bytes(), Torrent::from_bencode(&item).unwrap().info_bytes());
+    }
+
+    #[cfg(feature = "sha1")]
+    #[test]
+    fn info_hash_v1() {
+        let hash = |bytes: &[u8]| Torrent::from_bytes(bytes).unwrap().info_hash_v1_hex();
+        let canonical = b"d4:infod6:lengthi1e4:name1:x12:piece lengthi4e6:pieces20:\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0ee";
+        let unsorted = b"d4:infod4:name1:x6:lengthi1e12:piece lengthi4e6:pieces20:\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0ee";
+        assert_eq!("69bdbacd506c068c12b5feb8a8636b0442870879", hash(canonical));
+        assert_eq!("aca97f649610404c32ef7d84d7c6c1604e6a10b3", hash(unsorted));
+
+        let torrent = Torrent::from_bytes(canonical).unwrap();
+        assert_eq!(crate::hash::digest::<crate::hash::Sha1>(torrent.info_bytes()), torrent.info_hash_v1());
+        assert_eq!(0x69, torrent.info_hash_v1()[0]);
+    }
+}
@@ -0,0 +1,121 @@
+use std::{mem, vec};
+
+use crate::{BencodeDict, BencodeItem};
+
+enum Frame {
+    List(vec::IntoIter<BencodeItem>, Vec<BencodeItem>),
+    // the key is the one whose value is being rebuilt
+    Dict(vec::IntoIter<(String, BencodeItem)>, BencodeDict, String),
+}
+
+impl BencodeItem {
+    // Rebuilds the tree bottom-up: `f` gets every node after its children have been
+    // through `f`, and what it returns takes the node's place. Iterative, like the encoder.
+    pub fn transform<F: FnMut(BencodeItem) -> BencodeItem>(self, mut f: F) -> BencodeItem {
+        let mut stack: Vec<Frame> = vec!();
+        let mut item = self;
+        loop {
+            let mut done = match item {
+                BencodeItem::List(l) => {
+                    let len = l.len();
+                    stack.push(Frame::List(l.into_iter(), Vec::with_capacity(len)));
+                    None
+                },
+                BencodeItem::Dict(d) => {
+                    let len = d.len();
+                    stack.push(Frame::Dict(d.into_iter(), BencodeDict::with_capacity(len), String::new()));
+                    None
+                },
+                leaf => Some(f(leaf)),
+            };
+            // hand finished nodes to their parents until there's another child to go into
+            item = loop {
+                match stack.last_mut() {
+                    None => return done.expect("a finished root"),
+                    Some(Frame::List(rest, rebuilt)) => {
+                        rebuilt.extend(done.take());
+                        if let Some(child) = rest.next() {
+                            break child;
+                        }
+                    },
+                    Some(Frame::Dict(rest, rebuilt, key)) => {
+                        if let Some(value) = done.take() {
+                            rebuilt.push(mem::take(key), value);
+                        }
+                        if let Some((k, child)) = rest.next() {
+                            *key = k;
+                            break child;
+                        }
+                    },
+                }
+                done = match stack.pop() {
+                    Some(Frame::List(_, rebuilt)) => Some(f(BencodeItem::List(rebuilt))),
+                    Some(Frame::Dict(_, rebuilt, _)) => Some(f(BencodeItem::Dict(rebuilt))),
+                    None => unreachable!(),
+                };
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform() {
+        let item = bencode!({
+            "announce": "http://old.example.com/announce",
+            "announce-list": [["http://old.example.com/announce"], ["udp://x"]],
+            "info": {"pieces": "0123456789", "length": 5},
+        });
+        let rewritten = item.transform(|node| match node.as_str() {
+            Some(s) if s.starts_with("http://old.example.com") => bencode!(s.replacen("old", "new", 1)),
+            Some(s) if s.len() > 8 => bencode!(s[..8]),
+            _ => node,
+        });
+        assert_eq!(
+            bencode!({
+                "announce": "http://new.example.com/announce",
+                "announce-list": [["http://new.example.com/announce"], ["udp://x"]],
+                "info": {"pieces": "01234567", "length": 5},
+            }),
+            rewritten
+        );
+    }
+
+    #[test]
+    fn bottom_up() {
+        let mut seen = vec!();
+        let item = bencode!([1, {"a": 2}, []]).transform(|node| {
+            seen.push(node.clone());
+            match node {
+                BencodeItem::Int(i) => bencode!(i * 10),
+                // children are already transformed
+                BencodeItem::Dict(d) => bencode!(d.len() as i64 + d.get("a").and_then(BencodeItem::as_int).unwrap()),
+                other => other,
+            }
+        });
+        assert_eq!(bencode!([10, 21, []]), item);
+        assert_eq!(vec!(bencode!(1), bencode!(2), bencode!({"a": 20}), bencode!([]), bencode!([10, 21, []])), seen);
+        assert_eq!(bencode!("x"), bencode!(1).transform(|_| bencode!("x")));
+    }
+
+    #[test]
+    fn deep() {
+        let mut item = BencodeItem::Int(0);
+        for _ in 0..200_000 {
+            item = BencodeItem::List(vec!(item));
+        }
+        let mut nodes = 0;
+        let mut next = Some(item.transform(|node| {
+            nodes += 1;
+            node
+        }));
+        assert_eq!(200_001, nodes);
+        // dismantle by hand, dropping recursively would overflow
+        while let Some(BencodeItem::List(mut l)) = next.take() {
+            next = l.pop();
+        }
+    }
+}
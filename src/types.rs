@@ -1,66 +1,663 @@
 use std::fmt;
+use std::io;
+use std::num::ParseIntError;
+use std::ops::{Deref, Index};
 use std::str::Utf8Error;
 
+use crate::{pretty, BencodeDict, PrettyPrinter};
+
+const DISPLAY_STRING_LEN: usize = 64;
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+// New variants come with new features, so matches outside the crate need a wildcard arm;
+// kind() is the stable way to tell errors apart broadly. This stays an enum rather than a
+// struct of kind plus details: code generated by mescal-derive and about two hundred sites
+// in the crate build and match variants directly, and a struct would trade that for
+// nothing kind() doesn't already give callers.
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum BencodeError {
-    FileRead(String),
+    Io(IoError),
     UnrecognizedByte(String),
     UnexpectedEndMarker,
     BytestreamEnded,
     IntParseAscii(Utf8Error),
-    IntParseInt(String),
+    // the digits as they were in the input, and where they start
+    IntParseInt { error: ParseIntError, digits: Vec<u8>, offset: usize },
     IntParseLeadingZero,
     IntParseNegativeZero,
     StrParseLeadingZero,
     StrLenInvalidByte,
     StrParse,
-    DictKeyParse
+    DictKeyParse,
+    Cancelled,
+    TimedOut,
+    Throttled,
+    MissingKey(String),
+    UnexpectedType(String),
+    ValueOutOfRange(String),
+    UnknownVariant(String),
+    DuplicateKey(String),
+    TextParse(String),
+    QueryParse(String),
+    PatchConflict(String),
+    PeerWire(String),
+    Base64(String),
+    Json(String),
+    Serde(String),
+    // a complete value followed by `len` more bytes, where one value was expected
+    TrailingData { len: usize },
+    // a parse error from open() and friends, with where in the input it went wrong
+    Parse { offset: usize, excerpt: String, error: Box<BencodeError> },
 }
 
-#[derive(Debug, PartialEq)]
+// The io::Error behind BencodeError::Io. io::Error can't be compared, so two of these are
+// equal when their io::ErrorKind is, whatever their messages say; that keeps BencodeError
+// comparable in tests, but an Io error compared equal isn't necessarily about the same file.
+#[derive(Debug)]
+pub struct IoError(pub io::Error);
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.kind() == other.0.kind()
+    }
+}
+
+impl Deref for IoError {
+    type Target = io::Error;
+
+    fn deref(&self) -> &io::Error {
+        &self.0
+    }
+}
+
+impl From<io::Error> for BencodeError {
+    fn from(e: io::Error) -> Self {
+        BencodeError::Io(IoError(e))
+    }
+}
+
+// What went wrong, broadly: whether retrying could help, or the input should be rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    // the input isn't well-formed bencode (or text form, query, base64, ...)
+    Syntax,
+    // reading or writing a file failed
+    Io,
+    // a size limit, deadline, throttle or cancellation stopped the work; the input itself
+    // may be fine
+    Limit,
+    // well-formed input that doesn't fit what was asked of it: a missing key, a wrong
+    // type, an out of range value
+    Semantic,
+}
+
+impl BencodeError {
+    // A number per variant for FFI and logs, where the enum can't be matched on. Codes
+    // are never changed or reused: a new variant takes the next free one.
+    pub fn code(&self) -> u32 {
+        match self {
+            BencodeError::Parse { error, .. } => error.code(),
+            BencodeError::Io(_) => 1,
+            BencodeError::UnrecognizedByte(_) => 2,
+            BencodeError::UnexpectedEndMarker => 3,
+            BencodeError::BytestreamEnded => 4,
+            BencodeError::IntParseAscii(_) => 5,
+            BencodeError::IntParseInt { .. } => 6,
+            BencodeError::IntParseLeadingZero => 7,
+            BencodeError::IntParseNegativeZero => 8,
+            BencodeError::StrParseLeadingZero => 9,
+            BencodeError::StrLenInvalidByte => 10,
+            BencodeError::StrParse => 11,
+            BencodeError::DictKeyParse => 12,
+            BencodeError::Cancelled => 13,
+            BencodeError::TimedOut => 14,
+            BencodeError::Throttled => 15,
+            BencodeError::MissingKey(_) => 16,
+            BencodeError::UnexpectedType(_) => 17,
+            BencodeError::ValueOutOfRange(_) => 18,
+            BencodeError::UnknownVariant(_) => 19,
+            BencodeError::DuplicateKey(_) => 20,
+            BencodeError::TextParse(_) => 21,
+            BencodeError::QueryParse(_) => 22,
+            BencodeError::PatchConflict(_) => 23,
+            BencodeError::PeerWire(_) => 24,
+            BencodeError::Base64(_) => 25,
+            BencodeError::Json(_) => 26,
+            BencodeError::Serde(_) => 27,
+            BencodeError::TrailingData { .. } => 28,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            BencodeError::Parse { error, .. } => error.kind(),
+            BencodeError::Io(_) => ErrorKind::Io,
+            BencodeError::Cancelled | BencodeError::TimedOut | BencodeError::Throttled => ErrorKind::Limit,
+            BencodeError::UnrecognizedByte(_)
+            | BencodeError::UnexpectedEndMarker
+            | BencodeError::BytestreamEnded
+            | BencodeError::IntParseAscii(_)
+            | BencodeError::IntParseInt { .. }
+            | BencodeError::IntParseLeadingZero
+            | BencodeError::IntParseNegativeZero
+            | BencodeError::StrParseLeadingZero
+            | BencodeError::StrLenInvalidByte
+            | BencodeError::DictKeyParse
+            | BencodeError::TextParse(_)
+            | BencodeError::QueryParse(_)
+            | BencodeError::PeerWire(_)
+            | BencodeError::Base64(_)
+            | BencodeError::TrailingData { .. } => ErrorKind::Syntax,
+            BencodeError::StrParse
+            | BencodeError::MissingKey(_)
+            | BencodeError::UnexpectedType(_)
+            | BencodeError::ValueOutOfRange(_)
+            | BencodeError::UnknownVariant(_)
+            | BencodeError::DuplicateKey(_)
+            | BencodeError::PatchConflict(_)
+            | BencodeError::Json(_)
+            | BencodeError::Serde(_) => ErrorKind::Semantic,
+        }
+    }
+}
+
+// The String-carrying variants hold a message that reads on its own ("expected int,
+// found string"), sometimes after a path or position, so those print as they are or
+// behind a word on what was being done.
+impl fmt::Display for BencodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BencodeError::Io(e) => e.0.fmt(f),
+            BencodeError::UnrecognizedByte(m) => f.write_str(m),
+            BencodeError::UnexpectedEndMarker => f.write_str("unexpected end marker"),
+            BencodeError::BytestreamEnded => f.write_str("input ended in the middle of a value"),
+            BencodeError::IntParseAscii(_) => f.write_str("int contains non-ASCII bytes"),
+            BencodeError::IntParseInt { error, digits, offset } => {
+                write!(f, "invalid int {:?} at byte {}: {}", String::from_utf8_lossy(digits), offset, error)
+            },
+            BencodeError::IntParseLeadingZero => f.write_str("int has a leading zero"),
+            BencodeError::IntParseNegativeZero => f.write_str("int is negative zero"),
+            BencodeError::StrParseLeadingZero => f.write_str("string length has a leading zero"),
+            BencodeError::StrLenInvalidByte => f.write_str("string length contains a non-digit"),
+            BencodeError::StrParse => f.write_str("string isn't valid UTF-8"),
+            BencodeError::DictKeyParse => f.write_str("dict key isn't a valid UTF-8 string"),
+            BencodeError::Cancelled => f.write_str("cancelled"),
+            BencodeError::TimedOut => f.write_str("timed out"),
+            BencodeError::Throttled => f.write_str("throttled"),
+            BencodeError::MissingKey(m) | BencodeError::UnexpectedType(m) | BencodeError::ValueOutOfRange(m) => f.write_str(m),
+            BencodeError::UnknownVariant(m) => write!(f, "unknown variant \"{}\"", m),
+            BencodeError::DuplicateKey(m) => write!(f, "duplicate key: {}", m),
+            BencodeError::TextParse(m) => write!(f, "invalid text form: {}", m),
+            BencodeError::QueryParse(m) => write!(f, "invalid query: {}", m),
+            BencodeError::PatchConflict(m) => write!(f, "patch doesn't apply: {}", m),
+            BencodeError::PeerWire(m) => write!(f, "peer wire: {}", m),
+            BencodeError::Base64(m) => write!(f, "invalid base64: {}", m),
+            BencodeError::Json(m) => write!(f, "json: {}", m),
+            BencodeError::Serde(m) => f.write_str(m),
+            BencodeError::TrailingData { len } => write!(f, "{} bytes after the end of the value", len),
+            BencodeError::Parse { offset, excerpt, error } => write!(f, "{} near byte {}: {}", error, offset, excerpt),
+        }
+    }
+}
+
+pub type Result<T, E = BencodeError> = std::result::Result<T, E>;
+
+impl std::error::Error for BencodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BencodeError::IntParseAscii(e) => Some(e),
+            BencodeError::IntParseInt { error, .. } => Some(error),
+            BencodeError::Io(e) => Some(&e.0),
+            BencodeError::Parse { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+// ordered by raw bytes, so it can key a BTreeMap
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ByteString {
     pub bytes: Vec<u8>
 }
 
 impl ByteString {
     pub fn new(bytes: Vec<u8>) -> Self {
-        ByteString { bytes: bytes }
+        ByteString { bytes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn as_utf8(&self) -> Option<&str> {
+        std::str::from_utf8(&self.bytes).ok()
+    }
+
+    // lowercase, two digits per byte; how info hashes and peer ids are usually shown
+    pub fn to_hex(&self) -> String {
+        let mut out = String::with_capacity(self.bytes.len() * 2);
+        for b in &self.bytes {
+            out.push(HEX_DIGITS[(b >> 4) as usize] as char);
+            out.push(HEX_DIGITS[(b & 0xF) as usize] as char);
+        }
+        out
+    }
+
+    // either case; None unless it's an even number of hex digits
+    pub fn from_hex(hex: &str) -> Option<ByteString> {
+        // from_str_radix alone would take "+f"
+        if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None
+        }
+        let bytes = (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap());
+        Some(ByteString::new(bytes.collect()))
+    }
+
+    // the string split into N-byte arrays, e.g. `pieces.chunks_exact::<20>()` for the SHA-1
+    // hashes of a v1 torrent; errors instead of dropping a short tail like the slice
+    // method would
+    pub fn chunks_exact<const N: usize>(&self) -> Result<impl Iterator<Item = &[u8; N]>, BencodeError> {
+        if N == 0 || !self.bytes.len().is_multiple_of(N) {
+            return Err(BencodeError::ValueOutOfRange(format!("length {} is not a multiple of {}", self.bytes.len(), N)))
+        }
+        Ok(self.bytes.chunks_exact(N).map(|c| <&[u8; N]>::try_from(c).unwrap()))
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl Deref for ByteString {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl AsRef<[u8]> for ByteString {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+// Equality and hashing both see dicts as ordered lists of entries, so two dicts that
+// differ only in key order are different items with different hashes.
+//
+// The order is total: strings < ints < lists < dicts across variants, and within one,
+// strings compare by raw bytes, ints numerically, lists element by element, and dicts
+// entry by entry (key bytes, then value) in their stored order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum BencodeItem {
     String(ByteString),
     Int(i64),
     List(Vec<BencodeItem>),
-    Dict(Vec<(String, BencodeItem)>)
+    Dict(BencodeDict)
+}
+
+impl BencodeItem {
+    // "string", "int", "list" or "dict", for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            BencodeItem::String(_) => "string",
+            BencodeItem::Int(_) => "int",
+            BencodeItem::List(_) => "list",
+            BencodeItem::Dict(_) => "dict",
+        }
+    }
+
+    // None unless the item is a string holding valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_raw_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    // not `as_bytes`, which AsBencodeBytes already uses for the encoding
+    pub fn as_raw_bytes(&self) -> Option<&[u8]> {
+        match self {
+            BencodeItem::String(s) => Some(&s.bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw_bytes_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match self {
+            BencodeItem::String(s) => Some(&mut s.bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            BencodeItem::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_int_mut(&mut self) -> Option<&mut i64> {
+        match self {
+            BencodeItem::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[BencodeItem]> {
+        match self {
+            BencodeItem::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<BencodeItem>> {
+        match self {
+            BencodeItem::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&BencodeDict> {
+        match self {
+            BencodeItem::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict_mut(&mut self) -> Option<&mut BencodeDict> {
+        match self {
+            BencodeItem::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    // the first entry under `key`, if this is a dict
+    pub fn get(&self, key: &str) -> Option<&BencodeItem> {
+        self.as_dict()?.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut BencodeItem> {
+        self.as_dict_mut()?.get_mut(key)
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&BencodeItem> {
+        self.as_list()?.get(index)
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut BencodeItem> {
+        self.as_list_mut()?.get_mut(index)
+    }
+}
+
+// item["info"]["files"][0]; panics on a missing key, an out of range index or the wrong
+// variant, like indexing a map or slice would
+impl Index<&str> for BencodeItem {
+    type Output = BencodeItem;
+
+    fn index(&self, key: &str) -> &BencodeItem {
+        match self.get(key) {
+            Some(v) => v,
+            None => panic!("no key \"{}\" in {}", key, self.type_name()),
+        }
+    }
+}
+
+impl Index<usize> for BencodeItem {
+    type Output = BencodeItem;
+
+    fn index(&self, index: usize) -> &BencodeItem {
+        match self.get_index(index) {
+            Some(v) => v,
+            None => panic!("no index {} in {}", index, self.type_name()),
+        }
+    }
+}
+
+// item["private"] == 1, item["name"] == "x"; always false for the other variants
+impl PartialEq<i64> for BencodeItem {
+    fn eq(&self, other: &i64) -> bool {
+        self.as_int() == Some(*other)
+    }
+}
+
+impl PartialEq<&str> for BencodeItem {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_raw_bytes() == Some(other.as_bytes())
+    }
+}
+
+impl PartialEq<&[u8]> for BencodeItem {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.as_raw_bytes() == Some(*other)
+    }
+}
+
+impl PartialEq<&str> for ByteString {
+    fn eq(&self, other: &&str) -> bool {
+        self.bytes == other.as_bytes()
+    }
+}
+
+impl PartialEq<&[u8]> for ByteString {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.bytes == *other
+    }
+}
+
+impl PartialEq<str> for ByteString {
+    fn eq(&self, other: &str) -> bool {
+        self.bytes == other.as_bytes()
+    }
 }
 
+impl PartialEq<[u8]> for ByteString {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.bytes == other
+    }
+}
+
+// Short strings in full and anything longer, like `pieces`, cut to a preview. `{:#}`
+// prints the whole tree as compact JSON instead.
 impl fmt::Display for BencodeItem {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            BencodeItem::String(s) => {
-                if let Ok(s) = String::try_from(s) {
-                    write!(f, "\"{}\"", s)
-                } else {
-                    write!(f, "Bytes(len={})", s.bytes.len())
-                }
-            },
-            BencodeItem::Int(i) => write!(f, "{}", i),
-            BencodeItem::List(l) => {
-                write!(f, "[")?;
-                for item in l {
-                    write!(f, "{},", item)?;
-                }
-                write!(f, "]")
-            },
-            BencodeItem::Dict(d) => {
-                write!(f, "{{\n")?;
-                for (key, value) in &*d {
-                    write!(f, " \"{}\": {},\n", key, value)?;
-                }
-                write!(f, "\n}}")
-            }
+        if f.alternate() {
+            return pretty::write_json(self, f)
         }
+        PrettyPrinter::new().max_string_len(DISPLAY_STRING_LEN).write(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index() {
+        let mut item = bencode!({"info": {"files": [{"path": ["a", "b"]}]}});
+        assert_eq!(bencode!(["a", "b"]), item["info"]["files"][0]["path"]);
+        assert_eq!(Some(&bencode!("b")), item.get("info").and_then(|i| i.get("files")).and_then(|f| f.get_index(0))
+            .and_then(|f| f.get("path")).and_then(|p| p.get_index(1)));
+        assert_eq!(None, item.get("missing"));
+        assert_eq!(None, item["info"].get_index(0));
+
+        *item.get_mut("info").unwrap().get_mut("files").unwrap().get_index_mut(0).unwrap() = bencode!(1);
+        assert_eq!(bencode!({"info": {"files": [1]}}), item);
+    }
+
+    #[test]
+    #[should_panic(expected = "no key \"x\" in dict")]
+    fn index_missing_key() {
+        let _ = &bencode!({"a": 1})["x"];
+    }
+
+    #[test]
+    #[should_panic(expected = "no index 0 in int")]
+    fn index_wrong_variant() {
+        let _ = &bencode!(1)[0];
+    }
+
+    #[test]
+    // comparing with &[u8] tests that impl, not a needless borrow
+    #[allow(clippy::op_ref)]
+    fn primitive_eq() {
+        let item = bencode!({"private": 1, "name": "x", "pieces": ByteString::new(vec!(0xFF))});
+        assert!(item["private"] == 1);
+        assert!(item["private"] != 2);
+        assert!(item["name"] == "x");
+        assert!(item["name"] != 1);
+        assert!(item["pieces"] == &[0xFF][..]);
+        assert!(item != "x");
+        let name = ByteString::from("x");
+        assert!(name == "x");
+        assert!(ByteString::new(vec!(0xFF)) == &[0xFF][..]);
+        assert!(ByteString::new(vec!(0xFF)) == [0xFF][..]);
+    }
+
+    #[test]
+    fn byte_string() {
+        let s = ByteString::new(vec!(0x8A, 0x0F, 0x00));
+        assert_eq!(3, s.len());
+        assert!(!s.is_empty());
+        assert!(ByteString::new(vec!()).is_empty());
+        assert_eq!("8a0f00", s.to_hex());
+        let all = ByteString::new((0..=255).collect());
+        assert_eq!(all.bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>(), all.to_hex());
+        assert_eq!(Some(s.clone()), ByteString::from_hex("8A0f00"));
+        assert_eq!(None, ByteString::from_hex("8a0"));
+        assert_eq!(None, ByteString::from_hex("+f"));
+        assert_eq!(None, s.as_utf8());
+        assert_eq!(Some("x"), ByteString::from("x").as_utf8());
+        assert_eq!(Some(&0x8A), s.first());
+        assert_eq!(&[0x0F, 0x00], &s[1..]);
+        assert_eq!(&[0x8A, 0x0F, 0x00][..], AsRef::<[u8]>::as_ref(&s));
+    }
+
+    #[test]
+    fn chunks_exact() {
+        let pieces = ByteString::new((0..6).collect());
+        assert_eq!(vec!(&[0, 1, 2], &[3, 4, 5]), pieces.chunks_exact::<3>().unwrap().collect::<Vec<_>>());
+        assert_eq!(6, pieces.chunks_exact::<1>().unwrap().count());
+        assert_eq!(0, ByteString::new(vec!()).chunks_exact::<20>().unwrap().count());
+        assert_eq!(
+            Some(BencodeError::ValueOutOfRange(String::from("length 6 is not a multiple of 4"))),
+            pieces.chunks_exact::<4>().err()
+        );
+        assert!(pieces.chunks_exact::<0>().is_err());
+    }
+
+    #[test]
+    fn ordering() {
+        let mut items = vec!(
+            bencode!({"a": 1}), bencode!([2]), bencode!(-1), bencode!("b"), bencode!([1, 5]),
+            bencode!(ByteString::new(vec!(0xFF))), bencode!(10), bencode!({"a": 0, "b": 0}), bencode!(""),
+        );
+        items.sort();
+        assert_eq!(
+            vec!(
+                bencode!(""), bencode!("b"), bencode!(ByteString::new(vec!(0xFF))), bencode!(-1), bencode!(10),
+                bencode!([1, 5]), bencode!([2]), bencode!({"a": 0, "b": 0}), bencode!({"a": 1}),
+            ),
+            items
+        );
+
+        let set: std::collections::BTreeSet<BencodeItem> = [bencode!(2), bencode!(1), bencode!(2)].into_iter().collect();
+        assert_eq!(vec!(&bencode!(1), &bencode!(2)), set.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn hash_set() {
+        use std::collections::HashSet;
+
+        let a = crate::parse_bytes(&mut b"d1:ai1e1:bi2ee".iter().peekable()).unwrap();
+        let reordered = crate::parse_bytes(&mut b"d1:bi2e1:ai1ee".iter().peekable()).unwrap();
+        let mut set = HashSet::new();
+        set.insert(a.clone());
+        assert!(set.contains(&a));
+        // key order is significant
+        assert!(!set.contains(&reordered));
+    }
+
+    #[test]
+    fn display() {
+        let item = bencode!({"name": "a", "pieces": ByteString::new(vec!(0xAB; 1000))});
+        assert_eq!(
+            format!("{{\n  \"name\": \"a\",\n  \"pieces\": hex\"{}\"… (1000 bytes)\n}}", "ab".repeat(64)),
+            item.to_string()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        use std::error::Error;
+
+        let e = crate::parse_bytes(&mut b"i1xe".iter().peekable()).unwrap_err();
+        assert_eq!("invalid int \"1x\" at byte 1: invalid digit found in string", e.to_string());
+        assert!(matches!(e, BencodeError::IntParseInt { ref digits, offset: 1, .. } if digits == b"1x"));
+        assert_eq!(Some(String::from("invalid digit found in string")), e.source().map(|s| s.to_string()));
+        assert_eq!("int has a leading zero", BencodeError::IntParseLeadingZero.to_string());
+        assert_eq!(
+            "info: piece length: expected int, found string",
+            BencodeError::UnexpectedType(String::from("info: piece length: expected int, found string")).to_string()
+        );
+        assert_eq!("unknown variant \"Pong\"", BencodeError::UnknownVariant(String::from("Pong")).to_string());
+        assert_eq!("invalid query: at 3: expected ']'", BencodeError::QueryParse(String::from("at 3: expected ']'")).to_string());
+
+        let bytes = vec!(0xFF);
+        let utf8 = std::str::from_utf8(&bytes).unwrap_err();
+        let e = BencodeError::IntParseAscii(utf8);
+        assert_eq!(Some(utf8.to_string()), e.source().map(|s| s.to_string()));
+        assert!(BencodeError::StrParse.source().is_none());
+
+        assert_eq!(ErrorKind::Syntax, BencodeError::IntParseLeadingZero.kind());
+        assert_eq!(ErrorKind::Limit, BencodeError::TimedOut.kind());
+        assert_eq!(ErrorKind::Semantic, BencodeError::MissingKey(String::from("x")).kind());
+        let missing = crate::open("/nonexistent/x.torrent").unwrap_err();
+        assert_eq!(ErrorKind::Io, missing.kind());
+        assert!(matches!(&missing, BencodeError::Io(e) if e.kind() == std::io::ErrorKind::NotFound));
+        assert!(missing.source().is_some());
+        assert!(missing.to_string().starts_with("/nonexistent/x.torrent: "), "{}", missing);
+        assert_eq!(BencodeError::from(std::io::Error::from(std::io::ErrorKind::NotFound)), missing);
+
+        assert_eq!(1, missing.code());
+        assert_eq!(7, BencodeError::IntParseLeadingZero.code());
+        assert_eq!(27, BencodeError::Serde(String::from("x")).code());
+        assert_eq!(28, BencodeError::TrailingData { len: 1 }.code());
+        let wrapped = BencodeError::Parse { offset: 0, excerpt: String::new(), error: Box::new(BencodeError::StrParse) };
+        assert_eq!(11, wrapped.code());
+        let limited = crate::parse_bytes_with_options(&mut b"li1ei2ee".iter().peekable(), &crate::DecodeOptions::new().max_nodes(2));
+        assert_eq!(Some(ErrorKind::Limit), limited.err().map(|e| e.kind()));
+
+        // composes with ? into Box<dyn Error>
+        let boxed = || -> Result<(), Box<dyn Error>> {
+            crate::parse_bytes(&mut b"x".iter().peekable())?;
+            Ok(())
+        };
+        assert_eq!("unrecognized byte: 120", boxed().unwrap_err().to_string());
+
+        // and file and parse errors mix under ? without conversions
+        let mixed = |path: &str| -> Result<BencodeItem> {
+            let bytes = std::fs::read(path)?;
+            crate::parse_bytes(&mut bytes.iter().peekable())
+        };
+        assert_eq!(Err(BencodeError::from(std::io::Error::from(std::io::ErrorKind::NotFound))), mixed("/nonexistent/x.torrent"));
+    }
+
+    #[test]
+    fn accessors() {
+        let mut item = bencode!({"name": "a", "raw": ByteString::new(vec!(0xFF)), "length": 3, "files": [1]});
+        let dict = item.as_dict().unwrap();
+        assert_eq!(Some("a"), dict.get("name").and_then(BencodeItem::as_str));
+        assert_eq!(None, dict.get("raw").and_then(BencodeItem::as_str));
+        assert_eq!(Some(&[0xFF][..]), dict.get("raw").and_then(BencodeItem::as_raw_bytes));
+        assert_eq!(Some(3), dict.get("length").and_then(BencodeItem::as_int));
+        assert_eq!(Some(&[bencode!(1)][..]), dict.get("files").and_then(BencodeItem::as_list));
+        assert_eq!(None, dict.get("files").and_then(BencodeItem::as_int));
+        assert_eq!(None, item.as_list());
+
+        let dict = item.as_dict_mut().unwrap();
+        *dict.get_mut("length").and_then(BencodeItem::as_int_mut).unwrap() += 1;
+        dict.get_mut("files").and_then(BencodeItem::as_list_mut).unwrap().push(bencode!(2));
+        dict.get_mut("name").and_then(BencodeItem::as_raw_bytes_mut).unwrap().push(b'b');
+        assert_eq!(bencode!({"name": "ab", "raw": ByteString::new(vec!(0xFF)), "length": 4, "files": [1, 2]}), item);
     }
 }
@@ -0,0 +1,157 @@
+use crate::{BencodeItem, PathSegment};
+
+// Depth-first, in document order: a node comes before its children, and children in the
+// order they're stored. Uses an explicit stack, so deep trees don't overflow.
+pub struct Walk<'a> {
+    stack: Vec<(Vec<PathSegment<'a>>, &'a BencodeItem)>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (Vec<PathSegment<'a>>, &'a BencodeItem);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, item) = self.stack.pop()?;
+        match item {
+            BencodeItem::List(l) => {
+                for (i, child) in l.iter().enumerate().rev() {
+                    self.stack.push((with_segment(&path, PathSegment::Index(i)), child));
+                }
+            },
+            BencodeItem::Dict(d) => {
+                for (k, child) in d.iter().rev() {
+                    self.stack.push((with_segment(&path, PathSegment::Key(k)), child));
+                }
+            },
+            _ => (),
+        }
+        Some((path, item))
+    }
+}
+
+impl BencodeItem {
+    // the item itself comes first, with an empty path
+    pub fn walk(&self) -> Walk<'_> {
+        Walk { stack: vec!((vec!(), self)) }
+    }
+
+    // every node `pred` accepts, in walk order
+    pub fn find_all<F>(&self, mut pred: F) -> Vec<(Vec<PathSegment<'_>>, &BencodeItem)>
+    where
+        F: FnMut(&[PathSegment], &BencodeItem) -> bool,
+    {
+        self.walk().filter(|(path, node)| pred(path, node)).collect()
+    }
+
+    // Same order as walk. `f` sees each node before its children, so whatever it leaves
+    // behind is what gets walked next.
+    pub fn walk_mut<F: FnMut(&[PathSegment], &mut BencodeItem)>(&mut self, mut f: F) {
+        let mut stack: Vec<(Vec<PathSegment>, &mut BencodeItem)> = vec!((vec!(), self));
+        while let Some((path, item)) = stack.pop() {
+            f(&path, item);
+            match item {
+                BencodeItem::List(l) => {
+                    for (i, child) in l.iter_mut().enumerate().rev() {
+                        stack.push((with_segment(&path, PathSegment::Index(i)), child));
+                    }
+                },
+                BencodeItem::Dict(d) => {
+                    for (k, child) in d.iter_mut().rev() {
+                        let k: &String = k;
+                        stack.push((with_segment(&path, PathSegment::Key(k)), child));
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+}
+
+pub(crate) fn with_segment<'a>(path: &[PathSegment<'a>], segment: PathSegment<'a>) -> Vec<PathSegment<'a>> {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ByteString;
+
+    #[test]
+    fn walk() {
+        let item = bencode!({"a": [1, {"b": "x"}], "c": 2});
+        let walked: Vec<_> = item.walk().collect();
+        let paths: Vec<_> = walked.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            vec!(
+                vec!(),
+                vec!(PathSegment::Key("a")),
+                vec!(PathSegment::Key("a"), PathSegment::Index(0)),
+                vec!(PathSegment::Key("a"), PathSegment::Index(1)),
+                vec!(PathSegment::Key("a"), PathSegment::Index(1), PathSegment::Key("b")),
+                vec!(PathSegment::Key("c")),
+            ),
+            paths
+        );
+        for (path, node) in &walked {
+            assert_eq!(Some(*node), item.get_segments(path));
+        }
+        // string bytes over the whole tree
+        let total: usize = item.walk().filter_map(|(_, node)| node.as_raw_bytes()).map(<[u8]>::len).sum();
+        assert_eq!(1, total);
+    }
+
+    #[test]
+    fn find_all() {
+        let item = bencode!({
+            "announce": "udp://a",
+            "info": {"pieces": ByteString::new(vec!(0xFF, 0x00)), "source": "http://b", "name": "http"},
+        });
+        let urls = item.find_all(|_, node| node.as_str().is_some_and(|s| s.contains("://")));
+        assert_eq!(
+            vec!(
+                (vec!(PathSegment::Key("announce")), &bencode!("udp://a")),
+                (vec!(PathSegment::Key("info"), PathSegment::Key("source")), &bencode!("http://b")),
+            ),
+            urls
+        );
+        let binary = item.find_all(|_, node| node.as_raw_bytes().is_some() && node.as_str().is_none());
+        assert_eq!(vec!(vec!(PathSegment::Key("info"), PathSegment::Key("pieces"))), binary.into_iter().map(|(p, _)| p).collect::<Vec<_>>());
+        assert_eq!(2, item.find_all(|path, _| path.len() == 1).len());
+    }
+
+    #[test]
+    fn walk_mut() {
+        let mut item = bencode!({"announce": "udp://secret", "info": {"private": 1, "files": [{"x": "udp://secret"}]}});
+        let mut visited = 0;
+        item.walk_mut(|path, node| {
+            visited += 1;
+            if node.as_str() == Some("udp://secret") {
+                *node = bencode!("<redacted>");
+            }
+            // replacing a node means its old children are never visited
+            if path == [PathSegment::Key("info"), PathSegment::Key("files")] {
+                *node = bencode!([]);
+            }
+        });
+        assert_eq!(bencode!({"announce": "<redacted>", "info": {"private": 1, "files": []}}), item);
+        assert_eq!(5, visited);
+    }
+
+    #[test]
+    fn deep() {
+        let mut item = BencodeItem::Int(0);
+        for _ in 0..10_000 {
+            item = BencodeItem::List(vec!(item));
+        }
+        assert_eq!(10_001, item.walk().count());
+        let mut depth = 0;
+        item.walk_mut(|path, _| depth = depth.max(path.len()));
+        assert_eq!(10_000, depth);
+        // dismantle by hand, dropping recursively would overflow
+        let mut next = Some(item);
+        while let Some(BencodeItem::List(mut l)) = next.take() {
+            next = l.pop();
+        }
+    }
+}